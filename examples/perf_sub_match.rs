@@ -0,0 +1,109 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+extern crate log;
+extern crate env_logger;
+extern crate byteorder;
+extern crate scaproust;
+
+use std::io::*;
+use std::time::*;
+use std::thread;
+use std::str::FromStr;
+
+use byteorder::{BigEndian, WriteBytesExt};
+
+use scaproust::*;
+
+fn create_session() -> Session {
+    SessionBuilder::new().
+        with("inproc", Inproc).
+        build().expect("Failed to create session !")
+}
+
+fn usage(program: &str) -> ! {
+    let _ = writeln!(stderr(), "Usage: {} <topic-count> <msg-size> <msg-count>", program);
+    std::process::exit(1)
+}
+
+fn make_topic(id: u64) -> Vec<u8> {
+    let mut topic = Vec::with_capacity(8);
+    topic.write_u64::<BigEndian>(id).unwrap();
+    topic
+}
+
+fn make_msg(id: u64, msg_size: usize) -> Vec<u8> {
+    let mut msg = make_topic(id);
+    msg.resize(msg_size, 0);
+    msg
+}
+
+fn main() {
+    env_logger::init().unwrap();
+
+    let os_args: Vec<_> = std::env::args().collect();
+    let args: Vec<&str> = os_args.iter().map(|x| x.as_ref()).collect();
+    let program = args[0];
+
+    if args.len() != 4 {
+        usage(program);
+    }
+
+    let topic_count = u64::from_str(args[1]).expect("Failed to parse topic-count");
+    let msg_size = usize::from_str(args[2]).expect("Failed to parse msg-size");
+    let msg_count = usize::from_str(args[3]).expect("Failed to parse msg-count");
+
+    if topic_count == 0 || msg_size < 8 {
+        usage(program);
+    }
+
+    let url = "inproc://perf_sub_match";
+    let mut session = create_session();
+    let mut publ = session.create_socket::<Pub>().expect("Failed to create socket !");
+    let mut sub = session.create_socket::<Sub>().expect("Failed to create socket !");
+
+    // Odd ids are subscribed and even ids are published, so that
+    // the subscriber has to filter out every other message
+    for id in 0..topic_count {
+        sub.subscribe(&make_topic(id * 2 + 1)).expect("Failed to subscribe !");
+    }
+
+    sub.set_recv_timeout(Some(Duration::from_millis(500))).expect("Failed to set recv timeout !");
+    publ.bind(url).expect("Failed to bind socket !");
+    sub.connect(url).expect("Failed to connect socket !");
+
+    thread::sleep(Duration::from_millis(250));
+
+    let publisher = thread::spawn(move || {
+        for i in 0..msg_count as u64 {
+            let id = i % (topic_count * 2);
+            publ.send(make_msg(id, msg_size)).expect("Failed to send !");
+        }
+        publ
+    });
+
+    let start = Instant::now();
+    let mut last = start;
+    let mut received = 0;
+    while sub.recv().is_ok() {
+        received += 1;
+        last = Instant::now();
+    }
+
+    let _ = publisher.join();
+
+    let elapsed = last.duration_since(start);
+    let seconds = elapsed.as_secs() as f64;
+    let nanos = elapsed.subsec_nanos() as f64;
+    let elapsed_seconds = seconds + nanos / 1_000_000_000f64;
+    let msg_per_sec = msg_count as f64 / elapsed_seconds;
+
+    println!("subscription count: {}", topic_count);
+    println!("message size: {} [B]", msg_size);
+    println!("message count: {}", msg_count);
+    println!("message received: {}", received);
+    println!("throughput: {:0.0} [msg/s]", msg_per_sec);
+}
@@ -0,0 +1,132 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use time;
+
+use super::{BuildIdHasher, EndpointId};
+
+/// Computes the delay before each new attempt to re-establish an endpoint.
+/// The interval starts at `retry_ivl` and is doubled after each failed attempt,
+/// until `retry_ivl_max` is reached. The actual delay is randomly shortened
+/// by up to half of the interval, so that peers disconnected at the same time
+/// do not all retry at the same time.
+pub struct Backoff {
+    ivls: HashMap<EndpointId, Duration, BuildIdHasher>,
+    state: u64
+}
+
+impl Backoff {
+    pub fn new() -> Backoff {
+        let seed = time::precise_time_ns() ^ ((time::get_time().nsec as u64) << 32);
+
+        Backoff {
+            ivls: HashMap::default(),
+            state: seed | 1
+        }
+    }
+
+    /// Returns the delay to wait before the next attempt for the specified endpoint.
+    pub fn next_delay(&mut self, eid: EndpointId, ivl: Duration, ivl_max: Option<Duration>) -> Duration {
+        let next_ivl = match self.ivls.get(&eid) {
+            Some(prev_ivl) => double_ivl(*prev_ivl, ivl, ivl_max),
+            None => ivl
+        };
+
+        self.ivls.insert(eid, next_ivl);
+
+        next_ivl - self.jitter(next_ivl / 2)
+    }
+
+    /// Forgets the attempts made for the specified endpoint,
+    /// should be called once it has been successfully re-established.
+    pub fn reset(&mut self, eid: EndpointId) {
+        self.ivls.remove(&eid);
+    }
+
+    fn jitter(&mut self, max: Duration) -> Duration {
+        let max_nanos = max.as_secs() * 1_000_000_000 + u64::from(max.subsec_nanos());
+
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+
+        Duration::from_nanos(self.state % (max_nanos + 1))
+    }
+}
+
+fn double_ivl(prev_ivl: Duration, ivl: Duration, ivl_max: Option<Duration>) -> Duration {
+    match ivl_max {
+        Some(max) if max > ivl => prev_ivl.checked_mul(2).map_or(max, |x| if x > max { max } else { x }),
+        _ => ivl
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use core::EndpointId;
+
+    fn ms(x: u64) -> Duration {
+        Duration::from_millis(x)
+    }
+
+    #[test]
+    fn delay_should_stay_between_half_and_whole_interval() {
+        let mut backoff = Backoff::new();
+
+        for _ in 0..100 {
+            let delay = backoff.next_delay(EndpointId::from(1), ms(100), None);
+
+            assert!(delay >= ms(50));
+            assert!(delay <= ms(100));
+        }
+    }
+
+    #[test]
+    fn interval_should_be_doubled_up_to_max() {
+        let mut backoff = Backoff::new();
+        let eid = EndpointId::from(1);
+        let expected_ivls = [100, 200, 400, 500, 500];
+
+        for expected_ivl in &expected_ivls {
+            let delay = backoff.next_delay(eid, ms(100), Some(ms(500)));
+
+            assert!(delay >= ms(*expected_ivl) / 2);
+            assert!(delay <= ms(*expected_ivl));
+        }
+    }
+
+    #[test]
+    fn max_lower_than_interval_should_be_ignored() {
+        let mut backoff = Backoff::new();
+        let eid = EndpointId::from(1);
+
+        backoff.next_delay(eid, ms(100), Some(ms(50)));
+
+        assert!(backoff.next_delay(eid, ms(100), Some(ms(50))) <= ms(100));
+    }
+
+    #[test]
+    fn reset_should_restart_from_interval() {
+        let mut backoff = Backoff::new();
+        let eid = EndpointId::from(1);
+        let other_eid = EndpointId::from(2);
+
+        backoff.next_delay(eid, ms(100), Some(ms(10000)));
+        backoff.next_delay(eid, ms(100), Some(ms(10000)));
+        backoff.next_delay(other_eid, ms(100), Some(ms(10000)));
+        backoff.next_delay(other_eid, ms(100), Some(ms(10000)));
+        backoff.reset(eid);
+
+        assert!(backoff.next_delay(eid, ms(100), Some(ms(10000))) <= ms(100));
+        assert!(backoff.next_delay(other_eid, ms(100), Some(ms(10000))) >= ms(200));
+    }
+}
@@ -0,0 +1,513 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+/*
+Per pipe message queues sitting between the protocol and the network.
+
+The protocol sees a buffered pipe as ready to send as long as its send queue has room,
+the message is acknowledged as soon as it is queued, and the queue is flushed to the network
+each time the network pipe becomes writable.
+
+The recv queue is filled each time the network pipe is readable and has room,
+the protocol is told the pipe is readable as long as the queue holds a message.
+
+Acknowledgements and readiness changes produced while the protocol is sending or receiving
+cannot be delivered right away, they are stored as notifications that the socket
+replays once the protocol call has returned.
+*/
+
+use std::collections::{HashMap, VecDeque};
+use std::cmp;
+use std::fmt;
+use std::rc::Rc;
+use std::io::Result;
+use std::time::Duration;
+
+use super::{BuildIdHasher, SocketId, EndpointId, Message, EndpointTmpl, Scheduled};
+use super::network::Network;
+use super::context::{Context, Scheduler, Schedulable, Event};
+
+pub enum Notification {
+    SendAck(EndpointId),
+    SendReady(EndpointId),
+    RecvAck(EndpointId, Message),
+    RecvReady(EndpointId)
+}
+
+pub struct PipeBuffers {
+    buffers: HashMap<EndpointId, PipeBuffer, BuildIdHasher>,
+    notifications: VecDeque<Notification>
+}
+
+pub struct PipeBuffer {
+    send: Option<SendQueue>,
+    recv: Option<RecvQueue>
+}
+
+struct SendQueue {
+    capacity: usize,
+    msgs: VecDeque<Rc<Message>>,
+    len: usize,
+    net_ready: bool,
+    net_busy: bool,
+    proto_ready: bool
+}
+
+struct RecvQueue {
+    capacity: usize,
+    msgs: VecDeque<Message>,
+    len: usize,
+    net_ready: bool,
+    net_busy: bool,
+    proto_ready: bool,
+    proto_pending: bool
+}
+
+/// Network facade handed to the protocol, redirecting sends and recvs of buffered pipes to their queues.
+pub struct BufferedContext<'a> {
+    ctx: &'a mut Context,
+    buffers: &'a mut PipeBuffers
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* PipeBuffers                                                               */
+/*                                                                           */
+/*****************************************************************************/
+
+impl PipeBuffers {
+    pub fn new() -> PipeBuffers {
+        PipeBuffers {
+            buffers: HashMap::default(),
+            notifications: VecDeque::new()
+        }
+    }
+    pub fn insert(&mut self, eid: EndpointId, send_capacity: usize, recv_capacity: usize) {
+        if send_capacity > 0 || recv_capacity > 0 {
+            self.buffers.insert(eid, PipeBuffer::new(send_capacity, recv_capacity));
+        }
+    }
+    pub fn remove(&mut self, eid: EndpointId) {
+        if self.buffers.remove(&eid).is_some() {
+            self.notifications.retain(|n| n.endpoint_id() != eid);
+        }
+    }
+    pub fn is_send_buffered(&self, eid: EndpointId) -> bool {
+        self.buffers.get(&eid).map_or(false, |b| b.send.is_some())
+    }
+    pub fn is_recv_buffered(&self, eid: EndpointId) -> bool {
+        self.buffers.get(&eid).map_or(false, |b| b.recv.is_some())
+    }
+    pub fn has_pending_send(&self) -> bool {
+        self.buffers.values().any(|b| b.send.as_ref().map_or(false, |q| q.has_pending_send()))
+    }
+    pub fn pop_notification(&mut self) -> Option<Notification> {
+        self.notifications.pop_front()
+    }
+    pub fn on_network_sent(&mut self, eid: EndpointId) {
+        if let Some(q) = self.buffers.get_mut(&eid).and_then(|b| b.send.as_mut()) {
+            q.net_busy = false;
+        }
+    }
+    pub fn on_network_send_ready(&mut self, ctx: &mut Context, eid: EndpointId, ready: bool) {
+        if let Some(q) = self.buffers.get_mut(&eid).and_then(|b| b.send.as_mut()) {
+            q.net_ready = ready;
+            q.flush(ctx, eid);
+            q.notify_ready(eid, &mut self.notifications);
+        }
+    }
+    pub fn on_network_recv(&mut self, ctx: &mut Context, eid: EndpointId, msg: Message) {
+        if let Some(q) = self.buffers.get_mut(&eid).and_then(|b| b.recv.as_mut()) {
+            q.net_busy = false;
+            q.push(eid, msg, &mut self.notifications);
+            q.fill(ctx, eid);
+            q.notify_ready(eid, &mut self.notifications);
+        }
+    }
+    pub fn on_network_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId, ready: bool) {
+        if let Some(q) = self.buffers.get_mut(&eid).and_then(|b| b.recv.as_mut()) {
+            q.net_ready = ready;
+            q.fill(ctx, eid);
+        }
+    }
+    fn send(&mut self, ctx: &mut Context, eid: EndpointId, msg: Rc<Message>) {
+        match self.buffers.get_mut(&eid).and_then(|b| b.send.as_mut()) {
+            Some(q) => q.send(ctx, eid, msg, &mut self.notifications),
+            None    => ctx.send(eid, msg)
+        }
+    }
+    fn recv(&mut self, ctx: &mut Context, eid: EndpointId) {
+        match self.buffers.get_mut(&eid).and_then(|b| b.recv.as_mut()) {
+            Some(q) => q.recv(ctx, eid, &mut self.notifications),
+            None    => ctx.recv(eid)
+        }
+    }
+}
+
+impl Notification {
+    fn endpoint_id(&self) -> EndpointId {
+        match *self {
+            Notification::SendAck(eid)    |
+            Notification::SendReady(eid)  |
+            Notification::RecvAck(eid, _) |
+            Notification::RecvReady(eid)  => eid
+        }
+    }
+}
+
+/// Empty messages still take some room, so that a queue can not grow without limit.
+fn weight(msg: &Message) -> usize {
+    cmp::max(msg.len(), 1)
+}
+
+impl PipeBuffer {
+    fn new(send_capacity: usize, recv_capacity: usize) -> PipeBuffer {
+        PipeBuffer {
+            send: if send_capacity > 0 { Some(SendQueue::new(send_capacity)) } else { None },
+            recv: if recv_capacity > 0 { Some(RecvQueue::new(recv_capacity)) } else { None }
+        }
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* SendQueue                                                                 */
+/*                                                                           */
+/*****************************************************************************/
+
+impl SendQueue {
+    fn new(capacity: usize) -> SendQueue {
+        SendQueue {
+            capacity: capacity,
+            msgs: VecDeque::new(),
+            len: 0,
+            net_ready: false,
+            net_busy: false,
+            proto_ready: false
+        }
+    }
+    fn has_room(&self) -> bool {
+        self.len < self.capacity
+    }
+    fn has_pending_send(&self) -> bool {
+        self.net_busy || !self.msgs.is_empty()
+    }
+    fn send(&mut self, ctx: &mut Context, eid: EndpointId, msg: Rc<Message>, notifications: &mut VecDeque<Notification>) {
+        self.proto_ready = false;
+        self.len += weight(&msg);
+        self.msgs.push_back(msg);
+        self.flush(ctx, eid);
+
+        notifications.push_back(Notification::SendAck(eid));
+        self.notify_ready(eid, notifications);
+    }
+    fn flush(&mut self, ctx: &mut Context, eid: EndpointId) {
+        if !self.net_ready || self.net_busy {
+            return;
+        }
+
+        if let Some(msg) = self.msgs.pop_front() {
+            self.len -= weight(&msg);
+            self.net_ready = false;
+            self.net_busy = true;
+            ctx.send(eid, msg);
+        }
+    }
+    fn notify_ready(&mut self, eid: EndpointId, notifications: &mut VecDeque<Notification>) {
+        if !self.proto_ready && self.has_room() {
+            self.proto_ready = true;
+            notifications.push_back(Notification::SendReady(eid));
+        }
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* RecvQueue                                                                 */
+/*                                                                           */
+/*****************************************************************************/
+
+impl RecvQueue {
+    fn new(capacity: usize) -> RecvQueue {
+        RecvQueue {
+            capacity: capacity,
+            msgs: VecDeque::new(),
+            len: 0,
+            net_ready: false,
+            net_busy: false,
+            proto_ready: false,
+            proto_pending: false
+        }
+    }
+    fn has_room(&self) -> bool {
+        self.len < self.capacity
+    }
+    fn recv(&mut self, ctx: &mut Context, eid: EndpointId, notifications: &mut VecDeque<Notification>) {
+        self.proto_ready = false;
+
+        match self.msgs.pop_front() {
+            Some(msg) => {
+                self.len -= weight(&msg);
+                notifications.push_back(Notification::RecvAck(eid, msg));
+            },
+            None => self.proto_pending = true
+        }
+
+        self.fill(ctx, eid);
+        self.notify_ready(eid, notifications);
+    }
+    fn push(&mut self, eid: EndpointId, msg: Message, notifications: &mut VecDeque<Notification>) {
+        if self.proto_pending {
+            self.proto_pending = false;
+            notifications.push_back(Notification::RecvAck(eid, msg));
+        } else {
+            self.len += weight(&msg);
+            self.msgs.push_back(msg);
+        }
+    }
+    fn fill(&mut self, ctx: &mut Context, eid: EndpointId) {
+        if self.net_busy {
+            return;
+        }
+
+        if self.proto_pending || (self.net_ready && self.has_room()) {
+            self.net_ready = false;
+            self.net_busy = true;
+            ctx.recv(eid);
+        }
+    }
+    fn notify_ready(&mut self, eid: EndpointId, notifications: &mut VecDeque<Notification>) {
+        if !self.proto_ready && !self.proto_pending && !self.msgs.is_empty() {
+            self.proto_ready = true;
+            notifications.push_back(Notification::RecvReady(eid));
+        }
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* BufferedContext                                                           */
+/*                                                                           */
+/*****************************************************************************/
+
+impl<'a> BufferedContext<'a> {
+    pub fn new(ctx: &'a mut Context, buffers: &'a mut PipeBuffers) -> BufferedContext<'a> {
+        BufferedContext {
+            ctx: ctx,
+            buffers: buffers
+        }
+    }
+}
+
+impl<'a> Network for BufferedContext<'a> {
+    fn connect(&mut self, sid: SocketId, tmpl: &EndpointTmpl) -> Result<EndpointId> {
+        self.ctx.connect(sid, tmpl)
+    }
+    fn reconnect(&mut self, sid: SocketId, eid: EndpointId, tmpl: &EndpointTmpl) -> Result<()> {
+        self.ctx.reconnect(sid, eid, tmpl)
+    }
+    fn bind(&mut self, sid: SocketId, tmpl: &EndpointTmpl) -> Result<EndpointId> {
+        self.ctx.bind(sid, tmpl)
+    }
+    fn rebind(&mut self, sid: SocketId, eid: EndpointId, tmpl: &EndpointTmpl) -> Result<()> {
+        self.ctx.rebind(sid, eid, tmpl)
+    }
+    fn open(&mut self, eid: EndpointId, remote: bool) {
+        self.ctx.open(eid, remote)
+    }
+    fn close(&mut self, eid: EndpointId, remote: bool) {
+        self.ctx.close(eid, remote)
+    }
+    fn send(&mut self, eid: EndpointId, msg: Rc<Message>) {
+        self.buffers.send(self.ctx, eid, msg)
+    }
+    fn recv(&mut self, eid: EndpointId) {
+        self.buffers.recv(self.ctx, eid)
+    }
+}
+
+impl<'a> Scheduler for BufferedContext<'a> {
+    fn schedule(&mut self, schedulable: Schedulable, delay: Duration) -> Result<Scheduled> {
+        self.ctx.schedule(schedulable, delay)
+    }
+    fn cancel(&mut self, scheduled: Scheduled) {
+        self.ctx.cancel(scheduled)
+    }
+}
+
+impl<'a> Context for BufferedContext<'a> {
+    fn raise(&mut self, evt: Event) {
+        self.ctx.raise(evt)
+    }
+}
+
+impl<'a> fmt::Debug for BufferedContext<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.ctx.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use core::{EndpointId, Message};
+    use core::network::Network;
+    use core::tests::*;
+
+    use super::*;
+
+    fn pop_all(buffers: &mut PipeBuffers) -> Vec<Notification> {
+        let mut notifications = Vec::new();
+        while let Some(n) = buffers.pop_notification() {
+            notifications.push(n);
+        }
+        notifications
+    }
+
+    fn is_send_ack(n: &Notification) -> bool {
+        match *n { Notification::SendAck(_) => true, _ => false }
+    }
+
+    fn is_send_ready(n: &Notification) -> bool {
+        match *n { Notification::SendReady(_) => true, _ => false }
+    }
+
+    #[test]
+    fn unbuffered_pipe_sends_and_recvs_straight_to_the_network() {
+        let sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(sensor.clone());
+        let mut buffers = PipeBuffers::new();
+        let eid = EndpointId::from(1);
+
+        buffers.insert(eid, 0, 0);
+        {
+            let mut ctx = BufferedContext::new(&mut ctx, &mut buffers);
+            ctx.send(eid, Rc::new(Message::from_body(vec![1])));
+            ctx.recv(eid);
+        }
+
+        assert!(!buffers.is_send_buffered(eid));
+        assert!(!buffers.is_recv_buffered(eid));
+        assert!(buffers.pop_notification().is_none());
+        sensor.borrow().assert_one_send_to(eid);
+        sensor.borrow().assert_one_recv_from(eid);
+    }
+
+    #[test]
+    fn send_is_acked_at_once_and_pipe_stays_ready_while_queue_has_room() {
+        let sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(sensor.clone());
+        let mut buffers = PipeBuffers::new();
+        let eid = EndpointId::from(1);
+
+        buffers.insert(eid, 10, 0);
+        buffers.on_network_send_ready(&mut ctx, eid, true);
+        let notifications = pop_all(&mut buffers);
+        assert_eq!(1, notifications.len());
+        assert!(is_send_ready(&notifications[0]));
+
+        {
+            let mut ctx = BufferedContext::new(&mut ctx, &mut buffers);
+            ctx.send(eid, Rc::new(Message::from_body(vec![1; 4])));
+        }
+        let notifications = pop_all(&mut buffers);
+        assert_eq!(2, notifications.len());
+        assert!(is_send_ack(&notifications[0]));
+        assert!(is_send_ready(&notifications[1]));
+        sensor.borrow().assert_one_send_to(eid);
+
+        {
+            let mut ctx = BufferedContext::new(&mut ctx, &mut buffers);
+            ctx.send(eid, Rc::new(Message::from_body(vec![2; 4])));
+            ctx.send(eid, Rc::new(Message::from_body(vec![3; 8])));
+        }
+        let notifications = pop_all(&mut buffers);
+        assert_eq!(3, notifications.len());
+        assert!(is_send_ack(&notifications[0]));
+        assert!(is_send_ready(&notifications[1]));
+        assert!(is_send_ack(&notifications[2]));
+        sensor.borrow().assert_one_send_to(eid);
+        assert!(buffers.has_pending_send());
+
+        buffers.on_network_sent(eid);
+        buffers.on_network_send_ready(&mut ctx, eid, true);
+        let notifications = pop_all(&mut buffers);
+        assert_eq!(1, notifications.len());
+        assert!(is_send_ready(&notifications[0]));
+        sensor.borrow().assert_send_to(eid, 2);
+
+        buffers.on_network_sent(eid);
+        buffers.on_network_send_ready(&mut ctx, eid, true);
+        buffers.on_network_sent(eid);
+        assert!(!buffers.has_pending_send());
+        sensor.borrow().assert_send_to(eid, 3);
+    }
+
+    #[test]
+    fn recv_queue_reads_ahead_and_notifies_when_a_message_is_available() {
+        let sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(sensor.clone());
+        let mut buffers = PipeBuffers::new();
+        let eid = EndpointId::from(1);
+
+        buffers.insert(eid, 0, 10);
+        buffers.on_network_recv_ready(&mut ctx, eid, true);
+        sensor.borrow().assert_one_recv_from(eid);
+
+        buffers.on_network_recv(&mut ctx, eid, Message::from_body(vec![1; 4]));
+        let notifications = pop_all(&mut buffers);
+        assert_eq!(1, notifications.len());
+        match notifications[0] {
+            Notification::RecvReady(id) => assert_eq!(eid, id),
+            _ => panic!("expected recv ready")
+        }
+
+        buffers.on_network_recv_ready(&mut ctx, eid, true);
+        sensor.borrow().assert_recv_from(eid, 2);
+        {
+            let mut ctx = BufferedContext::new(&mut ctx, &mut buffers);
+            ctx.recv(eid);
+        }
+        let notifications = pop_all(&mut buffers);
+        assert_eq!(1, notifications.len());
+        match notifications[0] {
+            Notification::RecvAck(id, ref msg) => {
+                assert_eq!(eid, id);
+                assert_eq!(&[1; 4], msg.get_body());
+            },
+            _ => panic!("expected recv ack")
+        }
+
+        {
+            let mut ctx = BufferedContext::new(&mut ctx, &mut buffers);
+            ctx.recv(eid);
+        }
+        assert!(buffers.pop_notification().is_none());
+        buffers.on_network_recv(&mut ctx, eid, Message::from_body(vec![2; 4]));
+        let notifications = pop_all(&mut buffers);
+        assert_eq!(1, notifications.len());
+        match notifications[0] {
+            Notification::RecvAck(_, ref msg) => assert_eq!(&[2; 4], msg.get_body()),
+            _ => panic!("expected recv ack")
+        }
+    }
+
+    #[test]
+    fn removing_a_pipe_discards_its_notifications() {
+        let mut ctx = TestContext::with_sensor(Rc::new(RefCell::new(TestContextSensor::default())));
+        let mut buffers = PipeBuffers::new();
+        let eid = EndpointId::from(1);
+
+        buffers.insert(eid, 10, 10);
+        buffers.on_network_send_ready(&mut ctx, eid, true);
+        buffers.remove(eid);
+
+        assert!(buffers.pop_notification().is_none());
+        assert!(!buffers.is_send_buffered(eid));
+    }
+}
@@ -10,6 +10,7 @@ use std::time::Duration;
 use io_error::*;
 
 pub struct Config {
+    pub linger: Duration,
     pub send_timeout: Option<Duration>,
     pub send_priority: u8,
     pub recv_timeout: Option<Duration>,
@@ -17,12 +18,16 @@ pub struct Config {
     pub retry_ivl: Duration,
     pub retry_ivl_max: Option<Duration>,
     pub tcp_no_delay: bool,
-    pub recv_max_size: u64
+    pub recv_max_size: u64,
+    pub send_buffer: usize,
+    pub recv_buffer: usize
 }
 
 pub enum ConfigOption {
     /// Specifies how long the socket should try to send pending outbound messages 
-    /// after `drop` have been called. Default value is 1 second.
+    /// after `drop` have been called. Dropping the socket blocks at most that long.
+    /// Zero value means that pending messages are discarded immediately. 
+    /// Default value is 1 second.
     Linger(Duration),
 
     /// See [Socket::set_send_timeout](struct.Socket.html#method.set_send_timeout).
@@ -44,7 +49,7 @@ pub enum ConfigOption {
 
     /// For connection-based transports such as TCP, this option specifies how long to wait, 
     /// when connection is broken before trying to re-establish it. 
-    /// Note that actual reconnect interval may be randomised to some extent 
+    /// Note that actual reconnect interval is randomly shortened by up to a half
     /// to prevent severe reconnection storms. Default value is 0.1 second.
     RetryIvl(Duration),
 
@@ -59,13 +64,31 @@ pub enum ConfigOption {
     /// See [Socket::set_tcp_nodelay](struct.Socket.html#method.set_tcp_nodelay).
     TcpNoDelay(bool),
 
+    /// Size in bytes of the outbound queue of each pipe subsequently added to the socket.
+    /// Messages are queued while the pipe is busy, so short bursts do not block the sender.
+    /// Default value is 0, meaning messages are only sent when the pipe is ready.
+    SendBuffer(usize),
+
+    /// Size in bytes of the inbound queue of each pipe subsequently added to the socket.
+    /// Messages are read ahead from the pipe until the queue is full.
+    /// Default value is 0, meaning messages are only read when requested.
+    RecvBuffer(usize),
+
     /// Defined on `Sub` socket. Subscribes for a particular topic.
     /// A single `Sub` socket can handle multiple subscriptions.
+    /// Subscriptions are counted, so a topic subscribed twice must be unsubscribed twice.
     Subscribe(String),
 
     /// Defined on Sub` socket. Unsubscribes from a particular topic.
     Unsubscribe(String),
 
+    /// Defined on `Sub` socket. Subscribes for a topic given as raw bytes,
+    /// which does not need to be valid UTF-8.
+    SubscribeBytes(Vec<u8>),
+
+    /// Defined on `Sub` socket. Unsubscribes from a topic given as raw bytes.
+    UnsubscribeBytes(Vec<u8>),
+
     /// This option is defined on the Req socket.
     /// If a reply is not received in the specified amount of time, 
     /// the request will be automatically resent. 
@@ -76,12 +99,38 @@ pub enum ConfigOption {
     /// Once the deadline expires, receive function will return a TimedOut error 
     /// and all subsequent responses to the survey will be silently dropped.
     /// Default value is 1 second.
-    SurveyDeadline(Duration)
+    SurveyDeadline(Duration),
+
+    /// Defined on `Rep`, `Respondent` and `Pair1` sockets, and their raw versions.
+    /// Maximum number of devices a message can travel through, from 1 to 255,
+    /// messages that have been through more hops are dropped.
+    /// Default value is 8.
+    MaxTtl(u8),
+
+    /// Defined on `Pub` socket. Number of messages that can be queued for each subscriber
+    /// while its pipe is busy. Once a queue is full, messages are dropped according to
+    /// the `PubDropPolicy` option and counted, see `Socket::dropped_messages`.
+    /// Default value is 0, meaning busy subscribers miss the messages.
+    PubQueueDepth(usize),
+
+    /// Defined on `Pub` socket. Which message is dropped when a subscriber queue is full.
+    /// Default value is `DropPolicy::DropNewest`.
+    PubDropPolicy(DropPolicy)
+}
+
+/// Selects the message that is discarded when a queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropPolicy {
+    /// The message being queued is dropped, the queue content is kept.
+    DropNewest,
+    /// The oldest message of the queue is dropped to make room for the new one.
+    DropOldest
 }
 
 impl Default for Config {
     fn default() -> Config {
         Config {
+            linger: Duration::from_secs(1),
             send_timeout: None,
             send_priority: 8,
             recv_timeout: None,
@@ -89,7 +138,9 @@ impl Default for Config {
             retry_ivl: Duration::from_millis(100),
             retry_ivl_max: None,
             tcp_no_delay: false,
-            recv_max_size: 1024 * 1024
+            recv_max_size: 1024 * 1024,
+            send_buffer: 0,
+            recv_buffer: 0
         }
     }
 }
@@ -97,6 +148,7 @@ impl Default for Config {
 impl Config {
     pub fn set(&mut self, cfg_opt: ConfigOption) -> Result<()> {
         match cfg_opt {
+            ConfigOption::Linger(linger) => self.linger = linger,
             ConfigOption::SendTimeout(timeout) => self.send_timeout = timeout,
             ConfigOption::SendPriority(priority) => self.send_priority = priority,
             ConfigOption::RecvTimeout(timeout) => self.recv_timeout = timeout,
@@ -105,6 +157,8 @@ impl Config {
             ConfigOption::RetryIvlMax(ivl) => self.retry_ivl_max = ivl,
             ConfigOption::RecvMaxSize(x) => self.recv_max_size = x,
             ConfigOption::TcpNoDelay(x) => self.tcp_no_delay = x,
+            ConfigOption::SendBuffer(x) => self.send_buffer = x,
+            ConfigOption::RecvBuffer(x) => self.recv_buffer = x,
             _ => return Err(invalid_input_io_error("option not supported"))
         }
         Ok(())
@@ -123,7 +177,9 @@ impl ConfigOption {
             ConfigOption::RecvPriority(_) |
             ConfigOption::RetryIvl(_)     |
             ConfigOption::RetryIvlMax(_)  |
-            ConfigOption::TcpNoDelay(_)   => true,
+            ConfigOption::TcpNoDelay(_)   |
+            ConfigOption::SendBuffer(_)   |
+            ConfigOption::RecvBuffer(_)   => true,
             _ => false
         }
     }
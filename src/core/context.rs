@@ -8,7 +8,7 @@ use std::fmt;
 use std::io::Result;
 use std::time::Duration;
 
-use core::{EndpointId, EndpointSpec, Scheduled};
+use core::{ContextId, EndpointId, EndpointSpec, Scheduled};
 use core::network::Network;
 
 pub trait Context : Network + Scheduler + fmt::Debug {
@@ -48,7 +48,14 @@ pub enum Schedulable {
     SendTimeout,
     RecvTimeout,
     ReqResend,
-    SurveyCancel
+    SurveyCancel,
+    SurveyCollect,
+    Linger,
+    DeferredTimeout(usize),
+    ContextSendTimeout(ContextId),
+    ContextRecvTimeout(ContextId),
+    ContextResend(ContextId),
+    ContextSurveyCancel(ContextId)
 }
 
 impl fmt::Debug for Scheduled {
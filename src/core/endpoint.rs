@@ -5,12 +5,41 @@
 // This file may not be copied, modified, or distributed except according to those terms.
 
 use std::rc::Rc;
+use std::io;
 
 use super::{EndpointId, Message, EndpointSpec, EndpointDesc};
 use super::context::Context;
 
 pub enum Request {
-    Close(bool)
+    Close(bool),
+    LocalAddr(bool),
+    RemoteAddr(bool),
+    State,
+    Url,
+    AcceptedPipes
+}
+
+pub enum Reply {
+    Err(io::Error),
+    Close,
+    LocalAddr(Option<String>),
+    RemoteAddr(Option<String>),
+    State(State),
+    Url(String),
+    AcceptedPipes(Vec<(EndpointId, Option<String>)>)
+}
+
+/// Lifecycle stage of an endpoint, as seen by its socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// The transport connection is being established.
+    Connecting,
+    /// Connected, the SP protocol handshake is running.
+    Handshaking,
+    /// The endpoint is listening, or its pipe is exchanging messages.
+    Active,
+    /// The endpoint failed and is waiting to reconnect or rebind.
+    Backoff
 }
 
 pub struct Endpoint {
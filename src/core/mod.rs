@@ -12,6 +12,8 @@
 #[doc(hidden)] pub mod endpoint;
 #[doc(hidden)] pub mod device;
 #[doc(hidden)] pub mod probe;
+#[doc(hidden)] pub mod backoff;
+#[doc(hidden)] pub mod buffer;
 
 #[cfg(test)]
 pub mod tests;
@@ -57,6 +59,34 @@ impl<'x> Into<usize> for &'x EndpointId {
     }
 }
 
+/*****************************************************************************/
+/*                                                                           */
+/* ContextId                                                                 */
+/*                                                                           */
+/*****************************************************************************/
+
+#[doc(hidden)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ContextId(usize);
+
+impl fmt::Debug for ContextId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<usize> for ContextId {
+    fn from(value: usize) -> ContextId {
+        ContextId(value)
+    }
+}
+
+impl Into<usize> for ContextId {
+    fn into(self) -> usize {
+        self.0
+    }
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* Endpoint                                                                  */
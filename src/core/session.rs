@@ -72,8 +72,7 @@ impl Session {
 
     pub fn add_socket(&mut self, protocol_ctor: socket::ProtocolCtor) {
         let (tx, rx) = mpsc::channel();
-        let protocol = protocol_ctor(tx.clone());
-        let id = self.sockets.add(tx, protocol);
+        let id = self.sockets.add(tx, protocol_ctor);
 
         self.send_reply(Reply::SocketCreated(id, rx));
     }
@@ -151,9 +150,9 @@ impl SocketCollection {
         }
     }
 
-    fn add(&mut self, reply_tx: mpsc::Sender<socket::Reply>, proto: Box<socket::Protocol>) -> SocketId {
+    fn add(&mut self, reply_tx: mpsc::Sender<socket::Reply>, protocol_ctor: socket::ProtocolCtor) -> SocketId {
         let id = SocketId::from(self.ids.next());
-        let socket = socket::Socket::new(id, reply_tx, proto);
+        let socket = socket::Socket::new(id, reply_tx, protocol_ctor);
 
         self.sockets.insert(id, socket);
 
@@ -4,14 +4,22 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::collections::HashMap;
-use std::sync::mpsc::Sender;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::mem;
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::mpsc::{Sender, Receiver};
+use std::task::Waker;
+
+use mio::SetReadiness;
 use std::io;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use super::{BuildIdHasher, SocketId, EndpointId, Message, EndpointTmpl, EndpointSpec, EndpointDesc, Scheduled };
+use super::{BuildIdHasher, SocketId, ContextId, EndpointId, Message, EndpointTmpl, EndpointSpec, EndpointDesc, Scheduled };
+use super::endpoint;
 use super::endpoint::{Pipe, Acceptor};
 use super::config::{Config, ConfigOption};
+use super::backoff::Backoff;
+use super::buffer::{PipeBuffers, BufferedContext, Notification};
 use super::context::{Context, Schedulable, Event};
 use io_error::*;
 
@@ -20,26 +28,109 @@ pub enum Request {
     Bind(String),
     Send(Message, bool),
     Recv(bool),
+    Survey(Message),
     SetOption(ConfigOption),
+    Subscriptions,
+    DroppedMessages,
+    CreateContext,
+    ContextSend(ContextId, Message),
+    ContextRecv(ContextId),
+    CloseContext(ContextId),
+    Cancel,
+    SetReplyWaker(Arc<ReplyWaker>),
+    SetReadiness(SetReadiness),
+    Shared(Sender<Reply>, Box<Request>),
     Close
 }
 
 pub enum Reply {
     Err(io::Error),
-    Connect(EndpointId),
-    Bind(EndpointId),
+    Connect(EndpointId, mpsc::Receiver<endpoint::Reply>),
+    Bind(EndpointId, mpsc::Receiver<endpoint::Reply>),
     Send,
     Recv(Message),
-    SetOption
+    Survey(Vec<Message>, usize),
+    SetOption,
+    Subscriptions(Vec<Vec<u8>>),
+    DroppedMessages(Vec<(EndpointId, u64, usize)>),
+    ContextCreated(ContextId, mpsc::Receiver<Reply>)
 }
 
 pub struct Socket {
     id: SocketId,
     reply_sender: Sender<Reply>,
+    reply_waker: Option<Arc<ReplyWaker>>,
     protocol: Box<Protocol>,
+    protocol_replies: Receiver<Reply>,
+    requester: Option<Sender<Reply>>,
+    pending_send: Option<(Operation, Option<Sender<Reply>>)>,
+    pending_recv: Option<(Operation, Option<Sender<Reply>>)>,
+    calling: Option<Direction>,
+    deferred: VecDeque<DeferredOperation>,
+    deferred_id_seq: usize,
     pipes: HashMap<EndpointId, Pipe, BuildIdHasher>,
     acceptors: HashMap<EndpointId, Acceptor, BuildIdHasher>,
-    config: Config
+    endpoint_reply_senders: HashMap<EndpointId, Sender<endpoint::Reply>, BuildIdHasher>,
+    endpoint_urls: HashMap<EndpointId, String, BuildIdHasher>,
+    accepted_pipes: HashMap<EndpointId, EndpointId, BuildIdHasher>,
+    handshaking_pipes: HashSet<EndpointId>,
+    retries: HashMap<EndpointId, Scheduled, BuildIdHasher>,
+    context_reply_senders: HashMap<ContextId, Sender<Reply>, BuildIdHasher>,
+    context_id_seq: usize,
+    config: Config,
+    backoff: Backoff,
+    buffers: PipeBuffers,
+    linger: Option<Scheduled>
+}
+
+/// Wakes the task waiting for a reply when the socket sends one.
+#[derive(Default)]
+pub struct ReplyWaker {
+    waker: Mutex<Option<Waker>>
+}
+
+enum Operation {
+    Send(Option<Scheduled>),
+    Recv(Option<Scheduled>),
+    Survey
+}
+
+enum Deferred {
+    Send(Message),
+    Recv,
+    Survey(Message)
+}
+
+/// Surveys go the same way as sends, a duplex protocol processes one operation per direction.
+#[derive(Clone, Copy)]
+enum Direction {
+    Send,
+    Recv
+}
+
+impl Operation {
+    fn direction(&self) -> Direction {
+        match *self {
+            Operation::Recv(_) => Direction::Recv,
+            _                  => Direction::Send
+        }
+    }
+}
+
+impl Deferred {
+    fn direction(&self) -> Direction {
+        match *self {
+            Deferred::Recv => Direction::Recv,
+            _              => Direction::Send
+        }
+    }
+}
+
+struct DeferredOperation {
+    id: usize,
+    owner: Option<Sender<Reply>>,
+    operation: Deferred,
+    timeout: Option<(Instant, Scheduled)>
 }
 
 /*****************************************************************************/
@@ -69,6 +160,13 @@ pub trait Protocol {
 
     fn is_send_ready(&self) -> bool;
     fn is_recv_ready(&self) -> bool;
+    /// Whether a send and a recv can be in progress at the same time.
+    fn is_duplex(&self) -> bool {
+        false
+    }
+    fn has_pending_send(&self) -> bool {
+        false
+    }
 
     fn set_option(&mut self, _: ConfigOption) -> io::Result<()> {
         Err(invalid_input_io_error("option not supported"))
@@ -76,6 +174,26 @@ pub trait Protocol {
     fn on_timer_tick(&mut self, _: &mut Context, _: Schedulable) {
     }
     fn on_device_plugged(&mut self, _: &mut Context) {}
+    fn subscriptions(&self) -> io::Result<Vec<Vec<u8>>> {
+        Err(invalid_input_io_error("subscriptions not supported"))
+    }
+    fn dropped_messages(&self) -> io::Result<Vec<(EndpointId, u64, usize)>> {
+        Err(invalid_input_io_error("dropped messages not supported"))
+    }
+
+    fn survey(&mut self, _: &mut Context, _: Message) -> io::Result<()> {
+        Err(invalid_input_io_error("survey not supported"))
+    }
+
+    fn add_context(&mut self, _: &mut Context, _: ContextId, _: Sender<Reply>) -> io::Result<()> {
+        Err(invalid_input_io_error("contexts not supported"))
+    }
+    fn remove_context(&mut self, _: &mut Context, _: ContextId) {}
+    fn context_send(&mut self, _: &mut Context, _: ContextId, _: Message, _: Option<Scheduled>) {}
+    fn on_context_send_timeout(&mut self, _: &mut Context, _: ContextId) {}
+    fn context_recv(&mut self, _: &mut Context, _: ContextId, _: Option<Scheduled>) {}
+    fn on_context_recv_timeout(&mut self, _: &mut Context, _: ContextId) {}
+
     fn close(&mut self, ctx: &mut Context);
 }
 
@@ -88,14 +206,34 @@ pub type ProtocolCtor = Box<Fn(Sender<Reply>) -> Box<Protocol> + Send>;
 /*****************************************************************************/
 
 impl Socket {
-    pub fn new(id: SocketId, reply_tx: Sender<Reply>, proto: Box<Protocol>) -> Socket {
+    pub fn new(id: SocketId, reply_tx: Sender<Reply>, protocol_ctor: ProtocolCtor) -> Socket {
+        let (proto_tx, proto_rx) = mpsc::channel();
+
         Socket {
             id: id,
             reply_sender: reply_tx,
-            protocol: proto,
+            reply_waker: None,
+            protocol: protocol_ctor(proto_tx),
+            protocol_replies: proto_rx,
+            requester: None,
+            pending_send: None,
+            pending_recv: None,
+            calling: None,
+            deferred: VecDeque::new(),
+            deferred_id_seq: 0,
             pipes: HashMap::default(),
             acceptors: HashMap::default(),
-            config: Config::default()
+            endpoint_reply_senders: HashMap::default(),
+            endpoint_urls: HashMap::default(),
+            accepted_pipes: HashMap::default(),
+            handshaking_pipes: HashSet::new(),
+            retries: HashMap::default(),
+            context_reply_senders: HashMap::default(),
+            context_id_seq: 0,
+            config: Config::default(),
+            backoff: Backoff::new(),
+            buffers: PipeBuffers::new(),
+            linger: None
         }
     }
 
@@ -106,13 +244,211 @@ impl Socket {
         (proto_id, peer_proto_id)
     }
 
-    fn send_reply(&self, reply: Reply) {
-        let _ = self.reply_sender.send(reply);
+    /// Replies to the request being processed, on the channel of the handle that sent it.
+    fn send_reply(&mut self, reply: Reply) {
+        let _ = match self.requester {
+            Some(ref requester) => requester.send(reply),
+            None                => self.reply_sender.send(reply)
+        };
+
+        self.wake_reply_waker();
+    }
+
+    /// Replies to the handle that started the pending operation of the given direction.
+    fn complete_operation(&mut self, direction: Direction, reply: Reply) {
+        let _ = match self.pending_slot(direction).take() {
+            Some((_, Some(owner))) => owner.send(reply),
+            _                      => self.reply_sender.send(reply)
+        };
+
+        self.wake_reply_waker();
+    }
+
+    fn wake_reply_waker(&self) {
+        if let Some(ref waker) = self.reply_waker {
+            waker.wake();
+        }
+    }
+
+    /// Forwards the replies sent by the protocol, so that the completion of the
+    /// pending operation is known and the waiting task is woken up.
+    fn relay_protocol_replies(&mut self) {
+        while let Ok(reply) = self.protocol_replies.try_recv() {
+            match self.reply_direction(&reply) {
+                Some(direction) => self.complete_operation(direction, reply),
+                None            => self.send_reply(reply)
+            }
+        }
+    }
+
+    /// Finds the pending operation completed by a reply of the protocol.
+    /// When a send and a recv are both pending, an error belongs to the one the protocol was called for.
+    fn reply_direction(&self, reply: &Reply) -> Option<Direction> {
+        match (self.pending_send.is_some(), self.pending_recv.is_some()) {
+            (false, false) => None,
+            (true, false)  => Some(Direction::Send),
+            (false, true)  => Some(Direction::Recv),
+            (true, true)   => match *reply {
+                Reply::Send | Reply::Survey(..) => Some(Direction::Send),
+                Reply::Recv(_)                  => Some(Direction::Recv),
+                _ => Some(self.calling.unwrap_or(Direction::Send))
+            }
+        }
+    }
+
+    fn pending_slot(&mut self, direction: Direction) -> &mut Option<(Operation, Option<Sender<Reply>>)> {
+        match direction {
+            Direction::Send => &mut self.pending_send,
+            Direction::Recv => &mut self.pending_recv
+        }
+    }
+
+    /// Duplex protocols process a send and a recv at the same time,
+    /// the others one operation whatever its direction.
+    fn is_busy(&self, direction: Direction) -> bool {
+        if self.protocol.is_duplex() {
+            match direction {
+                Direction::Send => self.pending_send.is_some(),
+                Direction::Recv => self.pending_recv.is_some()
+            }
+        } else {
+            self.pending_send.is_some() || self.pending_recv.is_some()
+        }
+    }
+
+    fn start_operation(&mut self, operation: Operation) {
+        let direction = operation.direction();
+        let owner = self.requester.clone();
+
+        *self.pending_slot(direction) = Some((operation, owner));
+    }
+
+    /// Operations requested while the protocol is busy are started once it can take them,
+    /// each keeping the handle that requested it. On a duplex protocol, an operation
+    /// waiting for the send direction does not hold back the recvs, and the other way round.
+    /// The timeout is scheduled when the operation is deferred, so waiting counts against it.
+    fn defer_operation(&mut self, ctx: &mut Context, operation: Deferred, delay: Option<Duration>) {
+        let id = self.deferred_id_seq;
+        let timeout = match delay {
+            Some(delay) => match ctx.schedule(Schedulable::DeferredTimeout(id), delay) {
+                Ok(sched) => Some((Instant::now() + delay, sched)),
+                Err(e) => return self.send_reply(Reply::Err(e))
+            },
+            None => None
+        };
+
+        self.deferred_id_seq += 1;
+        self.deferred.push_back(DeferredOperation {
+            id: id,
+            owner: self.requester.clone(),
+            operation: operation,
+            timeout: timeout
+        });
+    }
+
+    pub fn run_deferred_operations(&mut self, ctx: &mut Context) {
+        while let Some(deferred) = self.pop_startable_operation() {
+            let delay = deferred.timeout.map(|(deadline, sched)| {
+                ctx.cancel(sched);
+                deadline.saturating_duration_since(Instant::now())
+            });
+            let requester = mem::replace(&mut self.requester, deferred.owner);
+
+            match deferred.operation {
+                Deferred::Send(msg)   => self.start_send(ctx, msg, delay),
+                Deferred::Recv        => self.start_recv(ctx, delay),
+                Deferred::Survey(msg) => self.start_survey(ctx, msg)
+            }
+
+            self.requester = requester;
+        }
+    }
+
+    fn pop_startable_operation(&mut self) -> Option<DeferredOperation> {
+        let position = self.deferred.iter().position(|deferred| !self.is_busy(deferred.operation.direction()));
+
+        position.and_then(|i| self.deferred.remove(i))
+    }
+
+    /// Fails a deferred operation that is still waiting when its timeout expires.
+    pub fn on_deferred_timeout(&mut self, _: &mut Context, id: usize) {
+        let position = self.deferred.iter().position(|deferred| deferred.id == id);
+        let deferred = match position.and_then(|i| self.deferred.remove(i)) {
+            Some(deferred) => deferred,
+            None => return
+        };
+        let error = match deferred.operation {
+            Deferred::Send(_)   => timedout_io_error("Send timed out"),
+            Deferred::Recv      => timedout_io_error("Recv timed out"),
+            Deferred::Survey(_) => timedout_io_error("Survey timed out")
+        };
+        let _ = match deferred.owner {
+            Some(owner) => owner.send(Reply::Err(error)),
+            None        => self.reply_sender.send(Reply::Err(error))
+        };
+
+        self.wake_reply_waker();
+    }
+
+    /// Processes the requests of a shared handle, replying on its own channel.
+    pub fn set_requester(&mut self, requester: Option<Sender<Reply>>) {
+        self.requester = requester;
+    }
+
+    /// Calls the protocol with a context that redirects the sends and recvs of buffered pipes,
+    /// then delivers the notifications produced by the pipe buffers.
+    fn call_protocol<T, F>(&mut self, ctx: &mut Context, f: F) -> T
+    where F : FnOnce(&mut Protocol, &mut Context) -> T {
+        let result = {
+            let mut ctx = BufferedContext::new(ctx, &mut self.buffers);
+
+            f(&mut *self.protocol, &mut ctx)
+        };
+
+        self.notify_protocol(ctx);
+
+        result
+    }
+
+    /// Calls the protocol for the pending operation of the given direction,
+    /// so that an error it replies completes that operation.
+    fn call_protocol_towards<T, F>(&mut self, ctx: &mut Context, direction: Direction, f: F) -> T
+    where F : FnOnce(&mut Protocol, &mut Context) -> T {
+        self.calling = Some(direction);
+
+        let result = self.call_protocol(ctx, f);
+
+        self.calling = None;
+
+        result
+    }
+
+    fn notify_protocol(&mut self, ctx: &mut Context) {
+        while let Some(notification) = self.buffers.pop_notification() {
+            let mut ctx = BufferedContext::new(ctx, &mut self.buffers);
+
+            match notification {
+                Notification::SendAck(eid)        => self.protocol.on_send_ack(&mut ctx, eid),
+                Notification::SendReady(eid)      => self.protocol.on_send_ready(&mut ctx, eid),
+                Notification::RecvAck(eid, msg)   => self.protocol.on_recv_ack(&mut ctx, eid, msg),
+                Notification::RecvReady(eid)      => self.protocol.on_recv_ready(&mut ctx, eid)
+            }
+        }
+
+        self.relay_protocol_replies();
     }
 
     pub fn poll(&self, ctx: &mut Context) {
-        ctx.raise(Event::CanRecv(self.protocol.is_recv_ready()));
-        ctx.raise(Event::CanSend(self.protocol.is_send_ready()));
+        ctx.raise(Event::CanRecv(self.is_recv_ready()));
+        ctx.raise(Event::CanSend(self.is_send_ready()));
+    }
+
+    pub fn is_recv_ready(&self) -> bool {
+        self.protocol.is_recv_ready()
+    }
+
+    pub fn is_send_ready(&self) -> bool {
+        self.protocol.is_send_ready()
     }
 
 /*****************************************************************************/
@@ -160,10 +496,11 @@ impl Socket {
     }
 
     fn on_connect_success(&mut self, ctx: &mut Context, eid: EndpointId, spec: EndpointSpec) {
+        let reply_rx = self.add_endpoint_reply_sender(eid, &spec.url);
         let pipe = self.connect_pipe(eid, spec);
 
         self.insert_pipe(ctx, eid, pipe);
-        self.send_reply(Reply::Connect(eid));
+        self.send_reply(Reply::Connect(eid, reply_rx));
     }
 
     fn on_connect_error(&mut self, err: io::Error) {
@@ -172,13 +509,16 @@ impl Socket {
 
     fn schedule_reconnect(&mut self, ctx: &mut Context, eid: EndpointId, spec: EndpointSpec) {
         let task = Schedulable::Reconnect(eid, spec);
-        let delay = self.config.retry_ivl;
-        let _ = ctx.schedule(task, delay); 
-        // TODO maybe we should keep track of the scheduled reconnection
-        // In case the facade wants to close the ep somewhere between the error and the timeout
+        let delay = self.next_retry_delay(eid);
+
+        self.schedule_retry(ctx, eid, task, delay);
     }
 
     pub fn reconnect(&mut self, ctx: &mut Context, eid: EndpointId, spec: EndpointSpec) {
+        if self.retries.remove(&eid).is_none() {
+            return;
+        }
+
         let pids = self.get_protocol_ids();
         let tmpl = EndpointTmpl {
             pids: pids,
@@ -199,6 +539,25 @@ impl Socket {
         self.schedule_reconnect(ctx, eid, spec);
     }
 
+    fn next_retry_delay(&mut self, eid: EndpointId) -> Duration {
+        self.backoff.next_delay(eid, self.config.retry_ivl, self.config.retry_ivl_max)
+    }
+
+    fn schedule_retry(&mut self, ctx: &mut Context, eid: EndpointId, task: Schedulable, delay: Duration) {
+        match ctx.schedule(task, delay) {
+            Ok(retry) => {
+                self.retries.insert(eid, retry);
+            },
+            Err(e) => self.on_endpoint_closed(eid, Some(e))
+        }
+    }
+
+    fn cancel_retry(&mut self, ctx: &mut Context, eid: EndpointId) {
+        if let Some(retry) = self.retries.remove(&eid) {
+            ctx.cancel(retry);
+        }
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* bind                                                                      */
@@ -215,12 +574,13 @@ impl Socket {
     }
 
     fn on_bind_success(&mut self, ctx: &mut Context, eid: EndpointId, spec: EndpointSpec) {
+        let reply_rx = self.add_endpoint_reply_sender(eid, &spec.url);
         let acceptor = self.connect_acceptor(eid, spec);
 
         acceptor.open(ctx);
 
         self.acceptors.insert(eid, acceptor);
-        self.send_reply(Reply::Bind(eid));
+        self.send_reply(Reply::Bind(eid, reply_rx));
     }
 
     fn on_bind_error(&mut self, err: io::Error) {
@@ -229,13 +589,16 @@ impl Socket {
 
     fn schedule_rebind(&mut self, ctx: &mut Context, eid: EndpointId, spec: EndpointSpec) {
         let task = Schedulable::Rebind(eid, spec);
-        let delay = self.config.retry_ivl;
-        let _ = ctx.schedule(task, delay); 
-        // TODO maybe we should keep track of the scheduled reconnection
-        // In case the facade wants to close the ep somewhere between the error and the timeout
+        let delay = self.next_retry_delay(eid);
+
+        self.schedule_retry(ctx, eid, task, delay);
     }
 
     pub fn rebind(&mut self, ctx: &mut Context, eid: EndpointId, spec: EndpointSpec) {
+        if self.retries.remove(&eid).is_none() {
+            return;
+        }
+
         let pids = self.get_protocol_ids();
         let tmpl = EndpointTmpl {
             pids: pids,
@@ -251,6 +614,8 @@ impl Socket {
     fn on_rebind_success(&mut self, ctx: &mut Context, eid: EndpointId, spec: EndpointSpec) {
         let acceptor = Acceptor::from_spec(eid, spec);
 
+        self.backoff.reset(eid);
+
         self.insert_acceptor(ctx, eid, acceptor)
     }
 
@@ -258,26 +623,107 @@ impl Socket {
         self.schedule_rebind(ctx, eid, spec);
     }
 
+/*****************************************************************************/
+/*                                                                           */
+/* endpoint                                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+    fn add_endpoint_reply_sender(&mut self, eid: EndpointId, url: &str) -> mpsc::Receiver<endpoint::Reply> {
+        let (tx, rx) = mpsc::channel();
+
+        self.endpoint_reply_senders.insert(eid, tx);
+        self.endpoint_urls.insert(eid, url.to_owned());
+
+        rx
+    }
+
+    pub fn send_endpoint_reply(&self, eid: EndpointId, reply: endpoint::Reply) {
+        if let Some(tx) = self.endpoint_reply_senders.get(&eid) {
+            let _ = tx.send(reply);
+        }
+    }
+
+    pub fn get_endpoint_url(&self, eid: EndpointId) -> Option<String> {
+        self.endpoint_urls.get(&eid).cloned()
+    }
+
+    /// Ids of the pipes accepted by the acceptor `aid` that are still open, in ascending order.
+    pub fn get_accepted_pipes(&self, aid: EndpointId) -> Vec<EndpointId> {
+        let mut eids: Vec<EndpointId> = self.accepted_pipes.iter()
+            .filter(|&(_, a)| *a == aid)
+            .map(|(eid, _)| *eid)
+            .collect();
+
+        eids.sort_by_key(|eid| -> usize { eid.into() });
+        eids
+    }
+
+    /// Pipes stay in the `pipes` map until opened, then belong to the protocol.
+    /// Returns `None` for unknown endpoints, including the closed ones.
+    pub fn get_endpoint_state(&self, eid: EndpointId) -> Option<endpoint::State> {
+        if !self.endpoint_urls.contains_key(&eid) {
+            None
+        } else if self.retries.contains_key(&eid) {
+            Some(endpoint::State::Backoff)
+        } else if self.handshaking_pipes.contains(&eid) {
+            Some(endpoint::State::Handshaking)
+        } else if self.pipes.contains_key(&eid) {
+            Some(endpoint::State::Connecting)
+        } else {
+            Some(endpoint::State::Active)
+        }
+    }
+
+    fn on_endpoint_closed(&mut self, eid: EndpointId, err: Option<io::Error>) {
+        self.endpoint_urls.remove(&eid);
+
+        if let Some(tx) = self.endpoint_reply_senders.remove(&eid) {
+            let reply = match err {
+                Some(e) => endpoint::Reply::Err(e),
+                None => endpoint::Reply::Close
+            };
+
+            let _ = tx.send(reply);
+        }
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* pipe                                                                      */
 /*                                                                           */
 /*****************************************************************************/
 
+    pub fn on_pipe_connected(&mut self, eid: EndpointId) {
+        if self.pipes.contains_key(&eid) {
+            self.handshaking_pipes.insert(eid);
+        }
+    }
+
     pub fn on_pipe_opened(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.backoff.reset(eid);
+        self.handshaking_pipes.remove(&eid);
+
         if let Some(pipe) = self.pipes.remove(&eid) {
-            self.protocol.add_pipe(ctx, eid, pipe);
+            self.buffers.insert(eid, self.config.send_buffer, self.config.recv_buffer);
+            self.call_protocol(ctx, |p, ctx| p.add_pipe(ctx, eid, pipe));
         }
     }
 
     pub fn on_pipe_accepted(&mut self, ctx: &mut Context, aid: EndpointId, eid: EndpointId) {
         let pipe = self.accept_pipe(aid, eid);
 
+        self.accepted_pipes.insert(eid, aid);
         self.insert_pipe(ctx, eid, pipe);
     }
 
     pub fn close_pipe(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.cancel_retry(ctx, eid);
+        self.backoff.reset(eid);
+
         let _ = self.remove_pipe(ctx, eid);
+
+        self.on_endpoint_closed(eid, None);
     }
 
     pub fn on_pipe_error(&mut self, ctx: &mut Context, eid: EndpointId, _: io::Error) {
@@ -293,10 +739,13 @@ impl Socket {
     }
 
     fn remove_pipe(&mut self, ctx: &mut Context, eid: EndpointId) -> Option<EndpointSpec> {
+        self.accepted_pipes.remove(&eid);
+        self.handshaking_pipes.remove(&eid);
         if let Some(pipe) = self.pipes.remove(&eid) {
             return pipe.close(ctx)
         }
-        if let Some(pipe) = self.protocol.remove_pipe(ctx, eid) {
+        self.buffers.remove(eid);
+        if let Some(pipe) = self.call_protocol(ctx, |p, ctx| p.remove_pipe(ctx, eid)) {
             return pipe.close(ctx)
         }
         None
@@ -335,7 +784,12 @@ impl Socket {
     }
 
     pub fn close_acceptor(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.cancel_retry(ctx, eid);
+        self.backoff.reset(eid);
+
         let _ = self.remove_acceptor(ctx, eid);
+
+        self.on_endpoint_closed(eid, None);
     }
 
     fn insert_acceptor(&mut self, ctx: &mut Context, eid: EndpointId, acceptor: Acceptor) {
@@ -360,22 +814,37 @@ impl Socket {
 
     pub fn send(&mut self, ctx: &mut Context, msg: Message) {
         #[cfg(debug_assertions)] debug!("[{:?}] send", ctx);
-        if let Some(delay) = self.get_send_timeout() {
+        let delay = self.get_send_timeout();
+
+        if self.is_busy(Direction::Send) {
+            return self.defer_operation(ctx, Deferred::Send(msg), delay);
+        }
+
+        self.start_send(ctx, msg, delay)
+    }
+
+    fn start_send(&mut self, ctx: &mut Context, msg: Message, delay: Option<Duration>) {
+        if let Some(delay) = delay {
             let task = Schedulable::SendTimeout;
 
             match ctx.schedule(task, delay) {
-                Ok(timeout) => self.protocol.send(ctx, msg, Some(timeout)),
+                Ok(timeout) => {
+                    self.start_operation(Operation::Send(Some(timeout)));
+                    self.call_protocol_towards(ctx, Direction::Send, |p, ctx| p.send(ctx, msg, Some(timeout)))
+                },
                 Err(e) => self.send_reply(Reply::Err(e))
             }
         } else {
-            self.protocol.send(ctx, msg, None);
+            self.start_operation(Operation::Send(None));
+            self.call_protocol_towards(ctx, Direction::Send, |p, ctx| p.send(ctx, msg, None));
         }
     }
 
     pub fn try_send(&mut self, ctx: &mut Context, msg: Message) {
         #[cfg(debug_assertions)] debug!("[{:?}] try_send", ctx);
-        if self.protocol.is_send_ready() {
-            self.protocol.send(ctx, msg, None);
+        if !self.is_busy(Direction::Send) && self.protocol.is_send_ready() {
+            self.start_operation(Operation::Send(None));
+            self.call_protocol_towards(ctx, Direction::Send, |p, ctx| p.send(ctx, msg, None));
         } else {
             let err = would_block_io_error("socket is not send ready");
 
@@ -385,12 +854,18 @@ impl Socket {
 
     pub fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
         #[cfg(debug_assertions)] debug!("[{:?}] send ack from ep {:?}", ctx, eid);
-        self.protocol.on_send_ack(ctx, eid);
+        if self.buffers.is_send_buffered(eid) {
+            self.buffers.on_network_sent(eid);
+        } else {
+            self.call_protocol(ctx, |p, ctx| p.on_send_ack(ctx, eid));
+        }
+        self.check_linger(ctx);
     }
 
     pub fn on_send_timeout(&mut self, ctx: &mut Context) {
         #[cfg(debug_assertions)] debug!("[{:?}] send timeout", ctx);
-        self.protocol.on_send_timeout(ctx);
+        self.call_protocol_towards(ctx, Direction::Send, |p, ctx| p.on_send_timeout(ctx));
+        self.check_linger(ctx);
     }
 
     fn get_send_timeout(&self) -> Option<Duration> {
@@ -399,10 +874,13 @@ impl Socket {
 
     pub fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId, ready: bool) {
         #[cfg(debug_assertions)] debug!("[{:?}] ep {:?} send ready: {} ", ctx, eid, ready);
-        if ready {
-            self.protocol.on_send_ready(ctx, eid)
+        if self.buffers.is_send_buffered(eid) {
+            self.buffers.on_network_send_ready(ctx, eid, ready);
+            self.notify_protocol(ctx);
+        } else if ready {
+            self.call_protocol(ctx, |p, ctx| p.on_send_ready(ctx, eid))
         } else {
-            self.protocol.on_send_not_ready(ctx, eid)
+            self.call_protocol(ctx, |p, ctx| p.on_send_not_ready(ctx, eid))
         }
     }
 
@@ -414,22 +892,37 @@ impl Socket {
 
     pub fn recv(&mut self, ctx: &mut Context) {
         #[cfg(debug_assertions)] debug!("[{:?}] recv", ctx);
-        if let Some(delay) = self.get_recv_timeout() {
+        let delay = self.get_recv_timeout();
+
+        if self.is_busy(Direction::Recv) {
+            return self.defer_operation(ctx, Deferred::Recv, delay);
+        }
+
+        self.start_recv(ctx, delay)
+    }
+
+    fn start_recv(&mut self, ctx: &mut Context, delay: Option<Duration>) {
+        if let Some(delay) = delay {
             let task = Schedulable::RecvTimeout;
 
             match ctx.schedule(task, delay) {
-                Ok(timeout) => self.protocol.recv(ctx, Some(timeout)),
+                Ok(timeout) => {
+                    self.start_operation(Operation::Recv(Some(timeout)));
+                    self.call_protocol_towards(ctx, Direction::Recv, |p, ctx| p.recv(ctx, Some(timeout)))
+                },
                 Err(e) => self.send_reply(Reply::Err(e))
             }
         } else {
-            self.protocol.recv(ctx, None);
+            self.start_operation(Operation::Recv(None));
+            self.call_protocol_towards(ctx, Direction::Recv, |p, ctx| p.recv(ctx, None));
         }
     }
 
     pub fn try_recv(&mut self, ctx: &mut Context) {
         #[cfg(debug_assertions)] debug!("[{:?}] try_recv", ctx);
-        if self.protocol.is_recv_ready() {
-            self.protocol.recv(ctx, None);
+        if !self.is_busy(Direction::Recv) && self.protocol.is_recv_ready() {
+            self.start_operation(Operation::Recv(None));
+            self.call_protocol_towards(ctx, Direction::Recv, |p, ctx| p.recv(ctx, None));
         } else {
             let err = would_block_io_error("socket is not recv ready");
             
@@ -439,12 +932,17 @@ impl Socket {
 
     pub fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, msg: Message) {
         #[cfg(debug_assertions)] debug!("[{:?}] recv ack from ep {:?}", ctx, eid);
-        self.protocol.on_recv_ack(ctx, eid, msg);
+        if self.buffers.is_recv_buffered(eid) {
+            self.buffers.on_network_recv(ctx, eid, msg);
+            self.notify_protocol(ctx);
+        } else {
+            self.call_protocol(ctx, |p, ctx| p.on_recv_ack(ctx, eid, msg));
+        }
     }
 
     pub fn on_recv_timeout(&mut self, ctx: &mut Context) {
         #[cfg(debug_assertions)] debug!("[{:?}] recv timeout", ctx);
-        self.protocol.on_recv_timeout(ctx);
+        self.call_protocol_towards(ctx, Direction::Recv, |p, ctx| p.on_recv_timeout(ctx));
     }
 
     fn get_recv_timeout(&self) -> Option<Duration> {
@@ -453,10 +951,108 @@ impl Socket {
 
     pub fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId, ready: bool) {
         #[cfg(debug_assertions)] debug!("[{:?}] ep {:?} recv ready: {}", ctx, eid, ready);
-        if ready {
-            self.protocol.on_recv_ready(ctx, eid)
+        if self.buffers.is_recv_buffered(eid) {
+            self.buffers.on_network_recv_ready(ctx, eid, ready);
+        } else if ready {
+            self.call_protocol(ctx, |p, ctx| p.on_recv_ready(ctx, eid))
         } else {
-            self.protocol.on_recv_not_ready(ctx, eid)
+            self.call_protocol(ctx, |p, ctx| p.on_recv_not_ready(ctx, eid))
+        }
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* survey                                                                    */
+/*                                                                           */
+/*****************************************************************************/
+
+    pub fn survey(&mut self, ctx: &mut Context, msg: Message) {
+        #[cfg(debug_assertions)] debug!("[{:?}] survey", ctx);
+        if self.is_busy(Direction::Send) {
+            let delay = self.get_send_timeout();
+
+            return self.defer_operation(ctx, Deferred::Survey(msg), delay);
+        }
+
+        self.start_survey(ctx, msg)
+    }
+
+    fn start_survey(&mut self, ctx: &mut Context, msg: Message) {
+        self.start_operation(Operation::Survey);
+
+        if let Err(e) = self.call_protocol_towards(ctx, Direction::Send, |p, ctx| p.survey(ctx, msg)) {
+            self.complete_operation(Direction::Send, Reply::Err(e));
+        }
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* context                                                                   */
+/*                                                                           */
+/*****************************************************************************/
+
+    pub fn create_context(&mut self, ctx: &mut Context) {
+        let cid = ContextId::from(self.context_id_seq);
+        let (tx, rx) = mpsc::channel();
+
+        self.context_id_seq += 1;
+
+        match self.call_protocol(ctx, |p, ctx| p.add_context(ctx, cid, tx.clone())) {
+            Ok(()) => {
+                self.context_reply_senders.insert(cid, tx);
+                self.send_reply(Reply::ContextCreated(cid, rx));
+            },
+            Err(e) => self.send_reply(Reply::Err(e))
+        }
+    }
+
+    pub fn context_send(&mut self, ctx: &mut Context, cid: ContextId, msg: Message) {
+        #[cfg(debug_assertions)] debug!("[{:?}] context {:?} send", ctx, cid);
+        if let Some(delay) = self.get_send_timeout() {
+            let task = Schedulable::ContextSendTimeout(cid);
+
+            match ctx.schedule(task, delay) {
+                Ok(timeout) => self.call_protocol(ctx, |p, ctx| p.context_send(ctx, cid, msg, Some(timeout))),
+                Err(e) => self.send_context_reply(cid, Reply::Err(e))
+            }
+        } else {
+            self.call_protocol(ctx, |p, ctx| p.context_send(ctx, cid, msg, None));
+        }
+    }
+
+    pub fn on_context_send_timeout(&mut self, ctx: &mut Context, cid: ContextId) {
+        #[cfg(debug_assertions)] debug!("[{:?}] context {:?} send timeout", ctx, cid);
+        self.call_protocol(ctx, |p, ctx| p.on_context_send_timeout(ctx, cid));
+    }
+
+    pub fn context_recv(&mut self, ctx: &mut Context, cid: ContextId) {
+        #[cfg(debug_assertions)] debug!("[{:?}] context {:?} recv", ctx, cid);
+        if let Some(delay) = self.get_recv_timeout() {
+            let task = Schedulable::ContextRecvTimeout(cid);
+
+            match ctx.schedule(task, delay) {
+                Ok(timeout) => self.call_protocol(ctx, |p, ctx| p.context_recv(ctx, cid, Some(timeout))),
+                Err(e) => self.send_context_reply(cid, Reply::Err(e))
+            }
+        } else {
+            self.call_protocol(ctx, |p, ctx| p.context_recv(ctx, cid, None));
+        }
+    }
+
+    pub fn on_context_recv_timeout(&mut self, ctx: &mut Context, cid: ContextId) {
+        #[cfg(debug_assertions)] debug!("[{:?}] context {:?} recv timeout", ctx, cid);
+        self.call_protocol(ctx, |p, ctx| p.on_context_recv_timeout(ctx, cid));
+    }
+
+    pub fn close_context(&mut self, ctx: &mut Context, cid: ContextId) {
+        if self.context_reply_senders.remove(&cid).is_some() {
+            self.call_protocol(ctx, |p, ctx| p.remove_context(ctx, cid));
+        }
+    }
+
+    fn send_context_reply(&self, cid: ContextId, reply: Reply) {
+        if let Some(tx) = self.context_reply_senders.get(&cid) {
+            let _ = tx.send(reply);
         }
     }
 
@@ -480,28 +1076,152 @@ impl Socket {
         self.send_reply(reply);
     }
 
+    pub fn subscriptions(&mut self, _: &mut Context) {
+        let reply = match self.protocol.subscriptions() {
+            Ok(topics) => Reply::Subscriptions(topics),
+            Err(e)     => Reply::Err(e)
+        };
+
+        self.send_reply(reply);
+    }
+
+    pub fn dropped_messages(&mut self, _: &mut Context) {
+        let reply = match self.protocol.dropped_messages() {
+            Ok(drops) => Reply::DroppedMessages(drops),
+            Err(e)    => Reply::Err(e)
+        };
+
+        self.send_reply(reply);
+    }
+
+    /// Aborts the pending send and recv operations, the protocol replies as if they had timed out.
+    pub fn cancel(&mut self, ctx: &mut Context) {
+        #[cfg(debug_assertions)] debug!("[{:?}] cancel", ctx);
+        for &direction in &[Direction::Send, Direction::Recv] {
+            let timeout = match *self.pending_slot(direction) {
+                Some((Operation::Send(timeout), _)) |
+                Some((Operation::Recv(timeout), _)) => timeout,
+                _ => continue
+            };
+
+            if let Some(sched) = timeout {
+                ctx.cancel(sched);
+            }
+
+            // the reply of the protocol completes the operation
+            match direction {
+                Direction::Send => self.call_protocol_towards(ctx, direction, |p, ctx| p.on_send_timeout(ctx)),
+                Direction::Recv => self.call_protocol_towards(ctx, direction, |p, ctx| p.on_recv_timeout(ctx))
+            }
+
+            *self.pending_slot(direction) = None;
+        }
+    }
+
+    pub fn set_reply_waker(&mut self, _: &mut Context, waker: Arc<ReplyWaker>) {
+        self.reply_waker = Some(waker);
+    }
+
     pub fn on_timer_tick(&mut self, ctx: &mut Context, task: Schedulable) {
-        self.protocol.on_timer_tick(ctx, task)
+        self.call_protocol(ctx, |p, ctx| p.on_timer_tick(ctx, task))
     }
 
     pub fn on_device_plugged(&mut self, ctx: &mut Context) {
-        self.protocol.on_device_plugged(ctx)
+        self.call_protocol(ctx, |p, ctx| p.on_device_plugged(ctx))
     }
 
+/*****************************************************************************/
+/*                                                                           */
+/* close                                                                     */
+/*                                                                           */
+/*****************************************************************************/
+
     pub fn close(&mut self, ctx: &mut Context) {
-        for (_, pipe) in self.pipes.drain() {
-            pipe.close(ctx);
-        }
         for (_, acceptor) in self.acceptors.drain() {
             acceptor.close(ctx);
         }
 
-        self.protocol.close(ctx);
+        match self.schedule_linger(ctx) {
+            Some(deadline) => self.linger = Some(deadline),
+            None => self.close_now(ctx)
+        }
+    }
+
+    fn schedule_linger(&mut self, ctx: &mut Context) -> Option<Scheduled> {
+        if self.config.linger == Duration::from_secs(0) || !self.has_pending_send() {
+            return None;
+        }
+
+        #[cfg(debug_assertions)] debug!("[{:?}] linger", ctx);
+        ctx.schedule(Schedulable::Linger, self.config.linger).ok()
+    }
+
+    fn has_pending_send(&self) -> bool {
+        self.protocol.has_pending_send() || self.buffers.has_pending_send()
+    }
+
+    fn check_linger(&mut self, ctx: &mut Context) {
+        if self.has_pending_send() {
+            return;
+        }
+
+        if let Some(deadline) = self.linger.take() {
+            ctx.cancel(deadline);
+            self.close_now(ctx);
+        }
+    }
+
+    pub fn on_linger_timeout(&mut self, ctx: &mut Context) {
+        #[cfg(debug_assertions)] debug!("[{:?}] linger timeout", ctx);
+        if self.linger.take().is_some() {
+            self.close_now(ctx);
+        }
+    }
+
+    fn close_now(&mut self, ctx: &mut Context) {
+        for (_, retry) in self.retries.drain() {
+            ctx.cancel(retry);
+        }
+        for deferred in self.deferred.drain(..) {
+            if let Some((_, timeout)) = deferred.timeout {
+                ctx.cancel(timeout);
+            }
+        }
+        for (_, pipe) in self.pipes.drain() {
+            pipe.close(ctx);
+        }
+
+        self.call_protocol(ctx, |p, ctx| p.close(ctx));
 
         ctx.raise(Event::Closed);
     }
 }
 
+/*****************************************************************************/
+/*                                                                           */
+/* reply waker                                                               */
+/*                                                                           */
+/*****************************************************************************/
+
+impl ReplyWaker {
+    pub fn register(&self, waker: &Waker) {
+        let mut slot = self.waker.lock().unwrap();
+        let stale = match *slot {
+            Some(ref registered) => !registered.will_wake(waker),
+            None                 => true
+        };
+
+        if stale {
+            *slot = Some(waker.clone());
+        }
+    }
+    pub fn wake(&self) {
+        if let Some(waker) = self.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+    }
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* tests                                                                     */
@@ -592,7 +1312,7 @@ mod tests {
     fn when_connect_fails() {
         let id = SocketId::from(1);
         let (tx, rx) = mpsc::channel();
-        let proto = Box::new(TestProto) as Box<Protocol>;
+        let proto: ProtocolCtor = Box::new(|_| Box::new(TestProto) as Box<Protocol>);
         let mut network = FailingNetwork;
         let mut socket = Socket::new(id, tx, proto);
 
@@ -653,7 +1373,7 @@ mod tests {
     fn when_connect_succeeds() {
         let id = SocketId::from(1);
         let (tx, rx) = mpsc::channel();
-        let proto = Box::new(TestProto) as Box<Protocol>;
+        let proto: ProtocolCtor = Box::new(|_| Box::new(TestProto) as Box<Protocol>);
         let mut network = WorkingNetwork(EndpointId::from(1));
         let mut socket = Socket::new(id, tx, proto);
 
@@ -662,7 +1382,7 @@ mod tests {
         let reply = rx.recv().expect("Socket should have sent a reply to the connect request");
 
         match reply {
-            Reply::Connect(eid) => {
+            Reply::Connect(eid, _) => {
                 assert_eq!(EndpointId::from(1), eid);
             },
             _ => {
@@ -670,4 +1390,321 @@ mod tests {
             },
         }
     }
+
+    struct LingeringProto(bool);
+
+    impl Protocol for LingeringProto {
+        fn id(&self) -> u16 {0}
+        fn peer_id(&self) -> u16 {0}
+        fn add_pipe(&mut self, _: &mut Context, _: EndpointId, _: Pipe) {}
+        fn remove_pipe(&mut self, _: &mut Context, _: EndpointId) -> Option<Pipe> {None}
+        fn send(&mut self, _: &mut Context, _: Message, _: Option<Scheduled>) { self.0 = true; }
+        fn on_send_ack(&mut self, _: &mut Context, _: EndpointId) { self.0 = false; }
+        fn on_send_timeout(&mut self, _: &mut Context) {}
+        fn on_send_ready(&mut self, _: &mut Context, _: EndpointId) {}
+        fn on_send_not_ready(&mut self, _: &mut Context, _: EndpointId) {}
+        fn recv(&mut self, _: &mut Context, _: Option<Scheduled>) {}
+        fn on_recv_ack(&mut self, _: &mut Context, _: EndpointId, _: Message) {}
+        fn on_recv_timeout(&mut self, _: &mut Context) {}
+        fn on_recv_ready(&mut self, _: &mut Context, _: EndpointId) {}
+        fn on_recv_not_ready(&mut self, _: &mut Context, _: EndpointId) {}
+        fn is_send_ready(&self) -> bool { false }
+        fn is_recv_ready(&self) -> bool { false }
+        fn has_pending_send(&self) -> bool { self.0 }
+        fn close(&mut self, _: &mut Context) {}
+    }
+
+    #[derive(Default)]
+    struct RecordingNetwork {
+        reconnects: usize,
+        lingers: usize,
+        cancels: usize,
+        closed: bool
+    }
+
+    impl network::Network for RecordingNetwork {
+        fn connect(&mut self, _: SocketId, _: &EndpointTmpl) -> io::Result<EndpointId> {
+            Ok(EndpointId::from(1))
+        }
+        fn reconnect(&mut self, _: SocketId, _: EndpointId, _: &EndpointTmpl) -> io::Result<()> {
+            self.reconnects += 1;
+            Ok(())
+        }
+        fn bind(&mut self, _: SocketId, _: &EndpointTmpl) -> io::Result<EndpointId> {
+            Ok(EndpointId::from(1))
+        }
+        fn rebind(&mut self, _: SocketId, _: EndpointId, _: &EndpointTmpl) -> io::Result<()> {
+            Ok(())
+        }
+        fn open(&mut self, _: EndpointId, _: bool) {}
+        fn close(&mut self, _: EndpointId, _: bool) {}
+        fn send(&mut self, _: EndpointId, _: Rc<Message>) {}
+        fn recv(&mut self, _: EndpointId) {}
+    }
+
+    impl Scheduler for RecordingNetwork {
+        fn schedule(&mut self, task: Schedulable, _: Duration) -> io::Result<Scheduled> {
+            if let Schedulable::Linger = task {
+                self.lingers += 1;
+            }
+            Ok(Scheduled::from(0))
+        }
+        fn cancel(&mut self, _: Scheduled){
+            self.cancels += 1;
+        }
+    }
+
+    impl fmt::Debug for RecordingNetwork {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "RecordingNetwork")
+        }
+    }
+
+    impl Context for RecordingNetwork {
+        fn raise(&mut self, evt: Event) {
+            if let Event::Closed = evt {
+                self.closed = true;
+            }
+        }
+    }
+
+    fn create_lingering_socket(pending_send: bool) -> Socket {
+        let (tx, _) = mpsc::channel();
+        let proto: ProtocolCtor = Box::new(move |_| Box::new(LingeringProto(pending_send)) as Box<Protocol>);
+
+        Socket::new(SocketId::from(1), tx, proto)
+    }
+
+    #[test]
+    fn when_closed_without_pending_send() {
+        let mut network = RecordingNetwork::default();
+        let mut socket = create_lingering_socket(false);
+
+        socket.close(&mut network);
+
+        assert_eq!(0, network.lingers);
+        assert!(network.closed);
+    }
+
+    #[test]
+    fn when_closed_with_pending_send_until_send_ack() {
+        let mut network = RecordingNetwork::default();
+        let mut socket = create_lingering_socket(true);
+
+        socket.close(&mut network);
+
+        assert_eq!(1, network.lingers);
+        assert!(!network.closed);
+
+        socket.on_send_ack(&mut network, EndpointId::from(1));
+
+        assert_eq!(1, network.cancels);
+        assert!(network.closed);
+    }
+
+    #[test]
+    fn when_closed_with_pending_send_until_linger_timeout() {
+        let mut network = RecordingNetwork::default();
+        let mut socket = create_lingering_socket(true);
+
+        socket.close(&mut network);
+
+        assert!(!network.closed);
+
+        socket.on_linger_timeout(&mut network);
+
+        assert_eq!(0, network.cancels);
+        assert!(network.closed);
+    }
+
+    #[test]
+    fn when_closed_with_pending_send_and_no_linger() {
+        let mut network = RecordingNetwork::default();
+        let mut socket = create_lingering_socket(true);
+
+        socket.config.set(ConfigOption::Linger(Duration::from_secs(0))).unwrap();
+        socket.close(&mut network);
+
+        assert_eq!(0, network.lingers);
+        assert!(network.closed);
+    }
+
+    #[test]
+    fn when_endpoint_is_closed_while_waiting_to_reconnect() {
+        let (tx, rx) = mpsc::channel();
+        let proto: ProtocolCtor = Box::new(|_| Box::new(TestProto) as Box<Protocol>);
+        let mut network = RecordingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let eid = EndpointId::from(1);
+
+        socket.connect(&mut network, String::from("test://fake"));
+
+        let ep_reply_rx = match rx.recv().unwrap() {
+            Reply::Connect(_, ep_reply_rx) => ep_reply_rx,
+            _ => panic!("Socket should have replied an ack to the connect request")
+        };
+
+        socket.on_pipe_error(&mut network, eid, other_io_error("test"));
+        socket.close_pipe(&mut network, eid);
+
+        assert_eq!(1, network.cancels);
+        match ep_reply_rx.recv().unwrap() {
+            endpoint::Reply::Close => {},
+            _ => panic!("Socket should have replied an ack to the close request")
+        }
+
+        let spec = EndpointSpec {
+            url: String::from("test://fake"),
+            desc: ::core::tests::new_test_endpoint_desc()
+        };
+
+        socket.reconnect(&mut network, eid, spec);
+
+        assert_eq!(0, network.reconnects);
+    }
+
+    #[test]
+    fn when_endpoint_state_is_queried() {
+        let (tx, rx) = mpsc::channel();
+        let proto: ProtocolCtor = Box::new(|_| Box::new(TestProto) as Box<Protocol>);
+        let mut network = RecordingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let eid = EndpointId::from(1);
+
+        socket.connect(&mut network, String::from("test://fake"));
+
+        let _ = rx.recv().unwrap();
+
+        assert_eq!(Some(String::from("test://fake")), socket.get_endpoint_url(eid));
+        assert_eq!(Some(endpoint::State::Connecting), socket.get_endpoint_state(eid));
+
+        socket.on_pipe_connected(eid);
+
+        assert_eq!(Some(endpoint::State::Handshaking), socket.get_endpoint_state(eid));
+
+        socket.on_pipe_error(&mut network, eid, other_io_error("test"));
+
+        assert_eq!(Some(endpoint::State::Backoff), socket.get_endpoint_state(eid));
+
+        socket.close_pipe(&mut network, eid);
+
+        assert_eq!(None, socket.get_endpoint_url(eid));
+        assert_eq!(None, socket.get_endpoint_state(eid));
+        assert_eq!(None, socket.get_endpoint_state(EndpointId::from(2)));
+    }
+
+    struct TimingOutProto(Sender<Reply>, bool);
+
+    impl Protocol for TimingOutProto {
+        fn id(&self) -> u16 {0}
+        fn peer_id(&self) -> u16 {0}
+        fn add_pipe(&mut self, _: &mut Context, _: EndpointId, _: Pipe) {}
+        fn remove_pipe(&mut self, _: &mut Context, _: EndpointId) -> Option<Pipe> {None}
+        fn send(&mut self, _: &mut Context, _: Message, _: Option<Scheduled>) {}
+        fn on_send_ack(&mut self, _: &mut Context, _: EndpointId) {}
+        fn on_send_timeout(&mut self, _: &mut Context) {
+            let _ = self.0.send(Reply::Err(timedout_io_error("Send timed out")));
+        }
+        fn on_send_ready(&mut self, _: &mut Context, _: EndpointId) {}
+        fn on_send_not_ready(&mut self, _: &mut Context, _: EndpointId) {}
+        fn recv(&mut self, _: &mut Context, _: Option<Scheduled>) {}
+        fn on_recv_ack(&mut self, _: &mut Context, _: EndpointId, _: Message) {}
+        fn on_recv_timeout(&mut self, _: &mut Context) {
+            let _ = self.0.send(Reply::Err(timedout_io_error("Recv timed out")));
+        }
+        fn on_recv_ready(&mut self, _: &mut Context, _: EndpointId) {}
+        fn on_recv_not_ready(&mut self, _: &mut Context, _: EndpointId) {}
+        fn is_send_ready(&self) -> bool { false }
+        fn is_recv_ready(&self) -> bool { false }
+        fn is_duplex(&self) -> bool { self.1 }
+        fn close(&mut self, _: &mut Context) {}
+    }
+
+    #[test]
+    fn when_pending_recv_is_cancelled() {
+        let (tx, rx) = mpsc::channel();
+        let proto: ProtocolCtor = Box::new(|reply_tx| Box::new(TimingOutProto(reply_tx, false)) as Box<Protocol>);
+        let mut network = RecordingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+
+        socket.set_option(&mut network, ConfigOption::RecvTimeout(Some(Duration::from_secs(1))));
+        let _ = rx.recv().unwrap();
+
+        socket.recv(&mut network);
+        assert!(rx.try_recv().is_err());
+
+        socket.cancel(&mut network);
+
+        assert_eq!(1, network.cancels);
+        match rx.try_recv().unwrap() {
+            Reply::Err(e) => assert_eq!(io::ErrorKind::TimedOut, e.kind()),
+            _ => panic!("Socket should have replied an error to the cancelled recv")
+        }
+
+        socket.cancel(&mut network);
+
+        assert_eq!(1, network.cancels);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn when_shared_handles_recv_at_the_same_time() {
+        let (tx, rx) = mpsc::channel();
+        let (tx_a, rx_a) = mpsc::channel();
+        let (tx_b, rx_b) = mpsc::channel();
+        let proto: ProtocolCtor = Box::new(|reply_tx| Box::new(TimingOutProto(reply_tx, false)) as Box<Protocol>);
+        let mut network = RecordingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+
+        socket.set_requester(Some(tx_a));
+        socket.recv(&mut network);
+        socket.set_requester(Some(tx_b));
+        socket.recv(&mut network);
+        socket.set_requester(None);
+        socket.run_deferred_operations(&mut network);
+
+        socket.on_recv_timeout(&mut network);
+        assert!(rx_a.try_recv().is_ok());
+        assert!(rx_b.try_recv().is_err());
+
+        socket.run_deferred_operations(&mut network);
+        socket.on_recv_timeout(&mut network);
+        assert!(rx_a.try_recv().is_err());
+        assert!(rx_b.try_recv().is_ok());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn when_shared_handles_send_and_recv_at_the_same_time() {
+        let (tx, rx) = mpsc::channel();
+        let (tx_a, rx_a) = mpsc::channel();
+        let (tx_b, rx_b) = mpsc::channel();
+        let (tx_c, rx_c) = mpsc::channel();
+        let proto: ProtocolCtor = Box::new(|reply_tx| Box::new(TimingOutProto(reply_tx, true)) as Box<Protocol>);
+        let mut network = RecordingNetwork::default();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+
+        socket.set_requester(Some(tx_a));
+        socket.recv(&mut network);
+        socket.set_requester(Some(tx_b));
+        socket.send(&mut network, Message::new());
+        socket.set_requester(Some(tx_c));
+        socket.send(&mut network, Message::new());
+        socket.set_requester(None);
+        socket.run_deferred_operations(&mut network);
+
+        socket.on_send_timeout(&mut network);
+        assert!(rx_a.try_recv().is_err());
+        assert!(rx_b.try_recv().is_ok());
+        assert!(rx_c.try_recv().is_err());
+
+        socket.run_deferred_operations(&mut network);
+        socket.on_recv_timeout(&mut network);
+        assert!(rx_a.try_recv().is_ok());
+        assert!(rx_c.try_recv().is_err());
+
+        socket.on_send_timeout(&mut network);
+        assert!(rx_c.try_recv().is_ok());
+        assert!(rx.try_recv().is_err());
+    }
 }
@@ -114,6 +114,11 @@ impl TestContextSensor {
         assert_eq!(0, self.recv_calls.len());
     }
 
+    pub fn assert_recv_from(&self, eid: EndpointId, times: usize) {
+        let count = self.recv_calls.iter().filter(|id| **id == eid).count();
+        assert_eq!(times, count);
+    }
+
     pub fn assert_one_recv_from(&self, eid: EndpointId) {
         assert_eq!(1, self.recv_calls.len());
 
@@ -140,6 +145,10 @@ impl TestContext {
             schedule_result: None
         }
     }
+
+    pub fn set_schedule_result(&mut self, scheduled: Scheduled) {
+        self.schedule_result = Some(scheduled);
+    }
 }
 
 impl Network for TestContext {
@@ -0,0 +1,127 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::sync::mpsc;
+use std::io;
+
+use super::*;
+use reactor;
+use core::{SocketId, ContextId, Message};
+use core::socket::{Request, Reply};
+use io_error::*;
+
+#[doc(hidden)]
+pub type ReplyReceiver = mpsc::Receiver<Reply>;
+
+#[doc(hidden)]
+pub struct RequestSender {
+    req_tx: EventLoopRequestSender,
+    socket_id: SocketId,
+    id: ContextId
+}
+
+impl RequestSender {
+    pub fn new(tx: EventLoopRequestSender, sid: SocketId, cid: ContextId) -> RequestSender {
+        RequestSender {
+            req_tx: tx,
+            socket_id: sid,
+            id: cid
+        }
+    }
+    fn send(&self, req: Request) -> io::Result<()> {
+        self.req_tx.send(reactor::Request::Socket(self.socket_id, req)).map_err(from_send_error)
+    }
+}
+
+/// Context of a socket, allowing several operations to be in progress on the same socket.
+///
+/// Obtained via the socket [create_context](struct.Socket.html#method.create_context) method.
+/// Each context has its own state, for example a `Req` context has its own pending request,
+/// and its reply is only delivered to the context that sent the request,
+/// while a `Rep` context replies to the last request it received,
+/// and a `Surveyor` context only receives the responses to its own survey.
+/// Send and receive timeouts are the ones of the socket.
+/// Dropping the context cancels any operation in progress.
+pub struct Context {
+    request_sender: RequestSender,
+    reply_receiver: ReplyReceiver
+}
+
+impl Context {
+    #[doc(hidden)]
+    pub fn new(request_tx: RequestSender, reply_rx: ReplyReceiver) -> Context {
+        Context {
+            request_sender: request_tx,
+            reply_receiver: reply_rx
+        }
+    }
+
+    /// Sends a buffer from this context.
+    pub fn send(&mut self, buffer: Vec<u8>) -> io::Result<()> {
+        self.send_msg(Message::from_body(buffer))
+    }
+
+    /// Sends a message from this context.
+    pub fn send_msg(&mut self, msg: Message) -> io::Result<()> {
+        let request = Request::ContextSend(self.request_sender.id, msg);
+
+        self.call(request, |reply| self.on_send_reply(reply))
+    }
+
+    fn on_send_reply(&self, reply: Reply) -> io::Result<()> {
+        match reply {
+            Reply::Send => Ok(()),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+    /// Receives a buffer in this context.
+    pub fn recv(&mut self) -> io::Result<Vec<u8>> {
+        self.recv_msg().map(|msg| msg.into())
+    }
+
+    /// Receives a message in this context.
+    pub fn recv_msg(&mut self) -> io::Result<Message> {
+        let request = Request::ContextRecv(self.request_sender.id);
+
+        self.call(request, |reply| self.on_recv_reply(reply))
+    }
+
+    fn on_recv_reply(&self, reply: Reply) -> io::Result<Message> {
+        match reply {
+            Reply::Recv(msg) => Ok(msg),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+    fn call<T, F : FnOnce(Reply) -> io::Result<T>>(&self, request: Request, process: F) -> io::Result<T> {
+        self.execute_request(request).and_then(process)
+    }
+
+    fn execute_request(&self, request: Request) -> io::Result<Reply> {
+        self.send_request(request).and_then(|_| self.recv_reply())
+    }
+
+    fn send_request(&self, request: Request) -> io::Result<()> {
+        self.request_sender.send(request)
+    }
+
+    fn recv_reply(&self) -> io::Result<Reply> {
+        self.reply_receiver.receive()
+    }
+
+    fn unexpected_reply<T>(&self) -> io::Result<T> {
+        Err(other_io_error("unexpected reply"))
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        let _ = self.send_request(Request::CloseContext(self.request_sender.id));
+    }
+}
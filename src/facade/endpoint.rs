@@ -4,14 +4,18 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
+use std::sync::mpsc;
 use std::io;
 
 use super::*;
 use reactor;
 use core::{SocketId, EndpointId};
-use core::endpoint::Request;
+use core::endpoint::{Request, Reply, State};
 use io_error::*;
 
+#[doc(hidden)]
+pub type ReplyReceiver = mpsc::Receiver<Reply>;
+
 #[doc(hidden)]
 pub struct RequestSender {
     req_tx: EventLoopRequestSender,
@@ -32,27 +36,159 @@ impl RequestSender {
     }
 }
 
+/// Pipe accepted by a bound endpoint, see [accepted_pipes](struct.Endpoint.html#method.accepted_pipes).
+#[derive(Debug)]
+pub struct AcceptedPipe {
+    /// Id of the pipe, unique within the session.
+    pub pipe: usize,
+    /// Address of the peer, `None` when the transport has no such address.
+    pub remote_addr: Option<String>
+}
+
+impl AcceptedPipe {
+    fn new(eid: EndpointId, remote_addr: Option<String>) -> AcceptedPipe {
+        AcceptedPipe {
+            pipe: eid.into(),
+            remote_addr: remote_addr
+        }
+    }
+}
+
 /// Endpoint of a socket.
 ///   
 /// Obtained via the socket [bind](struct.Socket.html#method.bind) or 
 /// [connect](struct.Socket.html#method.connect) methods.  
-/// Can only be used to close an endpoint.  
+/// Can be used to query the endpoint addresses and state, and to close it.  
 /// Note that `drop(Endpoint)` will **NOT** close it.
 pub struct Endpoint {
+    id: EndpointId,
     request_sender: RequestSender,
+    reply_receiver: ReplyReceiver,
     remote: bool
 }
 
 impl Endpoint {
     #[doc(hidden)]
-    pub fn new(request_tx: RequestSender, remote: bool) -> Endpoint {
+    pub fn new(request_tx: RequestSender, reply_rx: ReplyReceiver, remote: bool) -> Endpoint {
         Endpoint {
+            id: request_tx.id,
             request_sender: request_tx,
+            reply_receiver: reply_rx,
             remote: remote
         }
     }
 
+    /// Returns the id of the endpoint, unique within the session.
+    pub fn id(&self) -> usize {
+        self.id.into()
+    }
+
+    /// Removes the endpoint from the socket, cancelling any pending reconnection.
+    /// Returns once the endpoint is closed, or with the error that made it give up reconnecting.
     pub fn close(self) -> io::Result<()> {
-        self.request_sender.send(Request::Close(self.remote))
+        let request = Request::Close(self.remote);
+
+        self.call(request, |reply| self.on_close_reply(reply))
+    }
+
+    fn on_close_reply(&self, reply: Reply) -> io::Result<()> {
+        match reply {
+            Reply::Close => Ok(()),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+    /// Returns the url the endpoint was created with.
+    pub fn url(&self) -> io::Result<String> {
+        self.call(Request::Url, |reply| self.on_url_reply(reply))
+    }
+
+    fn on_url_reply(&self, reply: Reply) -> io::Result<String> {
+        match reply {
+            Reply::Url(url) => Ok(url),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+    /// Returns the local address of the endpoint, like the port a `tcp://127.0.0.1:0` bind actually got.
+    /// `None` when the transport has no such address, or while the endpoint is in backoff.
+    pub fn local_addr(&self) -> io::Result<Option<String>> {
+        let request = Request::LocalAddr(self.remote);
+
+        self.call(request, |reply| self.on_local_addr_reply(reply))
+    }
+
+    fn on_local_addr_reply(&self, reply: Reply) -> io::Result<Option<String>> {
+        match reply {
+            Reply::LocalAddr(addr) => Ok(addr),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+    /// Returns the address of the peer of a connected endpoint.
+    /// `None` for bound endpoints, see [accepted_pipes](#method.accepted_pipes) for their peers,
+    /// or when the connection is not established.
+    pub fn remote_addr(&self) -> io::Result<Option<String>> {
+        let request = Request::RemoteAddr(self.remote);
+
+        self.call(request, |reply| self.on_remote_addr_reply(reply))
+    }
+
+    fn on_remote_addr_reply(&self, reply: Reply) -> io::Result<Option<String>> {
+        match reply {
+            Reply::RemoteAddr(addr) => Ok(addr),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+    /// Returns the current state of the endpoint, bound endpoints are either active or in backoff.
+    pub fn state(&self) -> io::Result<State> {
+        self.call(Request::State, |reply| self.on_state_reply(reply))
+    }
+
+    fn on_state_reply(&self, reply: Reply) -> io::Result<State> {
+        match reply {
+            Reply::State(state) => Ok(state),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+    /// Returns the pipes a bound endpoint has accepted and that are still open, with the address of each peer.
+    /// Connected endpoints have no accepted pipes.
+    pub fn accepted_pipes(&self) -> io::Result<Vec<AcceptedPipe>> {
+        self.call(Request::AcceptedPipes, |reply| self.on_accepted_pipes_reply(reply))
+    }
+
+    fn on_accepted_pipes_reply(&self, reply: Reply) -> io::Result<Vec<AcceptedPipe>> {
+        match reply {
+            Reply::AcceptedPipes(pipes) => Ok(pipes.into_iter().map(|(eid, addr)| AcceptedPipe::new(eid, addr)).collect()),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+    fn unexpected_reply<T>(&self) -> io::Result<T> {
+        Err(other_io_error("unexpected reply"))
+    }
+
+    fn call<T, F : FnOnce(Reply) -> io::Result<T>>(&self, request: Request, process: F) -> io::Result<T> {
+        self.execute_request(request).and_then(process)
+    }
+
+    fn execute_request(&self, request: Request) -> io::Result<Reply> {
+        self.send_request(request).and_then(|_| self.recv_reply())
+    }
+
+    fn send_request(&self, request: Request) -> io::Result<()> {
+        self.request_sender.send(request)
+    }
+
+    fn recv_reply(&self) -> io::Result<Reply> {
+        self.reply_receiver.receive()
     }
 }
\ No newline at end of file
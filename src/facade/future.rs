@@ -0,0 +1,169 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{self, Poll};
+use std::sync::Arc;
+use std::io;
+
+use core::Message;
+use core::socket::{Request, Reply, ReplyWaker};
+use core::config::ConfigOption;
+use facade::socket::Socket;
+use facade::endpoint::Endpoint;
+use io_error::*;
+
+/// Asynchronous version of the [Socket](struct.Socket.html).
+///
+/// Obtained via the socket [into_async](struct.Socket.html#method.into_async) method.
+/// Each operation returns a future that is completed by the session background thread,
+/// and that borrows the socket mutably, so there is at most one operation in progress.
+/// Dropping a pending send or recv future cancels the operation in the backend,
+/// the reply of a dropped connect or bind future is simply discarded.
+pub struct AsyncSocket {
+    socket: Socket,
+    waker: Arc<ReplyWaker>,
+    stale_replies: usize
+}
+
+/// Future of an [AsyncSocket](struct.AsyncSocket.html) operation.
+/// The request is sent to the backend the first time the future is polled.
+pub struct SocketFuture<'a, T> {
+    socket: &'a mut AsyncSocket,
+    request: Option<Request>,
+    process: fn(&Socket, Reply) -> io::Result<T>,
+    cancellable: bool,
+    done: bool
+}
+
+impl AsyncSocket {
+    #[doc(hidden)]
+    pub fn new(socket: Socket, waker: Arc<ReplyWaker>) -> AsyncSocket {
+        AsyncSocket {
+            socket: socket,
+            waker: waker,
+            stale_replies: 0
+        }
+    }
+
+    /// Adds a remote endpoint to the socket, see [Socket::connect](struct.Socket.html#method.connect).
+    pub fn connect(&mut self, url: &str) -> SocketFuture<'_, Endpoint> {
+        SocketFuture::new(self, Request::Connect(From::from(url)), Socket::on_connect_reply, false)
+    }
+
+    /// Adds a local endpoint to the socket, see [Socket::bind](struct.Socket.html#method.bind).
+    pub fn bind(&mut self, url: &str) -> SocketFuture<'_, Endpoint> {
+        SocketFuture::new(self, Request::Bind(From::from(url)), Socket::on_bind_reply, false)
+    }
+
+    /// Sends a buffer, the future completes when the protocol has handed it to a peer.
+    pub fn send(&mut self, buffer: Vec<u8>) -> SocketFuture<'_, ()> {
+        self.send_msg(Message::from_body(buffer))
+    }
+
+    /// Sends a message, the future completes when the protocol has handed it to a peer.
+    pub fn send_msg(&mut self, msg: Message) -> SocketFuture<'_, ()> {
+        SocketFuture::new(self, Request::Send(msg, false), Socket::on_send_reply, true)
+    }
+
+    /// Receives a buffer.
+    pub fn recv(&mut self) -> SocketFuture<'_, Vec<u8>> {
+        SocketFuture::new(self, Request::Recv(false), on_recv_buffer_reply, true)
+    }
+
+    /// Receives a message.
+    pub fn recv_msg(&mut self) -> SocketFuture<'_, Message> {
+        SocketFuture::new(self, Request::Recv(false), Socket::on_recv_reply, true)
+    }
+
+    /// Sets a socket option, see [Socket::set_option](struct.Socket.html#method.set_option).
+    pub fn set_option(&mut self, cfg_opt: ConfigOption) -> SocketFuture<'_, ()> {
+        SocketFuture::new(self, Request::SetOption(cfg_opt), Socket::on_set_option_reply, false)
+    }
+}
+
+fn on_recv_buffer_reply(socket: &Socket, reply: Reply) -> io::Result<Vec<u8>> {
+    socket.on_recv_reply(reply).map(|msg| msg.into())
+}
+
+impl<'a, T> SocketFuture<'a, T> {
+    fn new(
+        socket: &'a mut AsyncSocket,
+        request: Request,
+        process: fn(&Socket, Reply) -> io::Result<T>,
+        cancellable: bool) -> SocketFuture<'a, T> {
+
+        SocketFuture {
+            socket: socket,
+            request: Some(request),
+            process: process,
+            cancellable: cancellable,
+            done: false
+        }
+    }
+
+    fn is_pending(&self) -> bool {
+        self.request.is_none() && !self.done
+    }
+
+    fn complete(&mut self, result: io::Result<T>) -> Poll<io::Result<T>> {
+        self.done = true;
+
+        Poll::Ready(result)
+    }
+}
+
+impl<'a, T> Future for SocketFuture<'a, T> {
+    type Output = io::Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context) -> Poll<io::Result<T>> {
+        let this = self.get_mut();
+
+        if this.done {
+            return Poll::Ready(Err(other_io_error("future polled after completion")));
+        }
+
+        if let Some(request) = this.request.take() {
+            if let Err(e) = this.socket.socket.send_request(request) {
+                return this.complete(Err(e));
+            }
+        }
+
+        // registering before checking the channel ensures a reply
+        // sent in between will wake the task up
+        this.socket.waker.register(cx.waker());
+
+        loop {
+            match this.socket.socket.try_recv_reply() {
+                Ok(Some(reply)) => {
+                    if this.socket.stale_replies > 0 {
+                        this.socket.stale_replies -= 1;
+                    } else {
+                        let result = (this.process)(&this.socket.socket, reply);
+
+                        return this.complete(result);
+                    }
+                },
+                Ok(None) => return Poll::Pending,
+                Err(e)   => return this.complete(Err(e))
+            }
+        }
+    }
+}
+
+impl<'a, T> Drop for SocketFuture<'a, T> {
+    fn drop(&mut self) {
+        if self.is_pending() {
+            // the backend replies exactly once per request, even when cancelled
+            self.socket.stale_replies += 1;
+
+            if self.cancellable {
+                let _ = self.socket.socket.send_request(Request::Cancel);
+            }
+        }
+    }
+}
@@ -6,7 +6,12 @@
 
 pub mod session;
 pub mod socket;
+pub mod future;
+pub mod typed;
+pub mod readiness;
+pub mod shared;
 pub mod endpoint;
+pub mod context;
 pub mod device;
 pub mod probe;
 
@@ -0,0 +1,43 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::io;
+
+use mio::{Registration, Evented, Poll, Token, Ready, PollOpt};
+
+/// Readiness handle of a socket, allowing it to join an application's own `mio` event loop.
+///
+/// Obtained via the socket [readiness](struct.Socket.html#method.readiness) method.
+/// The handle is readable while the socket can receive a message without blocking,
+/// and writable while it can send one, the same way a [Probe](struct.Probe.html) sees it.
+/// Readiness is maintained by the session background thread,
+/// so it may lag slightly behind the operations made on the socket.
+pub struct Readiness {
+    registration: Registration
+}
+
+impl Readiness {
+    #[doc(hidden)]
+    pub fn new(registration: Registration) -> Readiness {
+        Readiness {
+            registration: registration
+        }
+    }
+}
+
+impl Evented for Readiness {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        Evented::register(&self.registration, poll, token, interest, opts)
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt) -> io::Result<()> {
+        Evented::reregister(&self.registration, poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        Evented::deregister(&self.registration, poll)
+    }
+}
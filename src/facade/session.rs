@@ -113,6 +113,12 @@ impl Session {
         self.call(request, |reply| self.on_create_socket_reply(reply))
     }
 
+    /// Creates a socket whose type determines the protocol and the operations it supports,
+    /// for example a [PubSocket](struct.PubSocket.html) can only send.
+    pub fn create_typed_socket<T : typed::TypedSocket>(&mut self) -> io::Result<T> {
+        self.create_socket::<T::Protocol>().map(|socket| T::from_socket(socket, typed::sealed::Token))
+    }
+
     fn create_protocol_ctor<T>() -> ProtocolCtor 
     where T : Protocol + From<mpsc::Sender<core::socket::Reply>> + 'static
     {
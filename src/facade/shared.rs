@@ -0,0 +1,192 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::sync::{mpsc, Arc, Mutex};
+use std::io;
+
+use super::*;
+use core::Message;
+use core::socket::{Request, Reply};
+use core::config::ConfigOption;
+use facade::socket::{RequestSender, ReplyReceiver};
+use facade::endpoint::Endpoint;
+use io_error::*;
+
+/// Handle of a socket that can be cloned and shared between threads.
+///
+/// Obtained via the socket [into_shared](struct.Socket.html#method.into_shared) method.
+/// Each clone has its own reply channel, so clones can be used concurrently without locking each other,
+/// for example to send on the same `Push` or `Pub` socket from several worker threads.
+/// Operations of a single clone are serialized. The sends of the different clones are processed
+/// one at a time in the order they were requested, and so are their recvs, but a send does not wait
+/// for a recv of another clone on the protocols that can do both at once: `Push`, `Pull`, `Pub`, `Sub`, `Bus` and `Pair`.
+/// The other protocols tie a send to the next recv, so they process one operation of any kind at a time.
+/// The timeout of a waiting operation runs from the moment it is requested,
+/// and the non-blocking `try_send` and `try_recv` fail with `WouldBlock` instead of waiting.
+/// The socket is closed when the last clone is dropped.
+pub struct SharedSocket {
+    request_sender: RequestSender,
+    reply_sender: mpsc::Sender<Reply>,
+    reply_receiver: Mutex<ReplyReceiver>,
+    closer: Arc<Closer>
+}
+
+/// Closes the socket when the last clone is dropped.
+struct Closer {
+    request_sender: RequestSender,
+    reply_receiver: Mutex<ReplyReceiver>
+}
+
+impl SharedSocket {
+    #[doc(hidden)]
+    pub fn new(request_tx: RequestSender, reply_rx: ReplyReceiver) -> SharedSocket {
+        let closer = Closer {
+            request_sender: request_tx.clone(),
+            reply_receiver: Mutex::new(reply_rx)
+        };
+
+        SharedSocket::with_closer(request_tx, Arc::new(closer))
+    }
+
+    fn with_closer(request_tx: RequestSender, closer: Arc<Closer>) -> SharedSocket {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        SharedSocket {
+            request_sender: request_tx,
+            reply_sender: reply_tx,
+            reply_receiver: Mutex::new(reply_rx),
+            closer: closer
+        }
+    }
+
+    /// Adds a remote endpoint to the socket, see [Socket::connect](struct.Socket.html#method.connect).
+    pub fn connect(&self, url: &str) -> io::Result<Endpoint> {
+        let request = Request::Connect(From::from(url));
+
+        self.call(request, |reply| self.on_connect_reply(reply))
+    }
+
+    /// Adds a local endpoint to the socket, see [Socket::bind](struct.Socket.html#method.bind).
+    pub fn bind(&self, url: &str) -> io::Result<Endpoint> {
+        let request = Request::Bind(From::from(url));
+
+        self.call(request, |reply| self.on_bind_reply(reply))
+    }
+
+    /// Sends a buffer, see [Socket::send](struct.Socket.html#method.send).
+    pub fn send(&self, buffer: Vec<u8>) -> io::Result<()> {
+        self.send_msg(Message::from_body(buffer))
+    }
+
+    /// Sends a message, see [Socket::send_msg](struct.Socket.html#method.send_msg).
+    pub fn send_msg(&self, msg: Message) -> io::Result<()> {
+        self.call(Request::Send(msg, false), on_send_reply)
+    }
+
+    /// Non-blocking version of the send method.
+    pub fn try_send(&self, buffer: Vec<u8>) -> io::Result<()> {
+        self.try_send_msg(Message::from_body(buffer))
+    }
+
+    /// Non-blocking version of the send_msg method.
+    pub fn try_send_msg(&self, msg: Message) -> io::Result<()> {
+        self.call(Request::Send(msg, true), on_send_reply)
+    }
+
+    /// Receives a buffer, see [Socket::recv](struct.Socket.html#method.recv).
+    pub fn recv(&self) -> io::Result<Vec<u8>> {
+        self.recv_msg().map(|msg| msg.into())
+    }
+
+    /// Receives a message, see [Socket::recv_msg](struct.Socket.html#method.recv_msg).
+    pub fn recv_msg(&self) -> io::Result<Message> {
+        self.call(Request::Recv(false), on_recv_reply)
+    }
+
+    /// Non-blocking version of the recv method.
+    pub fn try_recv(&self) -> io::Result<Vec<u8>> {
+        self.try_recv_msg().map(|msg| msg.into())
+    }
+
+    /// Non-blocking version of the recv_msg method.
+    pub fn try_recv_msg(&self) -> io::Result<Message> {
+        self.call(Request::Recv(true), on_recv_reply)
+    }
+
+    /// Sets a socket option, the option applies to every clone.
+    /// See [ConfigOption](core/config/enum.ConfigOption.html) to get the list of options.
+    pub fn set_option(&self, cfg_opt: ConfigOption) -> io::Result<()> {
+        self.call(Request::SetOption(cfg_opt), on_set_option_reply)
+    }
+
+    fn call<T, F : FnOnce(Reply) -> io::Result<T>>(&self, request: Request, process: F) -> io::Result<T> {
+        // holding the receiver while waiting keeps concurrent calls on the same clone from stealing the reply
+        let reply_receiver = try!(self.reply_receiver.lock().map_err(|_| other_io_error("shared socket lock poisoned")));
+        let request = Request::Shared(self.reply_sender.clone(), Box::new(request));
+
+        try!(self.request_sender.send(request));
+
+        reply_receiver.receive().and_then(process)
+    }
+
+    fn on_connect_reply(&self, reply: Reply) -> io::Result<Endpoint> {
+        match reply {
+            Reply::Connect(id, reply_rx) => Ok(Endpoint::new(self.request_sender.child_sender(id), reply_rx, true)),
+            Reply::Err(e) => Err(e),
+            _ => Err(other_io_error("unexpected reply"))
+        }
+    }
+
+    fn on_bind_reply(&self, reply: Reply) -> io::Result<Endpoint> {
+        match reply {
+            Reply::Bind(id, reply_rx) => Ok(Endpoint::new(self.request_sender.child_sender(id), reply_rx, false)),
+            Reply::Err(e) => Err(e),
+            _ => Err(other_io_error("unexpected reply"))
+        }
+    }
+}
+
+impl Clone for SharedSocket {
+    fn clone(&self) -> SharedSocket {
+        SharedSocket::with_closer(self.request_sender.clone(), self.closer.clone())
+    }
+}
+
+impl Drop for Closer {
+    fn drop(&mut self) {
+        let _ = self.request_sender.send(Request::Close);
+
+        // the reply channel is closed once the socket is gone,
+        // which may take up to the linger duration
+        if let Ok(reply_receiver) = self.reply_receiver.get_mut() {
+            while reply_receiver.receive().is_ok() {}
+        }
+    }
+}
+
+fn on_send_reply(reply: Reply) -> io::Result<()> {
+    match reply {
+        Reply::Send   => Ok(()),
+        Reply::Err(e) => Err(e),
+        _ => Err(other_io_error("unexpected reply"))
+    }
+}
+
+fn on_recv_reply(reply: Reply) -> io::Result<Message> {
+    match reply {
+        Reply::Recv(msg) => Ok(msg),
+        Reply::Err(e)    => Err(e),
+        _ => Err(other_io_error("unexpected reply"))
+    }
+}
+
+fn on_set_option_reply(reply: Reply) -> io::Result<()> {
+    match reply {
+        Reply::SetOption => Ok(()),
+        Reply::Err(e)    => Err(e),
+        _ => Err(other_io_error("unexpected reply"))
+    }
+}
@@ -4,14 +4,17 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc};
+use std::mem;
 use std::io;
 use std::time::Duration;
 
+use mio::Registration;
+
 use super::*;
 use reactor;
 use core::{SocketId, Message, PollReq};
-use core::socket::{Request, Reply};
+use core::socket::{Request, Reply, ReplyWaker};
 use core::config::ConfigOption;
 use core;
 use io_error::*;
@@ -20,6 +23,7 @@ use io_error::*;
 pub type ReplyReceiver = mpsc::Receiver<Reply>;
 
 #[doc(hidden)]
+#[derive(Clone)]
 pub struct RequestSender {
     req_tx: EventLoopRequestSender,
     socket_id: SocketId
@@ -32,21 +36,63 @@ impl RequestSender {
             socket_id: id
         }
     }
-    fn child_sender(&self, eid: core::EndpointId) -> endpoint::RequestSender {
+    pub fn child_sender(&self, eid: core::EndpointId) -> endpoint::RequestSender {
         endpoint::RequestSender::new(self.req_tx.clone(), self.socket_id, eid)
     }
-    fn send(&self, req: Request) -> io::Result<()> {
+    fn context_sender(&self, cid: core::ContextId) -> context::RequestSender {
+        context::RequestSender::new(self.req_tx.clone(), self.socket_id, cid)
+    }
+    pub fn send(&self, req: Request) -> io::Result<()> {
         self.req_tx.send(reactor::Request::Socket(self.socket_id, req)).map_err(from_send_error)
     }
 }
 
+/// Responses collected by a survey, see [survey](struct.Socket.html#method.survey).
+pub struct SurveyResponses {
+    /// Responses received before the survey deadline, in arrival order.
+    pub responses: Vec<Message>,
+    /// Number of respondent pipes the survey was sent to.
+    pub surveyed: usize
+}
+
+impl SurveyResponses {
+    fn new(responses: Vec<Message>, surveyed: usize) -> SurveyResponses {
+        SurveyResponses {
+            responses: responses,
+            surveyed: surveyed
+        }
+    }
+}
+
+/// Outbound queue statistics of a pipe, see [dropped_messages](struct.Socket.html#method.dropped_messages).
+#[derive(Debug)]
+pub struct PipeDrops {
+    /// Id of the pipe, the same as the [endpoint](struct.Endpoint.html#method.id) id for connected endpoints.
+    pub pipe: usize,
+    /// Number of messages that were not sent to the pipe because its queue was full.
+    pub dropped: u64,
+    /// Number of messages waiting in the pipe queue.
+    pub queued: usize
+}
+
+impl PipeDrops {
+    fn new(eid: core::EndpointId, dropped: u64, queued: usize) -> PipeDrops {
+        PipeDrops {
+            pipe: eid.into(),
+            dropped: dropped,
+            queued: queued
+        }
+    }
+}
+
 /// Socket is what applications use to exchange messages.  
 ///   
 /// It is an abstraction of an application's "connection" to a messaging topology.
 /// Applications can have more than one Socket open at a time.
 pub struct Socket {
     request_sender: RequestSender,
-    reply_receiver: ReplyReceiver
+    reply_receiver: ReplyReceiver,
+    close_on_drop: bool
 }
 
 impl Socket {
@@ -54,7 +100,8 @@ impl Socket {
     pub fn new(request_tx: RequestSender, reply_rx: ReplyReceiver) -> Socket {
         Socket {
             request_sender: request_tx,
-            reply_receiver: reply_rx
+            reply_receiver: reply_rx,
+            close_on_drop: true
         }
     }
 
@@ -73,6 +120,41 @@ impl Socket {
         }
     } 
 
+    /// Converts the socket into an [AsyncSocket](struct.AsyncSocket.html),
+    /// whose operations return futures completed by the session background thread.
+    /// Options should preferably be set before the conversion.
+    pub fn into_async(self) -> io::Result<future::AsyncSocket> {
+        let waker = Arc::new(ReplyWaker::default());
+
+        try!(self.send_request(Request::SetReplyWaker(waker.clone())));
+
+        Ok(future::AsyncSocket::new(self, waker))
+    }
+
+    /// Converts the socket into a [SharedSocket](struct.SharedSocket.html),
+    /// a handle that can be cloned and used from several threads at once.
+    /// The socket is closed when the last handle is dropped.
+    pub fn into_shared(mut self) -> shared::SharedSocket {
+        let (_, closed_rx) = mpsc::channel();
+        let reply_rx = mem::replace(&mut self.reply_receiver, closed_rx);
+
+        // the shared handles close the socket, once the last one is dropped
+        self.close_on_drop = false;
+
+        shared::SharedSocket::new(self.request_sender.clone(), reply_rx)
+    }
+
+    /// Creates a readiness handle that can be registered in a `mio` event loop,
+    /// and becomes readable when the socket can receive and writable when it can send.
+    /// Only the last handle created for a socket is kept up to date.
+    pub fn readiness(&mut self) -> io::Result<readiness::Readiness> {
+        let (registration, set_readiness) = Registration::new2();
+
+        try!(self.send_request(Request::SetReadiness(set_readiness)));
+
+        Ok(readiness::Readiness::new(registration))
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* connect                                                                   */
@@ -93,11 +175,12 @@ impl Socket {
         self.call(request, |reply| self.on_connect_reply(reply))
     }
 
-    fn on_connect_reply(&self, reply: Reply) -> io::Result<endpoint::Endpoint> {
+    #[doc(hidden)]
+    pub fn on_connect_reply(&self, reply: Reply) -> io::Result<endpoint::Endpoint> {
         match reply {
-            Reply::Connect(id) => {
+            Reply::Connect(id, reply_rx) => {
                 let request_tx = self.request_sender.child_sender(id);
-                let ep = endpoint::Endpoint::new(request_tx, true);
+                let ep = endpoint::Endpoint::new(request_tx, reply_rx, true);
                 
                 Ok(ep)
             },
@@ -125,11 +208,12 @@ impl Socket {
         self.call(request, |reply| self.on_bind_reply(reply))
     }
 
-    fn on_bind_reply(&self, reply: Reply) -> io::Result<endpoint::Endpoint> {
+    #[doc(hidden)]
+    pub fn on_bind_reply(&self, reply: Reply) -> io::Result<endpoint::Endpoint> {
         match reply {
-            Reply::Bind(id) => {
+            Reply::Bind(id, reply_rx) => {
                 let request_tx = self.request_sender.child_sender(id);
-                let ep = endpoint::Endpoint::new(request_tx, false);
+                let ep = endpoint::Endpoint::new(request_tx, reply_rx, false);
                 
                 Ok(ep)
             },
@@ -170,7 +254,8 @@ impl Socket {
         self.call(request, |reply| self.on_send_reply(reply))
     }
 
-    fn on_send_reply(&self, reply: Reply) -> io::Result<()> {
+    #[doc(hidden)]
+    pub fn on_send_reply(&self, reply: Reply) -> io::Result<()> {
         match reply {
             Reply::Send => Ok(()),
             Reply::Err(e) => Err(e),
@@ -208,7 +293,8 @@ impl Socket {
         self.call(request, |reply| self.on_recv_reply(reply))
     }
 
-    fn on_recv_reply(&self, reply: Reply) -> io::Result<Message> {
+    #[doc(hidden)]
+    pub fn on_recv_reply(&self, reply: Reply) -> io::Result<Message> {
         match reply {
             Reply::Recv(msg) => Ok(msg),
             Reply::Err(e) => Err(e),
@@ -216,6 +302,59 @@ impl Socket {
         }
     }
 
+/*****************************************************************************/
+/*                                                                           */
+/* survey                                                                    */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// Sends a survey and returns all the responses received before the survey deadline.
+    /// Only `Surveyor` sockets support this, other socket types return an error
+    /// with the kind `InvalidInput`.
+    pub fn survey(&mut self, buffer: Vec<u8>) -> io::Result<SurveyResponses> {
+        self.survey_msg(Message::from_body(buffer))
+    }
+
+    /// Sends a survey message and returns all the responses received before the survey deadline.
+    pub fn survey_msg(&mut self, msg: Message) -> io::Result<SurveyResponses> {
+        let request = Request::Survey(msg);
+
+        self.call(request, |reply| self.on_survey_reply(reply))
+    }
+
+    fn on_survey_reply(&self, reply: Reply) -> io::Result<SurveyResponses> {
+        match reply {
+            Reply::Survey(responses, surveyed) => Ok(SurveyResponses::new(responses, surveyed)),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* context                                                                   */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// Creates a new context, that can send and receive independently from the socket and its other contexts.
+    /// Only `Req`, `Rep` and `Surveyor` sockets support contexts for now, other socket types return an error
+    /// with the kind `InvalidInput`.
+    pub fn create_context(&mut self) -> io::Result<context::Context> {
+        self.call(Request::CreateContext, |reply| self.on_create_context_reply(reply))
+    }
+
+    fn on_create_context_reply(&self, reply: Reply) -> io::Result<context::Context> {
+        match reply {
+            Reply::ContextCreated(id, reply_rx) => {
+                let request_tx = self.request_sender.context_sender(id);
+
+                Ok(context::Context::new(request_tx, reply_rx))
+            },
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* options                                                                   */
@@ -256,6 +395,20 @@ impl Socket {
         self.set_option(ConfigOption::RecvPriority(priority))
     }
 
+    /// Sets the size in bytes of the outbound queue of endpoints subsequently added to the socket.  
+    /// Messages are queued while the peer is busy, so short bursts do not block the sender.
+    /// Default value is 0, meaning no queue.
+    pub fn set_send_buffer(&mut self, size: usize) -> io::Result<()> {
+        self.set_option(ConfigOption::SendBuffer(size))
+    }
+
+    /// Sets the size in bytes of the inbound queue of endpoints subsequently added to the socket.  
+    /// Messages are read ahead until the queue is full.
+    /// Default value is 0, meaning no queue.
+    pub fn set_recv_buffer(&mut self, size: usize) -> io::Result<()> {
+        self.set_option(ConfigOption::RecvBuffer(size))
+    }
+
     /// This option, when set to `true`, disables Nagle’s algorithm.
     /// It also disables delaying of TCP acknowledgments. 
     /// Using this option improves latency at the expense of throughput.
@@ -264,6 +417,52 @@ impl Socket {
         self.set_option(ConfigOption::TcpNoDelay(value))
     }
 
+    /// Subscribes a `Sub` socket to the messages starting with the specified prefix.
+    /// The topic is matched byte per byte and does not need to be valid UTF-8.
+    /// An empty topic subscribes to every message.
+    pub fn subscribe(&mut self, topic: &[u8]) -> io::Result<()> {
+        self.set_option(ConfigOption::SubscribeBytes(topic.to_vec()))
+    }
+
+    /// Removes a subscription previously made on a `Sub` socket with the exact same prefix.
+    /// Subscriptions are counted, a topic subscribed several times stays active
+    /// until it has been unsubscribed as many times.
+    pub fn unsubscribe(&mut self, topic: &[u8]) -> io::Result<()> {
+        self.set_option(ConfigOption::UnsubscribeBytes(topic.to_vec()))
+    }
+
+    /// Returns the topics a `Sub` socket is currently subscribed to, in byte order.
+    /// Each topic is listed once, however many times it was subscribed.
+    /// Other socket types return an error with the kind `InvalidInput`.
+    pub fn subscriptions(&mut self) -> io::Result<Vec<Vec<u8>>> {
+        self.call(Request::Subscriptions, |reply| self.on_subscriptions_reply(reply))
+    }
+
+    fn on_subscriptions_reply(&self, reply: Reply) -> io::Result<Vec<Vec<u8>>> {
+        match reply {
+            Reply::Subscriptions(topics) => Ok(topics),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+    /// Returns, for each pipe of a `Pub` socket, how many messages were dropped
+    /// because the subscriber was too slow, see `ConfigOption::PubQueueDepth`.
+    /// Only the currently connected pipes are listed, the counter of a pipe
+    /// is discarded when it disconnects.
+    /// Other socket types return an error with the kind `InvalidInput`.
+    pub fn dropped_messages(&mut self) -> io::Result<Vec<PipeDrops>> {
+        self.call(Request::DroppedMessages, |reply| self.on_dropped_messages_reply(reply))
+    }
+
+    fn on_dropped_messages_reply(&self, reply: Reply) -> io::Result<Vec<PipeDrops>> {
+        match reply {
+            Reply::DroppedMessages(drops) => Ok(drops.into_iter().map(|(eid, dropped, queued)| PipeDrops::new(eid, dropped, queued)).collect()),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
     /// Sets a socket option.
     /// See [ConfigOption](core/config/enum.ConfigOption.html) to get the list of options.
     pub fn set_option(&mut self, cfg_opt: ConfigOption) -> io::Result<()> {
@@ -272,7 +471,8 @@ impl Socket {
         self.call(request, |reply| self.on_set_option_reply(reply))
     }
 
-    fn on_set_option_reply(&self, reply: Reply) -> io::Result<()> {
+    #[doc(hidden)]
+    pub fn on_set_option_reply(&self, reply: Reply) -> io::Result<()> {
         match reply {
             Reply::SetOption => Ok(()),
             Reply::Err(e)    => Err(e),
@@ -294,7 +494,8 @@ impl Socket {
         self.send_request(request).and_then(|_| self.recv_reply())
     }
 
-    fn send_request(&self, request: Request) -> io::Result<()> {
+    #[doc(hidden)]
+    pub fn send_request(&self, request: Request) -> io::Result<()> {
         self.request_sender.send(request)
     }
 
@@ -302,6 +503,15 @@ impl Socket {
         self.reply_receiver.receive()
     }
 
+    #[doc(hidden)]
+    pub fn try_recv_reply(&self) -> io::Result<Option<Reply>> {
+        match self.reply_receiver.try_recv() {
+            Ok(reply)                             => Ok(Some(reply)),
+            Err(mpsc::TryRecvError::Empty)        => Ok(None),
+            Err(mpsc::TryRecvError::Disconnected) => Err(other_io_error("evt channel closed"))
+        }
+    }
+
     fn unexpected_reply<T>(&self) -> io::Result<T> {
         Err(other_io_error("unexpected reply"))
     }
@@ -309,7 +519,14 @@ impl Socket {
 
 impl Drop for Socket {
     fn drop(&mut self) {
+        if !self.close_on_drop {
+            return;
+        }
+
         let _ = self.send_request(Request::Close);
-        let _ = self.recv_reply();
+
+        // the reply channel is closed once the socket is gone, 
+        // which may take up to the linger duration
+        while self.recv_reply().is_ok() {}
     }
 }
@@ -0,0 +1,577 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::time::Duration;
+use std::io;
+
+use core::Message;
+use core::config::{ConfigOption, DropPolicy};
+use facade::socket::{Socket, SurveyResponses, PipeDrops};
+use facade::endpoint::Endpoint;
+use facade::context::Context;
+use facade::readiness::Readiness;
+use proto;
+use self::sealed::Token;
+
+/// Accessors to the untyped socket, reachable from this crate only so that
+/// a typed socket cannot be turned into a socket of another protocol by accident.
+pub(crate) mod sealed {
+
+    use std::sync::mpsc::Sender;
+
+    use core::socket::{Protocol, Reply};
+    use facade::socket::Socket;
+
+    /// Proof that the caller belongs to this crate.
+    pub struct Token;
+
+    pub trait Sealed : Sized {
+        type Protocol : Protocol + From<Sender<Reply>> + 'static;
+
+        fn from_socket(socket: Socket, token: Token) -> Self;
+        fn socket(&mut self, token: Token) -> &mut Socket;
+        fn into_socket(self, token: Token) -> Socket;
+    }
+}
+
+impl<T : TypedSocket> From<T> for Socket {
+    /// Returns the untyped socket, to be used with devices, probes or the async facade.
+    /// This is an escape hatch: the untyped socket offers every operation,
+    /// whether its protocol supports it or not.
+    fn from(typed: T) -> Socket {
+        typed.into_socket(Token)
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* capabilities                                                              */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Socket bound to a protocol at compile time, created by
+/// [Session::create_typed_socket](struct.Session.html#method.create_typed_socket).
+///
+/// Only the operations supported by the protocol are available, sending is provided
+/// by [SendSocket](trait.SendSocket.html), receiving by [RecvSocket](trait.RecvSocket.html),
+/// and protocol specific options are methods of each socket type.
+/// This trait is sealed, the untyped socket can only be taken back explicitly,
+/// through [Socket::from](struct.Socket.html#impl-From%3CT%3E).
+pub trait TypedSocket : sealed::Sealed {
+    /// Adds a remote endpoint to the socket, see [Socket::connect](struct.Socket.html#method.connect).
+    fn connect(&mut self, url: &str) -> io::Result<Endpoint> {
+        self.socket(Token).connect(url)
+    }
+
+    /// Adds a local endpoint to the socket, see [Socket::bind](struct.Socket.html#method.bind).
+    fn bind(&mut self, url: &str) -> io::Result<Endpoint> {
+        self.socket(Token).bind(url)
+    }
+
+    /// Disables Nagle’s algorithm, see [Socket::set_tcp_nodelay](struct.Socket.html#method.set_tcp_nodelay).
+    fn set_tcp_nodelay(&mut self, value: bool) -> io::Result<()> {
+        self.socket(Token).set_tcp_nodelay(value)
+    }
+
+    /// Sets how long closing the socket waits for pending sends. Default value is 1 second.
+    fn set_linger(&mut self, linger: Duration) -> io::Result<()> {
+        self.socket(Token).set_option(ConfigOption::Linger(linger))
+    }
+
+    /// Sets how long to wait before re-establishing a broken connection. Default value is 0.1 second.
+    fn set_retry_ivl(&mut self, ivl: Duration) -> io::Result<()> {
+        self.socket(Token).set_option(ConfigOption::RetryIvl(ivl))
+    }
+
+    /// Sets the maximum reconnect interval of the exponential backoff, `None` disables the backoff.
+    fn set_retry_ivl_max(&mut self, ivl: Option<Duration>) -> io::Result<()> {
+        self.socket(Token).set_option(ConfigOption::RetryIvlMax(ivl))
+    }
+
+    /// Creates a readiness handle, see [Socket::readiness](struct.Socket.html#method.readiness).
+    fn readiness(&mut self) -> io::Result<Readiness> {
+        self.socket(Token).readiness()
+    }
+}
+
+/// Typed socket whose protocol can send messages.
+pub trait SendSocket : TypedSocket {
+    /// Sends a buffer, see [Socket::send](struct.Socket.html#method.send).
+    fn send(&mut self, buffer: Vec<u8>) -> io::Result<()> {
+        self.socket(Token).send(buffer)
+    }
+
+    /// Sends a message, see [Socket::send_msg](struct.Socket.html#method.send_msg).
+    fn send_msg(&mut self, msg: Message) -> io::Result<()> {
+        self.socket(Token).send_msg(msg)
+    }
+
+    /// Non-blocking version of the send method.
+    fn try_send(&mut self, buffer: Vec<u8>) -> io::Result<()> {
+        self.socket(Token).try_send(buffer)
+    }
+
+    /// Non-blocking version of the send_msg method.
+    fn try_send_msg(&mut self, msg: Message) -> io::Result<()> {
+        self.socket(Token).try_send_msg(msg)
+    }
+
+    /// Sets the timeout for send operation, see [Socket::set_send_timeout](struct.Socket.html#method.set_send_timeout).
+    fn set_send_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.socket(Token).set_send_timeout(timeout)
+    }
+
+    /// Sets outbound priority, see [Socket::set_send_priority](struct.Socket.html#method.set_send_priority).
+    fn set_send_priority(&mut self, priority: u8) -> io::Result<()> {
+        self.socket(Token).set_send_priority(priority)
+    }
+
+    /// Sets the size of the outbound pipe queue, see [Socket::set_send_buffer](struct.Socket.html#method.set_send_buffer).
+    fn set_send_buffer(&mut self, size: usize) -> io::Result<()> {
+        self.socket(Token).set_send_buffer(size)
+    }
+}
+
+/// Typed socket whose protocol can receive messages.
+pub trait RecvSocket : TypedSocket {
+    /// Receives a buffer, see [Socket::recv](struct.Socket.html#method.recv).
+    fn recv(&mut self) -> io::Result<Vec<u8>> {
+        self.socket(Token).recv()
+    }
+
+    /// Receives a message, see [Socket::recv_msg](struct.Socket.html#method.recv_msg).
+    fn recv_msg(&mut self) -> io::Result<Message> {
+        self.socket(Token).recv_msg()
+    }
+
+    /// Non-blocking version of the recv method.
+    fn try_recv(&mut self) -> io::Result<Vec<u8>> {
+        self.socket(Token).try_recv()
+    }
+
+    /// Non-blocking version of the recv_msg method.
+    fn try_recv_msg(&mut self) -> io::Result<Message> {
+        self.socket(Token).try_recv_msg()
+    }
+
+    /// Sets the timeout for recv operation, see [Socket::set_recv_timeout](struct.Socket.html#method.set_recv_timeout).
+    fn set_recv_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.socket(Token).set_recv_timeout(timeout)
+    }
+
+    /// Sets inbound priority, see [Socket::set_recv_priority](struct.Socket.html#method.set_recv_priority).
+    fn set_recv_priority(&mut self, priority: u8) -> io::Result<()> {
+        self.socket(Token).set_recv_priority(priority)
+    }
+
+    /// Sets the size of the inbound pipe queue, see [Socket::set_recv_buffer](struct.Socket.html#method.set_recv_buffer).
+    fn set_recv_buffer(&mut self, size: usize) -> io::Result<()> {
+        self.socket(Token).set_recv_buffer(size)
+    }
+
+    /// Sets the maximum size in bytes of the messages that can be received.
+    fn set_recv_max_size(&mut self, size: u64) -> io::Result<()> {
+        self.socket(Token).set_option(ConfigOption::RecvMaxSize(size))
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* pair                                                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Socket of the `Pair` protocol, see [Pair](proto/pair/struct.Pair.html).
+pub struct PairSocket {
+    socket: Socket
+}
+
+impl sealed::Sealed for PairSocket {
+    type Protocol = proto::pair::Pair;
+
+    fn from_socket(socket: Socket, _: Token) -> PairSocket {
+        PairSocket { socket: socket }
+    }
+    fn socket(&mut self, _: Token) -> &mut Socket {
+        &mut self.socket
+    }
+    fn into_socket(self, _: Token) -> Socket {
+        self.socket
+    }
+}
+
+impl TypedSocket for PairSocket {}
+
+impl SendSocket for PairSocket {}
+impl RecvSocket for PairSocket {}
+
+/// Socket of the `Pair1` protocol, see [Pair1](proto/pair1/struct.Pair1.html).
+pub struct Pair1Socket {
+    socket: Socket
+}
+
+impl Pair1Socket {
+    /// Sets the maximum number of hops a message can go through before being dropped.
+    pub fn set_max_ttl(&mut self, ttl: u8) -> io::Result<()> {
+        self.socket.set_option(ConfigOption::MaxTtl(ttl))
+    }
+}
+
+impl sealed::Sealed for Pair1Socket {
+    type Protocol = proto::pair1::Pair1;
+
+    fn from_socket(socket: Socket, _: Token) -> Pair1Socket {
+        Pair1Socket { socket: socket }
+    }
+    fn socket(&mut self, _: Token) -> &mut Socket {
+        &mut self.socket
+    }
+    fn into_socket(self, _: Token) -> Socket {
+        self.socket
+    }
+}
+
+impl TypedSocket for Pair1Socket {}
+
+impl SendSocket for Pair1Socket {}
+impl RecvSocket for Pair1Socket {}
+
+/*****************************************************************************/
+/*                                                                           */
+/* pub sub                                                                   */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Socket of the `Pub` protocol, see [Pub](proto/publ/struct.Pub.html).
+pub struct PubSocket {
+    socket: Socket
+}
+
+impl PubSocket {
+    /// Sets how many messages can be queued for a busy subscriber. Default value is 0, meaning no queue.
+    pub fn set_queue_depth(&mut self, depth: usize) -> io::Result<()> {
+        self.socket.set_option(ConfigOption::PubQueueDepth(depth))
+    }
+
+    /// Sets which message is dropped when the queue of a subscriber is full.
+    pub fn set_drop_policy(&mut self, policy: DropPolicy) -> io::Result<()> {
+        self.socket.set_option(ConfigOption::PubDropPolicy(policy))
+    }
+
+    /// Returns, for each pipe, how many messages were dropped because the subscriber was too slow.
+    /// Only the currently connected pipes are listed.
+    pub fn dropped_messages(&mut self) -> io::Result<Vec<PipeDrops>> {
+        self.socket.dropped_messages()
+    }
+}
+
+impl sealed::Sealed for PubSocket {
+    type Protocol = proto::publ::Pub;
+
+    fn from_socket(socket: Socket, _: Token) -> PubSocket {
+        PubSocket { socket: socket }
+    }
+    fn socket(&mut self, _: Token) -> &mut Socket {
+        &mut self.socket
+    }
+    fn into_socket(self, _: Token) -> Socket {
+        self.socket
+    }
+}
+
+impl TypedSocket for PubSocket {}
+
+impl SendSocket for PubSocket {}
+
+/// Socket of the `Sub` protocol, see [Sub](proto/sub/struct.Sub.html).
+pub struct SubSocket {
+    socket: Socket
+}
+
+impl SubSocket {
+    /// Subscribes to the messages starting with the specified prefix.
+    pub fn subscribe(&mut self, topic: &[u8]) -> io::Result<()> {
+        self.socket.subscribe(topic)
+    }
+
+    /// Removes a subscription previously made with the exact same prefix.
+    pub fn unsubscribe(&mut self, topic: &[u8]) -> io::Result<()> {
+        self.socket.unsubscribe(topic)
+    }
+
+    /// Returns the topics the socket is currently subscribed to, in byte order.
+    pub fn subscriptions(&mut self) -> io::Result<Vec<Vec<u8>>> {
+        self.socket.subscriptions()
+    }
+}
+
+impl sealed::Sealed for SubSocket {
+    type Protocol = proto::sub::Sub;
+
+    fn from_socket(socket: Socket, _: Token) -> SubSocket {
+        SubSocket { socket: socket }
+    }
+    fn socket(&mut self, _: Token) -> &mut Socket {
+        &mut self.socket
+    }
+    fn into_socket(self, _: Token) -> Socket {
+        self.socket
+    }
+}
+
+impl TypedSocket for SubSocket {}
+
+impl RecvSocket for SubSocket {}
+
+/*****************************************************************************/
+/*                                                                           */
+/* req rep                                                                   */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Socket of the `Req` protocol, see [Req](proto/req/struct.Req.html).
+pub struct ReqSocket {
+    socket: Socket
+}
+
+impl ReqSocket {
+    /// Sends a request and waits for its reply.
+    pub fn request(&mut self, buffer: Vec<u8>) -> io::Result<Vec<u8>> {
+        try!(self.socket.send(buffer));
+
+        self.socket.recv()
+    }
+
+    /// Sets how long to wait for a reply before resending the request. Default value is 1 minute.
+    pub fn set_resend_ivl(&mut self, ivl: Duration) -> io::Result<()> {
+        self.socket.set_option(ConfigOption::ReqResendIvl(ivl))
+    }
+
+    /// Creates a new context, allowing several requests in flight on the same socket.
+    pub fn create_context(&mut self) -> io::Result<Context> {
+        self.socket.create_context()
+    }
+}
+
+impl sealed::Sealed for ReqSocket {
+    type Protocol = proto::req::Req;
+
+    fn from_socket(socket: Socket, _: Token) -> ReqSocket {
+        ReqSocket { socket: socket }
+    }
+    fn socket(&mut self, _: Token) -> &mut Socket {
+        &mut self.socket
+    }
+    fn into_socket(self, _: Token) -> Socket {
+        self.socket
+    }
+}
+
+impl TypedSocket for ReqSocket {}
+
+impl SendSocket for ReqSocket {}
+impl RecvSocket for ReqSocket {}
+
+/// Socket of the `Rep` protocol, see [Rep](proto/rep/struct.Rep.html).
+pub struct RepSocket {
+    socket: Socket
+}
+
+impl RepSocket {
+    /// Sets the maximum number of hops a request can go through before being dropped.
+    pub fn set_max_ttl(&mut self, ttl: u8) -> io::Result<()> {
+        self.socket.set_option(ConfigOption::MaxTtl(ttl))
+    }
+
+    /// Creates a new context, allowing several requests to be processed in parallel on the same socket.
+    pub fn create_context(&mut self) -> io::Result<Context> {
+        self.socket.create_context()
+    }
+}
+
+impl sealed::Sealed for RepSocket {
+    type Protocol = proto::rep::Rep;
+
+    fn from_socket(socket: Socket, _: Token) -> RepSocket {
+        RepSocket { socket: socket }
+    }
+    fn socket(&mut self, _: Token) -> &mut Socket {
+        &mut self.socket
+    }
+    fn into_socket(self, _: Token) -> Socket {
+        self.socket
+    }
+}
+
+impl TypedSocket for RepSocket {}
+
+impl SendSocket for RepSocket {}
+impl RecvSocket for RepSocket {}
+
+/*****************************************************************************/
+/*                                                                           */
+/* pipeline                                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Socket of the `Push` protocol, see [Push](proto/push/struct.Push.html).
+pub struct PushSocket {
+    socket: Socket
+}
+
+impl sealed::Sealed for PushSocket {
+    type Protocol = proto::push::Push;
+
+    fn from_socket(socket: Socket, _: Token) -> PushSocket {
+        PushSocket { socket: socket }
+    }
+    fn socket(&mut self, _: Token) -> &mut Socket {
+        &mut self.socket
+    }
+    fn into_socket(self, _: Token) -> Socket {
+        self.socket
+    }
+}
+
+impl TypedSocket for PushSocket {}
+
+impl SendSocket for PushSocket {}
+
+/// Socket of the `Pull` protocol, see [Pull](proto/pull/struct.Pull.html).
+pub struct PullSocket {
+    socket: Socket
+}
+
+impl sealed::Sealed for PullSocket {
+    type Protocol = proto::pull::Pull;
+
+    fn from_socket(socket: Socket, _: Token) -> PullSocket {
+        PullSocket { socket: socket }
+    }
+    fn socket(&mut self, _: Token) -> &mut Socket {
+        &mut self.socket
+    }
+    fn into_socket(self, _: Token) -> Socket {
+        self.socket
+    }
+}
+
+impl TypedSocket for PullSocket {}
+
+impl RecvSocket for PullSocket {}
+
+/*****************************************************************************/
+/*                                                                           */
+/* survey                                                                    */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Socket of the `Surveyor` protocol, see [Surveyor](proto/surv/struct.Surveyor.html).
+pub struct SurveyorSocket {
+    socket: Socket
+}
+
+impl SurveyorSocket {
+    /// Sends a survey and returns all the responses received before the survey deadline.
+    pub fn survey(&mut self, buffer: Vec<u8>) -> io::Result<SurveyResponses> {
+        self.socket.survey(buffer)
+    }
+
+    /// Sends a survey message and returns all the responses received before the survey deadline.
+    pub fn survey_msg(&mut self, msg: Message) -> io::Result<SurveyResponses> {
+        self.socket.survey_msg(msg)
+    }
+
+    /// Sets how long responses are collected after a survey is sent. Default value is 1 second.
+    pub fn set_survey_deadline(&mut self, deadline: Duration) -> io::Result<()> {
+        self.socket.set_option(ConfigOption::SurveyDeadline(deadline))
+    }
+
+    /// Creates a new context, allowing several surveys to be open at once.
+    pub fn create_context(&mut self) -> io::Result<Context> {
+        self.socket.create_context()
+    }
+}
+
+impl sealed::Sealed for SurveyorSocket {
+    type Protocol = proto::surv::Surveyor;
+
+    fn from_socket(socket: Socket, _: Token) -> SurveyorSocket {
+        SurveyorSocket { socket: socket }
+    }
+    fn socket(&mut self, _: Token) -> &mut Socket {
+        &mut self.socket
+    }
+    fn into_socket(self, _: Token) -> Socket {
+        self.socket
+    }
+}
+
+impl TypedSocket for SurveyorSocket {}
+
+impl SendSocket for SurveyorSocket {}
+impl RecvSocket for SurveyorSocket {}
+
+/// Socket of the `Respondent` protocol, see [Respondent](proto/resp/struct.Respondent.html).
+pub struct RespondentSocket {
+    socket: Socket
+}
+
+impl RespondentSocket {
+    /// Sets the maximum number of hops a survey can go through before being dropped.
+    pub fn set_max_ttl(&mut self, ttl: u8) -> io::Result<()> {
+        self.socket.set_option(ConfigOption::MaxTtl(ttl))
+    }
+}
+
+impl sealed::Sealed for RespondentSocket {
+    type Protocol = proto::resp::Respondent;
+
+    fn from_socket(socket: Socket, _: Token) -> RespondentSocket {
+        RespondentSocket { socket: socket }
+    }
+    fn socket(&mut self, _: Token) -> &mut Socket {
+        &mut self.socket
+    }
+    fn into_socket(self, _: Token) -> Socket {
+        self.socket
+    }
+}
+
+impl TypedSocket for RespondentSocket {}
+
+impl SendSocket for RespondentSocket {}
+impl RecvSocket for RespondentSocket {}
+
+/*****************************************************************************/
+/*                                                                           */
+/* bus                                                                       */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Socket of the `Bus` protocol, see [Bus](proto/bus/struct.Bus.html).
+pub struct BusSocket {
+    socket: Socket
+}
+
+impl sealed::Sealed for BusSocket {
+    type Protocol = proto::bus::Bus;
+
+    fn from_socket(socket: Socket, _: Token) -> BusSocket {
+        BusSocket { socket: socket }
+    }
+    fn socket(&mut self, _: Token) -> &mut Socket {
+        &mut self.socket
+    }
+    fn into_socket(self, _: Token) -> Socket {
+        self.socket
+    }
+}
+
+impl TypedSocket for BusSocket {}
+
+impl SendSocket for BusSocket {}
+impl RecvSocket for BusSocket {}
@@ -29,6 +29,10 @@ pub fn timedout_io_error<E>(msg: E) -> io::Error where E: Into<Box<error::Error
     io::Error::new(io::ErrorKind::TimedOut, msg)
 }
 
+pub fn addr_in_use_io_error<E>(msg: E) -> io::Error where E: Into<Box<error::Error + Send + Sync>> {
+    io::Error::new(io::ErrorKind::AddrInUse, msg)
+}
+
 pub fn from_send_error<T>(send_error: mio_extras::channel::SendError<T>) -> io::Error {
     match send_error {
         mio_extras::channel::SendError::Io(e) => e,
@@ -63,6 +63,10 @@ extern crate mio;
 extern crate mio_uds;
 extern crate mio_extras;
 extern crate iovec;
+#[cfg(feature = "tls")]
+extern crate rustls;
+#[cfg(feature = "tls")]
+extern crate rustls_pemfile;
 
 #[cfg(windows)]
 extern crate mio_named_pipes;
@@ -85,19 +89,32 @@ mod io_error;
 
 pub use facade::session::SessionBuilder;
 pub use facade::session::Session;
-pub use facade::socket::Socket;
+pub use facade::socket::{Socket, SurveyResponses, PipeDrops};
+pub use facade::future::{AsyncSocket, SocketFuture};
+pub use facade::shared::SharedSocket;
+pub use facade::typed::{TypedSocket, SendSocket, RecvSocket};
+pub use facade::typed::{PairSocket, Pair1Socket, PubSocket, SubSocket, ReqSocket, RepSocket};
+pub use facade::typed::{PushSocket, PullSocket, SurveyorSocket, RespondentSocket, BusSocket};
 pub use facade::device::Device;
 pub use facade::probe::Probe;
-pub use facade::endpoint::Endpoint;
+pub use facade::readiness::Readiness;
+pub use facade::endpoint::{Endpoint, AcceptedPipe};
+pub use core::endpoint::State as EndpointState;
+pub use facade::context::Context;
 pub use core::Message;
 pub use core::PollReq;
 pub use core::PollRes;
-pub use core::config::ConfigOption;
+pub use core::config::{ConfigOption, DropPolicy};
 
 pub use transport::tcp::Tcp;
 pub use transport::ipc::Ipc;
+pub use transport::inproc::Inproc;
+pub use transport::ws::Ws;
+#[cfg(feature = "tls")]
+pub use transport::tls::Tls;
 
 pub use proto::pair::Pair;
+pub use proto::pair1::Pair1;
 pub use proto::publ::Pub;
 pub use proto::sub::Sub;
 pub use proto::req::Req;
@@ -107,6 +124,7 @@ pub use proto::pull::Pull;
 pub use proto::surv::Surveyor;
 pub use proto::resp::Respondent;
 pub use proto::bus::Bus;
+pub use proto::raw::{RawReq, RawRep, RawSurveyor, RawRespondent, RawPair1};
 
 #[cfg(test)]
 mod tests {
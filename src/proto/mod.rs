@@ -7,6 +7,7 @@
 //! Scalability protocols provided by scaproust
 
 pub mod pair;
+pub mod pair1;
 pub mod push;
 pub mod pull;
 pub mod req;
@@ -16,9 +17,11 @@ pub mod sub;
 pub mod surv;
 pub mod resp;
 pub mod bus;
+pub mod raw;
 
 mod pipes;
 mod priolist;
+mod trie;
 mod policy;
 
 use core::Scheduled;
@@ -46,6 +49,18 @@ pub type Timeout = Option<Scheduled>;
 pub const PAIR:       u16 = (    16)    ;
 
 
+/// **Polyamorous one-to-many protocol**   
+///   
+/// Version 1 of the pair protocol, where a single socket can be connected to many peers.
+/// Each received message carries the id of the pipe it came from in its header, 
+/// and sending a message with that header sends it back to the same peer. 
+/// A message sent without header goes to any available peer.
+/// If the requested peer is not connected anymore, the message is silently dropped.
+/// Each device a message goes through increments its hop count,
+/// messages travelling through more than 8 devices are discarded.
+pub const PAIR1:      u16 = (    16) + 1;
+
+
 /// **Publish/subscribe protocol**   
 ///   
 /// Broadcasts messages to multiple destinations.
@@ -58,6 +73,7 @@ pub const PAIR:       u16 = (    16)    ;
 /// Subscribing via [`Socket::set_option`](struct.Socket.html#method.set_option) and [`ConfigOption::Subscribe`](../enum.ConfigOption.html#variant.Subscribe)
 /// Will match any message with intial 5 bytes being "Hello", for example, message "Hello, World!" will match.
 /// Topic with zero length matches any message.
+/// Topics that are not valid UTF-8 can be subscribed with [`Socket::subscribe`](../struct.Socket.html#method.subscribe).
 /// If the socket is subscribed to multiple topics, 
 /// message matching any of them will be delivered to the user.
 /// Since the filtering is performed on the Subscriber side, 
@@ -65,6 +81,8 @@ pub const PAIR:       u16 = (    16)    ;
 /// The entire message, including the topic, is delivered to the user.  
 ///   
 /// This socket is used to distribute messages to multiple destinations. Receive operation is not defined.
+/// Messages for a subscriber whose pipe is busy can be queued, up to [`ConfigOption::PubQueueDepth`](../enum.ConfigOption.html#variant.PubQueueDepth),
+/// beyond that they are dropped and counted.
 pub const PUB:        u16 = (2 * 16)    ;
 
 
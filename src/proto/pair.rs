@@ -16,13 +16,20 @@ use io_error::*;
 
 pub struct Pair {
     inner: Inner,
-    state: Option<State>
+    send_state: Option<SendState>,
+    recv_state: Option<RecvState>
 }
 
-enum State {
+// Sends and recvs go through the same pipe but do not depend on each other,
+// so each has its own state and both can be pending at once.
+enum SendState {
     Idle,
     Sending(EndpointId, Rc<Message>, Timeout),
-    SendOnHold(Rc<Message>, Timeout),
+    SendOnHold(Rc<Message>, Timeout)
+}
+
+enum RecvState {
+    Idle,
     Receiving(EndpointId, Timeout),
     RecvOnHold(Timeout)
 }
@@ -30,6 +37,8 @@ enum State {
 struct Inner {
     reply_tx: Sender<Reply>,
     pipe: Option<(EndpointId, Pipe)>,
+    prefetching: Option<EndpointId>,
+    prefetched_msg: Option<Message>,
     send_ready: bool,
     recv_ready: bool
 }
@@ -42,25 +51,41 @@ struct Inner {
 
 impl Pair {
 
-    fn apply<F>(&mut self, ctx: &mut Context, transition: F) where F : FnOnce(State, &mut Context, &mut Inner) -> State {
-        if let Some(old_state) = self.state.take() {
+    fn apply_send<F>(&mut self, ctx: &mut Context, transition: F) where F : FnOnce(SendState, &mut Context, &mut Inner) -> SendState {
+        if let Some(old_state) = self.send_state.take() {
             #[cfg(debug_assertions)] let old_name = old_state.name();
             let was_send_ready = self.inner.send_ready;
-            let was_recv_ready = self.inner.recv_ready;
+            let was_recv_ready = self.inner.is_recv_ready();
             let new_state = transition(old_state, ctx, &mut self.inner);
-            let is_send_ready = self.inner.send_ready;
-            let is_recv_ready = self.inner.recv_ready;
             #[cfg(debug_assertions)] let new_name = new_state.name();
 
-            self.state = Some(new_state);
+            self.send_state = Some(new_state);
+            self.check_ready_changes(ctx, was_send_ready, was_recv_ready);
 
-            ctx.check_send_ready_change(was_send_ready, is_send_ready);
-            ctx.check_recv_ready_change(was_recv_ready, is_recv_ready);
+            #[cfg(debug_assertions)] debug!("[{:?}] switch send from {} to {}", ctx, old_name, new_name);
+        }
+    }
 
-            #[cfg(debug_assertions)] debug!("[{:?}] switch from {} to {}", ctx, old_name, new_name);
+    fn apply_recv<F>(&mut self, ctx: &mut Context, transition: F) where F : FnOnce(RecvState, &mut Context, &mut Inner) -> RecvState {
+        if let Some(old_state) = self.recv_state.take() {
+            #[cfg(debug_assertions)] let old_name = old_state.name();
+            let was_send_ready = self.inner.send_ready;
+            let was_recv_ready = self.inner.is_recv_ready();
+            let new_state = transition(old_state, ctx, &mut self.inner);
+            #[cfg(debug_assertions)] let new_name = new_state.name();
+
+            self.recv_state = Some(new_state);
+            self.check_ready_changes(ctx, was_send_ready, was_recv_ready);
+
+            #[cfg(debug_assertions)] debug!("[{:?}] switch recv from {} to {}", ctx, old_name, new_name);
         }
     }
 
+    fn check_ready_changes(&self, ctx: &mut Context, was_send_ready: bool, was_recv_ready: bool) {
+        ctx.check_send_ready_change(was_send_ready, self.inner.send_ready);
+        ctx.check_recv_ready_change(was_recv_ready, self.inner.is_recv_ready());
+    }
+
 }
 
 #[doc(hidden)]
@@ -70,10 +95,13 @@ impl From<Sender<Reply>> for Pair {
             inner: Inner {
                 reply_tx: tx,
                 pipe: None,
+                prefetching: None,
+                prefetched_msg: None,
                 send_ready: false,
                 recv_ready: false
             },
-            state: Some(State::Idle)
+            send_state: Some(SendState::Idle),
+            recv_state: Some(RecvState::Idle)
         }
     }
 }
@@ -95,46 +123,60 @@ impl Protocol for Pair {
         let pipe = self.inner.remove_pipe(ctx, eid);
 
         if pipe.is_some() {
-            self.apply(ctx, |s, ctx, inner| s.on_pipe_removed(ctx, inner, eid));
+            self.apply_send(ctx, |s, ctx, inner| {
+                inner.on_pipe_removed(ctx);
+                s.on_pipe_removed(eid)
+            });
+            self.apply_recv(ctx, |s, _, _| s.on_pipe_removed(eid));
         }
 
         pipe
     }
     fn send(&mut self, ctx: &mut Context, msg: Message, timeout: Timeout) {
-        self.apply(ctx, |s, ctx, inner| s.send(ctx, inner, Rc::new(msg), timeout))
+        self.apply_send(ctx, |s, ctx, inner| s.send(ctx, inner, Rc::new(msg), timeout))
     }
     fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_send_ack(ctx, inner, eid))
+        self.apply_send(ctx, |s, ctx, inner| s.on_send_ack(ctx, inner, eid))
     }
     fn on_send_timeout(&mut self, ctx: &mut Context) {
-        self.apply(ctx, |s, ctx, inner| s.on_send_timeout(ctx, inner))
+        self.apply_send(ctx, |s, ctx, inner| s.on_send_timeout(ctx, inner))
     }
     fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_send_ready(ctx, inner, eid))
+        self.apply_send(ctx, |s, ctx, inner| s.on_send_ready(ctx, inner, eid))
     }
     fn on_send_not_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_send_not_ready(ctx, inner, eid))
+        self.apply_send(ctx, |s, ctx, inner| s.on_send_not_ready(ctx, inner, eid))
     }    
     fn recv(&mut self, ctx: &mut Context, timeout: Timeout) {
-        self.apply(ctx, |s, ctx, inner| s.recv(ctx, inner, timeout))
+        self.apply_recv(ctx, |s, ctx, inner| s.recv(ctx, inner, timeout))
     }
     fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, msg: Message) {
-        self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg))
+        self.apply_recv(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg))
     }
     fn on_recv_timeout(&mut self, ctx: &mut Context) {
-        self.apply(ctx, |s, ctx, inner| s.on_recv_timeout(ctx, inner))
+        self.apply_recv(ctx, |s, ctx, inner| s.on_recv_timeout(ctx, inner))
     }
     fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_recv_ready(ctx, inner, eid))
+        self.apply_recv(ctx, |s, ctx, inner| s.on_recv_ready(ctx, inner, eid))
     }
     fn on_recv_not_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_recv_not_ready(ctx, inner, eid))
+        self.apply_recv(ctx, |s, ctx, inner| s.on_recv_not_ready(ctx, inner, eid))
     }
     fn is_send_ready(&self) -> bool {
         self.inner.send_ready
     }
     fn is_recv_ready(&self) -> bool {
-        self.inner.recv_ready
+        self.inner.is_recv_ready()
+    }
+    fn is_duplex(&self) -> bool {
+        true
+    }
+    fn has_pending_send(&self) -> bool {
+        match self.send_state {
+            Some(SendState::Sending(..))    |
+            Some(SendState::SendOnHold(..)) => true,
+            _ => false
+        }
     }
     fn close(&mut self, ctx: &mut Context) {
         self.inner.close(ctx)
@@ -143,127 +185,142 @@ impl Protocol for Pair {
 
 /*****************************************************************************/
 /*                                                                           */
-/* State                                                                     */
+/* send                                                                      */
 /*                                                                           */
 /*****************************************************************************/
 
-impl State {
+impl SendState {
 
     #[cfg(debug_assertions)]
     fn name(&self) -> &'static str {
         match *self {
-            State::Idle           => "Idle",
-            State::Sending(..)    => "Sending",
-            State::SendOnHold(..) => "SendOnHold",
-            State::Receiving(..)  => "Receiving",
-            State::RecvOnHold(..) => "RecvOnHold"
+            SendState::Idle           => "Idle",
+            SendState::Sending(..)    => "Sending",
+            SendState::SendOnHold(..) => "SendOnHold"
         }
     }
 
-    fn on_pipe_removed(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
-        inner.on_pipe_removed(ctx);
-
+    fn on_pipe_removed(self, eid: EndpointId) -> SendState {
         match self {
-            State::Sending(id, msg, timeout) => {
+            SendState::Sending(id, msg, timeout) => {
                 if id == eid {
-                    State::SendOnHold(msg, timeout)
+                    SendState::SendOnHold(msg, timeout)
                 } else {
-                    State::Sending(id, msg, timeout)
+                    SendState::Sending(id, msg, timeout)
                 }
             },
-            State::Receiving(id, timeout) => {
-                if id == eid {
-                    State::RecvOnHold(timeout)
-                } else {
-                    State::Receiving(id, timeout)
-                }
-            }
             any => any
         }
     }
 
-/*****************************************************************************/
-/*                                                                           */
-/* send                                                                      */
-/*                                                                           */
-/*****************************************************************************/
-
-    fn send(self, ctx: &mut Context, inner: &mut Inner, msg: Rc<Message>, timeout: Timeout) -> State {
+    fn send(self, ctx: &mut Context, inner: &mut Inner, msg: Rc<Message>, timeout: Timeout) -> SendState {
         if let Some(eid) = inner.send(ctx, msg.clone()) {
-            State::Sending(eid, msg, timeout)
+            SendState::Sending(eid, msg, timeout)
         } else {
-            State::SendOnHold(msg, timeout)
+            SendState::SendOnHold(msg, timeout)
         }
     }
-    fn on_send_ack(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
+    fn on_send_ack(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> SendState {
         match self {
-            State::Sending(id, msg, timeout) => {
+            SendState::Sending(id, msg, timeout) => {
                 if id == eid {
                     inner.on_send_ack(ctx, timeout);
-                    State::Idle
+                    SendState::Idle
                 } else {
-                    State::Sending(id, msg, timeout)
+                    SendState::Sending(id, msg, timeout)
                 }
             },
             any => any
         }
     }
-    fn on_send_timeout(self, _: &mut Context, inner: &mut Inner) -> State {
+    fn on_send_timeout(self, _: &mut Context, inner: &mut Inner) -> SendState {
         inner.on_send_timeout();
 
-        State::Idle
+        SendState::Idle
     }
-    fn on_send_ready(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
+    fn on_send_ready(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> SendState {
         inner.on_send_ready(eid);
 
         match self {
-            State::SendOnHold(msg, timeout) => State::Idle.send(ctx, inner, msg, timeout),
+            SendState::SendOnHold(msg, timeout) => SendState::Idle.send(ctx, inner, msg, timeout),
             any => any
         }
     }
-    fn on_send_not_ready(self, _: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
+    fn on_send_not_ready(self, _: &mut Context, inner: &mut Inner, eid: EndpointId) -> SendState {
         inner.on_send_not_ready(eid);
         self
     }
-    
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* recv                                                                      */
 /*                                                                           */
 /*****************************************************************************/
 
-    fn recv(self, ctx: &mut Context, inner: &mut Inner, timeout: Timeout) -> State {
-        inner.recv(ctx).map_or_else(
-            |   | State::RecvOnHold(timeout),
-            |eid| State::Receiving(eid, timeout))
+impl RecvState {
+
+    #[cfg(debug_assertions)]
+    fn name(&self) -> &'static str {
+        match *self {
+            RecvState::Idle           => "Idle",
+            RecvState::Receiving(..)  => "Receiving",
+            RecvState::RecvOnHold(..) => "RecvOnHold"
+        }
     }
-    fn on_recv_ack(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId, msg: Message) -> State {
+
+    fn on_pipe_removed(self, eid: EndpointId) -> RecvState {
         match self {
-            State::Receiving(id, timeout) => {
+            RecvState::Receiving(id, timeout) => {
                 if id == eid {
-                    inner.on_recv_ack(ctx, timeout, msg);
-                    State::Idle
+                    RecvState::RecvOnHold(timeout)
                 } else {
-                    State::Receiving(id, timeout)
+                    RecvState::Receiving(id, timeout)
                 }
             },
             any => any
         }
     }
-    fn on_recv_timeout(self, _: &mut Context, inner: &mut Inner) -> State {
+
+    fn recv(self, ctx: &mut Context, inner: &mut Inner, timeout: Timeout) -> RecvState {
+        if let Some(msg) = inner.prefetched_msg.take() {
+            inner.on_recv_ack(ctx, timeout, msg);
+            inner.prefetch(ctx);
+
+            return RecvState::Idle;
+        }
+
+        inner.prefetch(ctx).map_or_else(
+            |   | RecvState::RecvOnHold(timeout),
+            |eid| RecvState::Receiving(eid, timeout))
+    }
+    fn on_recv_ack(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId, msg: Message) -> RecvState {
+        if !inner.on_prefetch_ack(eid, msg) {
+            return self;
+        }
+
+        match self {
+            RecvState::Receiving(_, timeout) => RecvState::Idle.recv(ctx, inner, timeout),
+            any => any
+        }
+    }
+    fn on_recv_timeout(self, _: &mut Context, inner: &mut Inner) -> RecvState {
         inner.on_recv_timeout();
 
-        State::Idle
+        RecvState::Idle
     }
-    fn on_recv_ready(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
+    fn on_recv_ready(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> RecvState {
         inner.on_recv_ready(eid);
 
         match self {
-            State::RecvOnHold(timeout) => State::Idle.recv(ctx, inner, timeout),
-            any => any
+            RecvState::RecvOnHold(timeout) => RecvState::Idle.recv(ctx, inner, timeout),
+            any => {
+                inner.prefetch(ctx);
+                any
+            }
         }
     }
-    fn on_recv_not_ready(self, _: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
+    fn on_recv_not_ready(self, _: &mut Context, inner: &mut Inner, eid: EndpointId) -> RecvState {
         inner.on_recv_not_ready(eid);
         self
     }
@@ -297,6 +354,7 @@ impl Inner {
     fn on_pipe_removed(&mut self, _: &mut Context) {
         self.send_ready = false;
         self.recv_ready = false;
+        self.prefetching = None;
     }
     fn send(&mut self, ctx: &mut Context, msg: Rc<Message>) -> Option<EndpointId> {
         if self.send_ready == false {
@@ -341,6 +399,25 @@ impl Inner {
             Some(*eid)
         })
     }
+    fn prefetch(&mut self, ctx: &mut Context) -> Option<EndpointId> {
+        if self.prefetching.is_none() && self.prefetched_msg.is_none() {
+            self.prefetching = self.recv(ctx);
+        }
+
+        self.prefetching
+    }
+    fn on_prefetch_ack(&mut self, eid: EndpointId, msg: Message) -> bool {
+        if self.prefetching != Some(eid) {
+            return false;
+        }
+
+        self.prefetching = None;
+        self.prefetched_msg = Some(msg);
+        true
+    }
+    fn is_recv_ready(&self) -> bool {
+        self.prefetched_msg.is_some()
+    }
     fn on_recv_ready(&mut self, eid: EndpointId) {
         if self.pipe.as_ref().map(|&(ref id, _)| *id) == Some(eid) {
             self.recv_ready = true;
@@ -626,7 +703,7 @@ mod tests {
     }
 
     #[test]
-    fn when_recv_starts_event_is_raised() {
+    fn when_msg_is_prefetched_event_is_raised() {
         let (tx, _) = mpsc::channel();
         let mut pair = Pair::from(tx);
         let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
@@ -636,9 +713,10 @@ mod tests {
 
         pair.add_pipe(&mut ctx, eid, pipe);
         pair.on_recv_ready(&mut ctx, eid);
-        pair.recv(&mut ctx, None);
         pair.on_recv_ack(&mut ctx, eid, Message::new());
+        pair.recv(&mut ctx, None);
         pair.on_recv_ready(&mut ctx, eid);
+        pair.on_recv_ack(&mut ctx, eid, Message::new());
 
         let sensor = ctx_sensor.borrow();
         let raised_evts = sensor.get_raised_events();
@@ -671,8 +749,8 @@ mod tests {
     }
 
     #[test]
-    fn when_recv_ready_pipe_is_removed_event_is_raised() {
-        let (tx, _) = mpsc::channel();
+    fn when_prefetched_msg_pipe_is_removed_msg_can_still_be_received() {
+        let (tx, rx) = mpsc::channel();
         let mut pair = Pair::from(tx);
         let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
         let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
@@ -681,13 +759,79 @@ mod tests {
 
         pair.add_pipe(&mut ctx, eid, pipe);
         pair.on_recv_ready(&mut ctx, eid);
+        pair.on_recv_ack(&mut ctx, eid, Message::new());
         pair.remove_pipe(&mut ctx, eid);
 
-        let sensor = ctx_sensor.borrow();
-        let raised_evts = sensor.get_raised_events();
+        assert!(pair.is_recv_ready());
+        assert_eq!(1, ctx_sensor.borrow().get_raised_events().len());
+        assert_eq!(Event::CanRecv(true), ctx_sensor.borrow().get_raised_events()[0]);
 
-        assert_eq!(2, raised_evts.len());
-        assert_eq!(Event::CanRecv(true), raised_evts[0]);
-        assert_eq!(Event::CanRecv(false), raised_evts[1]);
+        pair.recv(&mut ctx, None);
+
+        let reply = rx.try_recv().expect("facade should have been sent a reply !");
+        let is_reply_ok = match reply {
+            Reply::Recv(_) => true,
+            _ => false
+        };
+        assert!(is_reply_ok);
+        assert!(!pair.is_recv_ready());
+    }
+
+    #[test]
+    fn when_msg_is_prefetched_while_sending_it_is_kept() {
+        let (tx, rx) = mpsc::channel();
+        let mut pair = Pair::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(7);
+        let pipe = new_test_pipe(eid);
+
+        pair.add_pipe(&mut ctx, eid, pipe);
+        pair.on_send_ready(&mut ctx, eid);
+        pair.on_recv_ready(&mut ctx, eid);
+        pair.send(&mut ctx, Message::new(), None);
+        pair.on_recv_ack(&mut ctx, eid, Message::from_body(vec![65]));
+        pair.on_send_ack(&mut ctx, eid);
+        let _ = rx.try_recv().expect("facade should have been sent a reply !");
+
+        pair.recv(&mut ctx, None);
+
+        let reply = rx.try_recv().expect("facade should have been sent a reply !");
+        let body = match reply {
+            Reply::Recv(msg) => msg.get_body().to_vec(),
+            _ => Vec::new()
+        };
+        assert_eq!(vec![65], body);
+        ctx_sensor.borrow().assert_one_recv_from(eid);
+    }
+
+    #[test]
+    fn when_sending_while_receiving_both_are_notified() {
+        let (tx, rx) = mpsc::channel();
+        let mut pair = Pair::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(7);
+        let pipe = new_test_pipe(eid);
+
+        pair.add_pipe(&mut ctx, eid, pipe);
+        pair.on_send_ready(&mut ctx, eid);
+        pair.on_recv_ready(&mut ctx, eid);
+        pair.recv(&mut ctx, None);
+        pair.send(&mut ctx, Message::new(), None);
+        assert!(pair.has_pending_send());
+
+        pair.on_send_ack(&mut ctx, eid);
+        match rx.try_recv().expect("facade should have been sent a reply !") {
+            Reply::Send => {},
+            _ => panic!("the send should have been acknowledged")
+        }
+        assert!(rx.try_recv().is_err());
+
+        pair.on_recv_ack(&mut ctx, eid, Message::from_body(vec![65]));
+        match rx.try_recv().expect("facade should have been sent a reply !") {
+            Reply::Recv(msg) => assert_eq!(vec![65], msg.get_body().to_vec()),
+            _ => panic!("the recv should have been completed")
+        }
     }
 }
@@ -0,0 +1,756 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::rc::Rc;
+use std::sync::mpsc::Sender;
+use std::io;
+
+use byteorder::*;
+
+use core::{EndpointId, Message};
+use core::socket::{Protocol, Reply};
+use core::config::ConfigOption;
+use core::endpoint::Pipe;
+use core::context::Context;
+use super::priolist::Priolist;
+use super::pipes::PipeCollection;
+use super::{Timeout, PAIR1};
+use super::policy::{fair_queue, load_balancing};
+use super::raw::Rawable;
+use io_error::*;
+
+pub struct Pair1 {
+    inner: Inner,
+    state: Option<State>
+}
+
+enum State {
+    Idle,
+    Sending(EndpointId, Rc<Message>, Timeout),
+    SendOnHold(Option<EndpointId>, Rc<Message>, Timeout),
+    Receiving(EndpointId, Timeout),
+    RecvOnHold(Timeout)
+}
+
+struct Inner {
+    reply_tx: Sender<Reply>,
+    pipes: PipeCollection,
+    lb: Priolist,
+    fq: Priolist,
+    ttl: u8,
+    is_device_item: bool
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Pair1                                                                     */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Pair1 {
+
+    fn apply<F>(&mut self, ctx: &mut Context, transition: F) where F : FnOnce(State, &mut Context, &mut Inner) -> State {
+        if let Some(old_state) = self.state.take() {
+            #[cfg(debug_assertions)] let old_name = old_state.name();
+            let was_send_ready = self.inner.is_send_ready();
+            let was_recv_ready = self.inner.is_recv_ready();
+            let new_state = transition(old_state, ctx, &mut self.inner);
+            let is_send_ready = self.inner.is_send_ready();
+            let is_recv_ready = self.inner.is_recv_ready();
+            #[cfg(debug_assertions)] let new_name = new_state.name();
+
+            self.state = Some(new_state);
+
+            ctx.check_send_ready_change(was_send_ready, is_send_ready);
+            ctx.check_recv_ready_change(was_recv_ready, is_recv_ready);
+
+            #[cfg(debug_assertions)] debug!("[{:?}] switch from {} to {}", ctx, old_name, new_name);
+        }
+    }
+
+}
+
+impl From<Sender<Reply>> for Pair1 {
+    fn from(tx: Sender<Reply>) -> Pair1 {
+        Pair1 {
+            inner: Inner {
+                reply_tx: tx,
+                pipes: PipeCollection::new(),
+                lb: Priolist::new(),
+                fq: Priolist::new(),
+                ttl: 8,
+                is_device_item: false
+            },
+            state: Some(State::Idle)
+        }
+    }
+}
+
+impl Rawable for Pair1 {
+    fn set_raw(&mut self) {
+        self.inner.is_device_item = true;
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Protocol                                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Protocol for Pair1 {
+    fn id(&self)      -> u16 { PAIR1 }
+    fn peer_id(&self) -> u16 { PAIR1 }
+
+    fn add_pipe(&mut self, _: &mut Context, eid: EndpointId, pipe: Pipe) {
+        self.inner.add_pipe(eid, pipe)
+    }
+    fn remove_pipe(&mut self, ctx: &mut Context, eid: EndpointId) -> Option<Pipe> {
+        let was_send_ready = self.inner.is_send_ready();
+        let was_recv_ready = self.inner.is_recv_ready();
+        let pipe = self.inner.remove_pipe(eid);
+        let is_send_ready = self.inner.is_send_ready();
+        let is_recv_ready = self.inner.is_recv_ready();
+
+        if pipe.is_some() {
+            self.apply(ctx, |s, ctx, inner| s.on_pipe_removed(ctx, inner, eid));
+        }
+
+        ctx.check_send_ready_change(was_send_ready, is_send_ready);
+        ctx.check_recv_ready_change(was_recv_ready, is_recv_ready);
+
+        pipe
+    }
+    fn send(&mut self, ctx: &mut Context, msg: Message, timeout: Timeout) {
+        let (raw_msg, target) = encode(msg, self.inner.is_device_item);
+
+        self.apply(ctx, |s, ctx, inner| s.send(ctx, inner, Rc::new(raw_msg), target, timeout))
+    }
+    fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.apply(ctx, |s, ctx, inner| s.on_send_ack(ctx, inner, eid))
+    }
+    fn on_send_timeout(&mut self, ctx: &mut Context) {
+        self.apply(ctx, |s, ctx, inner| s.on_send_timeout(ctx, inner))
+    }
+    fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.apply(ctx, |s, ctx, inner| s.on_send_ready(ctx, inner, eid))
+    }
+    fn on_send_not_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.apply(ctx, |s, ctx, inner| s.on_send_not_ready(ctx, inner, eid))
+    }
+    fn recv(&mut self, ctx: &mut Context, timeout: Timeout) {
+        self.apply(ctx, |s, ctx, inner| s.recv(ctx, inner, timeout))
+    }
+    fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, raw_msg: Message) {
+        let msg = decode(ctx, raw_msg, eid, self.inner.ttl, self.inner.is_device_item);
+
+        self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg))
+    }
+    fn on_recv_timeout(&mut self, ctx: &mut Context) {
+        self.apply(ctx, |s, ctx, inner| s.on_recv_timeout(ctx, inner))
+    }
+    fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.apply(ctx, |s, ctx, inner| s.on_recv_ready(ctx, inner, eid))
+    }
+    fn on_recv_not_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.apply(ctx, |s, ctx, inner| s.on_recv_not_ready(ctx, inner, eid))
+    }
+    fn is_send_ready(&self) -> bool {
+        self.inner.is_send_ready()
+    }
+    fn is_recv_ready(&self) -> bool {
+        self.inner.is_recv_ready()
+    }
+    fn has_pending_send(&self) -> bool {
+        match self.state {
+            Some(State::Sending(..)) | Some(State::SendOnHold(..)) => true,
+            _ => false
+        }
+    }
+    fn set_option(&mut self, opt: ConfigOption) -> io::Result<()> {
+        match opt {
+            ConfigOption::MaxTtl(ttl) => self.inner.set_max_ttl(ttl),
+            _ => Err(invalid_input_io_error("option not supported"))
+        }
+    }
+    fn on_device_plugged(&mut self, _: &mut Context) {
+        self.inner.is_device_item = true;
+    }
+    fn close(&mut self, ctx: &mut Context) {
+        self.inner.close(ctx)
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* State                                                                     */
+/*                                                                           */
+/*****************************************************************************/
+
+impl State {
+
+    #[cfg(debug_assertions)]
+    fn name(&self) -> &'static str {
+        match *self {
+            State::Idle           => "Idle",
+            State::Sending(..)    => "Sending",
+            State::SendOnHold(..) => "SendOnHold",
+            State::Receiving(..)  => "Receiving",
+            State::RecvOnHold(..) => "RecvOnHold"
+        }
+    }
+
+    fn on_pipe_removed(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
+        match self {
+            State::Sending(id, msg, timeout) => {
+                if id == eid {
+                    inner.on_send_dropped(ctx, timeout);
+                    State::Idle
+                } else {
+                    State::Sending(id, msg, timeout)
+                }
+            },
+            State::SendOnHold(Some(id), msg, timeout) => {
+                if id == eid {
+                    inner.on_send_dropped(ctx, timeout);
+                    State::Idle
+                } else {
+                    State::SendOnHold(Some(id), msg, timeout)
+                }
+            },
+            State::Receiving(id, timeout) => {
+                if id == eid {
+                    State::Idle.recv(ctx, inner, timeout)
+                } else {
+                    State::Receiving(id, timeout)
+                }
+            },
+            any => any
+        }
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* send                                                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+    fn send(self, ctx: &mut Context, inner: &mut Inner, msg: Rc<Message>, target: Option<EndpointId>, timeout: Timeout) -> State {
+        if let Some(eid) = target {
+            if !inner.has_pipe(&eid) {
+                inner.on_send_dropped(ctx, timeout);
+
+                return State::Idle;
+            }
+        }
+
+        if let Some(eid) = inner.send(ctx, msg.clone(), target) {
+            State::Sending(eid, msg, timeout)
+        } else {
+            State::SendOnHold(target, msg, timeout)
+        }
+    }
+    fn on_send_ack(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
+        match self {
+            State::Sending(id, msg, timeout) => {
+                if id == eid {
+                    inner.on_send_ack(ctx, timeout);
+                    State::Idle
+                } else {
+                    State::Sending(id, msg, timeout)
+                }
+            },
+            any => any
+        }
+    }
+    fn on_send_timeout(self, _: &mut Context, inner: &mut Inner) -> State {
+        inner.on_send_timeout();
+
+        State::Idle
+    }
+    fn on_send_ready(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
+        inner.on_send_ready(eid);
+
+        match self {
+            State::SendOnHold(target, msg, timeout) => {
+                if target.map_or(true, |id| id == eid) {
+                    State::Idle.send(ctx, inner, msg, target, timeout)
+                } else {
+                    State::SendOnHold(target, msg, timeout)
+                }
+            },
+            any => any
+        }
+    }
+    fn on_send_not_ready(self, _: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
+        inner.on_send_not_ready(eid);
+        self
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* recv                                                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+    fn recv(self, ctx: &mut Context, inner: &mut Inner, timeout: Timeout) -> State {
+        inner.recv(ctx).map_or_else(
+            |   | State::RecvOnHold(timeout),
+            |eid| State::Receiving(eid, timeout))
+    }
+    fn on_recv_ack(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId, msg: Option<Message>) -> State {
+        match self {
+            State::Receiving(id, timeout) => {
+                if id != eid {
+                    State::Receiving(id, timeout)
+                } else if let Some(msg) = msg {
+                    inner.on_recv_ack(ctx, timeout, msg);
+                    State::Idle
+                } else {
+                    State::Idle.recv(ctx, inner, timeout)
+                }
+            },
+            any => any
+        }
+    }
+    fn on_recv_timeout(self, _: &mut Context, inner: &mut Inner) -> State {
+        inner.on_recv_timeout();
+
+        State::Idle
+    }
+    fn on_recv_ready(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
+        inner.on_recv_ready(eid);
+
+        match self {
+            State::RecvOnHold(timeout) => State::Idle.recv(ctx, inner, timeout),
+            any => any
+        }
+    }
+    fn on_recv_not_ready(self, _: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
+        inner.on_recv_not_ready(eid);
+        self
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Inner                                                                     */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Inner {
+    fn add_pipe(&mut self, eid: EndpointId, pipe: Pipe) {
+        self.lb.insert(eid, pipe.get_send_priority());
+        self.fq.insert(eid, pipe.get_recv_priority());
+        self.pipes.insert(eid, pipe);
+    }
+    fn remove_pipe(&mut self, eid: EndpointId) -> Option<Pipe> {
+        self.lb.remove(&eid);
+        self.fq.remove(&eid);
+        self.pipes.remove(&eid)
+    }
+    fn has_pipe(&mut self, eid: &EndpointId) -> bool {
+        self.pipes.get_mut(eid).is_some()
+    }
+
+    fn send(&mut self, ctx: &mut Context, msg: Rc<Message>, target: Option<EndpointId>) -> Option<EndpointId> {
+        match target {
+            Some(eid) => {
+                if self.lb.is_active(&eid) {
+                    self.lb.deactivate(&eid);
+                    self.pipes.send_to(ctx, msg, eid)
+                } else {
+                    None
+                }
+            },
+            None => load_balancing::send(&mut self.lb, &mut self.pipes, ctx, msg)
+        }
+    }
+    fn on_send_ack(&self, ctx: &mut Context, timeout: Timeout) {
+        let _ = self.reply_tx.send(Reply::Send);
+        if let Some(sched) = timeout {
+            ctx.cancel(sched);
+        }
+    }
+    fn on_send_dropped(&self, ctx: &mut Context, timeout: Timeout) {
+        self.on_send_ack(ctx, timeout)
+    }
+    fn on_send_timeout(&self) {
+        let error = timedout_io_error("Send timed out");
+        let _ = self.reply_tx.send(Reply::Err(error));
+    }
+    fn on_send_ready(&mut self, eid: EndpointId) {
+        self.lb.activate(&eid)
+    }
+    fn on_send_not_ready(&mut self, eid: EndpointId) {
+        self.lb.deactivate(&eid)
+    }
+    fn is_send_ready(&self) -> bool {
+        self.lb.peek()
+    }
+
+    fn recv(&mut self, ctx: &mut Context) -> Option<EndpointId> {
+        fair_queue::recv(&mut self.fq, &mut self.pipes, ctx)
+    }
+    fn on_recv_ready(&mut self, eid: EndpointId) {
+        self.fq.activate(&eid)
+    }
+    fn on_recv_not_ready(&mut self, eid: EndpointId) {
+        self.fq.deactivate(&eid)
+    }
+    fn on_recv_ack(&self, ctx: &mut Context, timeout: Timeout, msg: Message) {
+        let _ = self.reply_tx.send(Reply::Recv(msg));
+        if let Some(sched) = timeout {
+            ctx.cancel(sched);
+        }
+    }
+    fn on_recv_timeout(&self) {
+        let error = timedout_io_error("Recv timed out");
+        let _ = self.reply_tx.send(Reply::Err(error));
+    }
+    fn is_recv_ready(&self) -> bool {
+        self.fq.peek()
+    }
+    fn set_max_ttl(&mut self, ttl: u8) -> io::Result<()> {
+        if ttl == 0 {
+            Err(invalid_input_io_error("max ttl must be between 1 and 255"))
+        } else {
+            Ok(self.ttl = ttl)
+        }
+    }
+    fn close(&mut self, ctx: &mut Context) {
+        self.pipes.close_all(ctx)
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Codec                                                                     */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Strips the hop count from the received message, dropping it when
+/// it is malformed or has travelled through more than `ttl` devices.
+/// The id of the pipe the message came from is stored in the header,
+/// or the hop count itself when the socket is part of a device.
+fn decode(ctx: &Context, raw_msg: Message, eid: EndpointId, ttl: u8, is_device_item: bool) -> Option<Message> {
+    if raw_msg.get_body().len() < 4 {
+        return None;
+    }
+
+    let (_, mut payload) = raw_msg.split();
+    let body = payload.split_off(4);
+    let hops = BigEndian::read_u32(&payload);
+
+    if hops > 0xff || hops >= u32::from(ttl) {
+        info!("[{:?}] message dropped after {} hops, max ttl exceeded", ctx, hops);
+        return None;
+    }
+
+    if is_device_item {
+        return Some(Message::from_header_and_body(payload, body));
+    }
+
+    let originator: usize = eid.into();
+    let mut header = vec![0; 4];
+
+    BigEndian::write_u32(&mut header[..], originator as u32);
+
+    Some(Message::from_header_and_body(header, body))
+}
+
+/// Replaces the header of the message to send with its hop count.
+/// A message sent by a device goes to any peer, with the hop count found in the header
+/// incremented, otherwise the hop count is zero and the first four bytes of the header,
+/// when provided, are the id of the pipe to send the message to.
+fn encode(msg: Message, is_device_item: bool) -> (Message, Option<EndpointId>) {
+    let (header, body) = msg.split();
+    let mut hops_bytes = vec![0; 4];
+
+    if is_device_item {
+        let hops = if header.len() < 4 { 0 } else { BigEndian::read_u32(&header) };
+
+        BigEndian::write_u32(&mut hops_bytes[..], hops.saturating_add(1));
+
+        return (Message::from_header_and_body(hops_bytes, body), None);
+    }
+
+    let target = if header.len() < 4 {
+        None
+    } else {
+        Some(EndpointId::from(BigEndian::read_u32(&header) as usize))
+    };
+
+    (Message::from_header_and_body(hops_bytes, body), target)
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* tests                                                                     */
+/*                                                                           */
+/*****************************************************************************/
+
+#[cfg(test)]
+mod tests {
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::mpsc;
+
+    use core::{EndpointId, Message};
+    use core::socket::{Protocol, Reply};
+    use core::config::ConfigOption;
+    use core::context::Event;
+    use core::tests::*;
+
+    use super::*;
+
+    fn header_of(eid: EndpointId) -> Vec<u8> {
+        let eid: usize = eid.into();
+
+        vec![0, 0, 0, eid as u8]
+    }
+
+    #[test]
+    fn send_goes_to_the_pipe_given_in_the_header() {
+        let (tx, rx) = mpsc::channel();
+        let mut pair = Pair1::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid1 = EndpointId::from(1);
+        let eid2 = EndpointId::from(2);
+
+        pair.add_pipe(&mut ctx, eid1, new_test_pipe(eid1));
+        pair.add_pipe(&mut ctx, eid2, new_test_pipe(eid2));
+        pair.on_send_ready(&mut ctx, eid1);
+        pair.on_send_ready(&mut ctx, eid2);
+        pair.send(&mut ctx, Message::from_header_and_body(header_of(eid2), vec![65]), None);
+        pair.on_send_ack(&mut ctx, eid2);
+
+        let sensor = ctx_sensor.borrow();
+        sensor.assert_send_to(eid1, 0);
+        sensor.assert_send_to(eid2, 1);
+
+        let sent = &sensor.get_send_calls()[0].1;
+        assert_eq!(&[0, 0, 0, 0], sent.get_header());
+        assert_eq!(&[65], sent.get_body());
+
+        match rx.try_recv() {
+            Ok(Reply::Send) => {},
+            _ => panic!("facade should have been sent a Send reply !")
+        }
+    }
+
+    #[test]
+    fn send_without_header_goes_to_any_ready_pipe() {
+        let (tx, _) = mpsc::channel();
+        let mut pair = Pair1::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid1 = EndpointId::from(1);
+        let eid2 = EndpointId::from(2);
+
+        pair.add_pipe(&mut ctx, eid1, new_test_pipe(eid1));
+        pair.add_pipe(&mut ctx, eid2, new_test_pipe(eid2));
+        pair.on_send_ready(&mut ctx, eid2);
+        pair.send(&mut ctx, Message::from_body(vec![65]), None);
+
+        let sensor = ctx_sensor.borrow();
+        sensor.assert_send_to(eid1, 0);
+        sensor.assert_send_to(eid2, 1);
+    }
+
+    #[test]
+    fn send_to_a_pipe_that_is_not_ready_is_put_on_hold() {
+        let (tx, _) = mpsc::channel();
+        let mut pair = Pair1::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid1 = EndpointId::from(1);
+        let eid2 = EndpointId::from(2);
+
+        pair.add_pipe(&mut ctx, eid1, new_test_pipe(eid1));
+        pair.add_pipe(&mut ctx, eid2, new_test_pipe(eid2));
+        pair.on_send_ready(&mut ctx, eid1);
+        pair.send(&mut ctx, Message::from_header_and_body(header_of(eid2), vec![65]), None);
+        ctx_sensor.borrow().assert_no_send_call();
+
+        pair.on_send_ready(&mut ctx, eid2);
+
+        let sensor = ctx_sensor.borrow();
+        sensor.assert_send_to(eid1, 0);
+        sensor.assert_send_to(eid2, 1);
+    }
+
+    #[test]
+    fn send_to_an_unknown_pipe_is_dropped() {
+        let (tx, rx) = mpsc::channel();
+        let mut pair = Pair1::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid1 = EndpointId::from(1);
+
+        pair.add_pipe(&mut ctx, eid1, new_test_pipe(eid1));
+        pair.on_send_ready(&mut ctx, eid1);
+        pair.send(&mut ctx, Message::from_header_and_body(header_of(EndpointId::from(7)), vec![65]), None);
+
+        ctx_sensor.borrow().assert_no_send_call();
+        match rx.try_recv() {
+            Ok(Reply::Send) => {},
+            _ => panic!("facade should have been sent a Send reply !")
+        }
+    }
+
+    #[test]
+    fn recv_stores_the_pipe_id_in_the_header() {
+        let (tx, rx) = mpsc::channel();
+        let mut pair = Pair1::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(3);
+
+        pair.add_pipe(&mut ctx, eid, new_test_pipe(eid));
+        pair.on_recv_ready(&mut ctx, eid);
+        pair.recv(&mut ctx, None);
+        pair.on_recv_ack(&mut ctx, eid, Message::from_body(vec![0, 0, 0, 0, 65, 66]));
+
+        let msg = match rx.try_recv() {
+            Ok(Reply::Recv(msg)) => msg,
+            _ => panic!("facade should have been sent a Recv reply !")
+        };
+        assert_eq!(&header_of(eid)[..], msg.get_header());
+        assert_eq!(&[65, 66], msg.get_body());
+    }
+
+    #[test]
+    fn recv_drops_messages_that_exceed_ttl() {
+        let (tx, rx) = mpsc::channel();
+        let mut pair = Pair1::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid1 = EndpointId::from(1);
+        let eid2 = EndpointId::from(2);
+
+        pair.add_pipe(&mut ctx, eid1, new_test_pipe(eid1));
+        pair.add_pipe(&mut ctx, eid2, new_test_pipe(eid2));
+        pair.on_recv_ready(&mut ctx, eid1);
+        pair.on_recv_ready(&mut ctx, eid2);
+        pair.recv(&mut ctx, None);
+        pair.on_recv_ack(&mut ctx, eid1, Message::from_body(vec![0, 0, 0, 8, 65]));
+
+        assert!(rx.try_recv().is_err());
+
+        pair.on_recv_ack(&mut ctx, eid2, Message::from_body(vec![0, 0, 0, 7, 66]));
+
+        match rx.try_recv() {
+            Ok(Reply::Recv(msg)) => assert_eq!(&[66], msg.get_body()),
+            _ => panic!("facade should have been sent a Recv reply !")
+        }
+    }
+
+    #[test]
+    fn max_ttl_must_be_between_1_and_255() {
+        let (tx, _) = mpsc::channel();
+        let mut pair = Pair1::from(tx);
+
+        assert!(pair.set_option(ConfigOption::MaxTtl(0)).is_err());
+        assert!(pair.set_option(ConfigOption::MaxTtl(1)).is_ok());
+        assert!(pair.set_option(ConfigOption::MaxTtl(255)).is_ok());
+    }
+
+    #[test]
+    fn recv_drops_messages_that_exceed_max_ttl() {
+        let (tx, rx) = mpsc::channel();
+        let mut pair = Pair1::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid1 = EndpointId::from(1);
+        let eid2 = EndpointId::from(2);
+
+        pair.set_option(ConfigOption::MaxTtl(2)).unwrap();
+        pair.add_pipe(&mut ctx, eid1, new_test_pipe(eid1));
+        pair.add_pipe(&mut ctx, eid2, new_test_pipe(eid2));
+        pair.on_recv_ready(&mut ctx, eid1);
+        pair.on_recv_ready(&mut ctx, eid2);
+        pair.recv(&mut ctx, None);
+        pair.on_recv_ack(&mut ctx, eid1, Message::from_body(vec![0, 0, 0, 2, 65]));
+
+        assert!(rx.try_recv().is_err());
+
+        pair.on_recv_ack(&mut ctx, eid2, Message::from_body(vec![0, 0, 0, 1, 66]));
+
+        match rx.try_recv() {
+            Ok(Reply::Recv(msg)) => assert_eq!(&[66], msg.get_body()),
+            _ => panic!("facade should have been sent a Recv reply !")
+        }
+    }
+
+    #[test]
+    fn when_in_device_recv_stores_the_hop_count_in_the_header() {
+        let (tx, rx) = mpsc::channel();
+        let mut pair = Pair1::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(3);
+
+        pair.on_device_plugged(&mut ctx);
+        pair.add_pipe(&mut ctx, eid, new_test_pipe(eid));
+        pair.on_recv_ready(&mut ctx, eid);
+        pair.recv(&mut ctx, None);
+        pair.on_recv_ack(&mut ctx, eid, Message::from_body(vec![0, 0, 0, 2, 65, 66]));
+
+        let msg = match rx.try_recv() {
+            Ok(Reply::Recv(msg)) => msg,
+            _ => panic!("facade should have been sent a Recv reply !")
+        };
+        assert_eq!(&[0, 0, 0, 2], msg.get_header());
+        assert_eq!(&[65, 66], msg.get_body());
+    }
+
+    #[test]
+    fn when_in_device_send_increments_the_hop_count_and_goes_to_any_ready_pipe() {
+        let (tx, _) = mpsc::channel();
+        let mut pair = Pair1::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid1 = EndpointId::from(1);
+        let eid2 = EndpointId::from(2);
+
+        pair.on_device_plugged(&mut ctx);
+        pair.add_pipe(&mut ctx, eid1, new_test_pipe(eid1));
+        pair.add_pipe(&mut ctx, eid2, new_test_pipe(eid2));
+        pair.on_send_ready(&mut ctx, eid2);
+        pair.send(&mut ctx, Message::from_header_and_body(vec![0, 0, 0, 2], vec![65]), None);
+
+        let sensor = ctx_sensor.borrow();
+        sensor.assert_send_to(eid1, 0);
+        sensor.assert_send_to(eid2, 1);
+
+        let sent = &sensor.get_send_calls()[0].1;
+        assert_eq!(&[0, 0, 0, 3], sent.get_header());
+        assert_eq!(&[65], sent.get_body());
+    }
+
+    #[test]
+    fn when_last_send_ready_pipe_is_removed_event_is_raised() {
+        let (tx, _) = mpsc::channel();
+        let mut pair = Pair1::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(5);
+
+        pair.add_pipe(&mut ctx, eid, new_test_pipe(eid));
+        pair.on_send_ready(&mut ctx, eid);
+        pair.remove_pipe(&mut ctx, eid);
+
+        let sensor = ctx_sensor.borrow();
+        let raised_evts = sensor.get_raised_events();
+
+        assert_eq!(2, raised_evts.len());
+        assert_eq!(Event::CanSend(true), raised_evts[0]);
+        assert_eq!(Event::CanSend(false), raised_evts[1]);
+    }
+}
@@ -139,6 +139,10 @@ impl Priolist {
         self.current.is_some()
     }
 
+    pub fn is_active(&self, id: &EndpointId) -> bool {
+        self.find_by_id_in_all(id).map_or(false, |index| self.is_index_active(index))
+    }
+
     pub fn deactivate(&mut self, id: &EndpointId) {
         if let Some(index) = self.find_by_id_in_all(id) {
             self.deactivate_at_index(index);
@@ -4,12 +4,14 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::rc::Rc;
 use std::sync::mpsc::Sender;
+use std::io;
 
-use core::{EndpointId, Message};
+use core::{BuildIdHasher, EndpointId, Message};
 use core::socket::{Protocol, Reply};
+use core::config::{ConfigOption, DropPolicy};
 use core::endpoint::Pipe;
 use core::context::{Context, Event};
 use super::pipes::PipeCollection;
@@ -20,7 +22,15 @@ use io_error::*;
 pub struct Pub {
     reply_tx: Sender<Reply>,
     pipes: PipeCollection,
-    bc: HashSet<EndpointId>
+    bc: HashSet<EndpointId>,
+    queues: HashMap<EndpointId, PipeQueue, BuildIdHasher>,
+    queue_depth: usize,
+    drop_policy: DropPolicy
+}
+
+struct PipeQueue {
+    msgs: VecDeque<Rc<Message>>,
+    dropped: u64
 }
 
 /*****************************************************************************/
@@ -34,7 +44,31 @@ impl From<Sender<Reply>> for Pub {
         Pub {
             reply_tx: tx,
             pipes: PipeCollection::new(),
-            bc: HashSet::new()
+            bc: HashSet::new(),
+            queues: HashMap::default(),
+            queue_depth: 0,
+            drop_policy: DropPolicy::DropNewest
+        }
+    }
+}
+
+impl Pub {
+    fn set_queue_depth(&mut self, depth: usize) {
+        let policy = self.drop_policy;
+
+        self.queue_depth = depth;
+        for queue in self.queues.values_mut() {
+            queue.truncate(depth, policy);
+        }
+    }
+    fn enqueue_for_busy_pipes(&mut self, msg: &Rc<Message>) {
+        let depth = self.queue_depth;
+        let policy = self.drop_policy;
+
+        for (eid, queue) in &mut self.queues {
+            if !self.bc.contains(eid) {
+                queue.push(msg.clone(), depth, policy);
+            }
         }
     }
 }
@@ -50,9 +84,11 @@ impl Protocol for Pub {
     fn peer_id(&self) -> u16 { SUB }
 
     fn add_pipe(&mut self, _: &mut Context, eid: EndpointId, pipe: Pipe) {
+        self.queues.insert(eid, PipeQueue::new());
         self.pipes.insert(eid, pipe);
     }
     fn remove_pipe(&mut self, ctx: &mut Context, eid: EndpointId) -> Option<Pipe> {
+        self.queues.remove(&eid);
         self.bc.remove(&eid);
         if self.bc.is_empty() {
             ctx.raise(Event::CanSend(false));
@@ -62,6 +98,7 @@ impl Protocol for Pub {
     fn send(&mut self, ctx: &mut Context, msg: Message, timeout: Timeout) {
         let msg = Rc::new(msg);
 
+        self.enqueue_for_busy_pipes(&msg);
         broadcast::send_to_all(&mut self.bc, &mut self.pipes, ctx, msg);
         ctx.raise(Event::CanSend(false));
 
@@ -75,6 +112,10 @@ impl Protocol for Pub {
     fn on_send_timeout(&mut self, _: &mut Context) {
     }
     fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
+        if let Some(msg) = self.queues.get_mut(&eid).and_then(|q| q.msgs.pop_front()) {
+            self.pipes.send_to(ctx, msg, eid);
+            return;
+        }
         if self.bc.is_empty() {
             ctx.raise(Event::CanSend(true));
         }
@@ -111,7 +152,129 @@ impl Protocol for Pub {
     fn is_recv_ready(&self) -> bool {
         false
     }
+    fn is_duplex(&self) -> bool {
+        true
+    }
+    fn has_pending_send(&self) -> bool {
+        self.queues.values().any(|q| !q.msgs.is_empty())
+    }
+    fn set_option(&mut self, opt: ConfigOption) -> io::Result<()> {
+        match opt {
+            ConfigOption::PubQueueDepth(depth)  => Ok(self.set_queue_depth(depth)),
+            ConfigOption::PubDropPolicy(policy) => Ok(self.drop_policy = policy),
+            _ => Err(invalid_input_io_error("option not supported"))
+        }
+    }
+    fn dropped_messages(&self) -> io::Result<Vec<(EndpointId, u64, usize)>> {
+        Ok(self.queues.iter().map(|(eid, q)| (*eid, q.dropped, q.msgs.len())).collect())
+    }
     fn close(&mut self, ctx: &mut Context) {
         self.pipes.close_all(ctx)
     }
 }
+
+/*****************************************************************************/
+/*                                                                           */
+/* PipeQueue                                                                 */
+/*                                                                           */
+/*****************************************************************************/
+
+impl PipeQueue {
+    fn new() -> PipeQueue {
+        PipeQueue {
+            msgs: VecDeque::new(),
+            dropped: 0
+        }
+    }
+    fn push(&mut self, msg: Rc<Message>, depth: usize, policy: DropPolicy) {
+        if self.msgs.len() < depth {
+            self.msgs.push_back(msg);
+            return;
+        }
+
+        self.dropped += 1;
+
+        if policy == DropPolicy::DropOldest && depth > 0 {
+            self.msgs.pop_front();
+            self.msgs.push_back(msg);
+        }
+    }
+    fn truncate(&mut self, depth: usize, policy: DropPolicy) {
+        while self.msgs.len() > depth {
+            match policy {
+                DropPolicy::DropNewest => self.msgs.pop_back(),
+                DropPolicy::DropOldest => self.msgs.pop_front()
+            };
+            self.dropped += 1;
+        }
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* tests                                                                     */
+/*                                                                           */
+/*****************************************************************************/
+
+#[cfg(test)]
+mod tests {
+
+    use std::rc::Rc;
+
+    use core::Message;
+    use core::config::DropPolicy;
+
+    use super::PipeQueue;
+
+    fn fill(queue: &mut PipeQueue, depth: usize, policy: DropPolicy) {
+        for i in 0..5 {
+            queue.push(Rc::new(Message::from_body(vec![i])), depth, policy);
+        }
+    }
+
+    fn bodies(queue: &PipeQueue) -> Vec<u8> {
+        queue.msgs.iter().map(|msg| msg.get_body()[0]).collect()
+    }
+
+    #[test]
+    fn when_queue_is_full_drop_newest_keeps_the_first_messages() {
+        let mut queue = PipeQueue::new();
+
+        fill(&mut queue, 3, DropPolicy::DropNewest);
+
+        assert_eq!(vec![0, 1, 2], bodies(&queue));
+        assert_eq!(2, queue.dropped);
+    }
+
+    #[test]
+    fn when_queue_is_full_drop_oldest_keeps_the_last_messages() {
+        let mut queue = PipeQueue::new();
+
+        fill(&mut queue, 3, DropPolicy::DropOldest);
+
+        assert_eq!(vec![2, 3, 4], bodies(&queue));
+        assert_eq!(2, queue.dropped);
+    }
+
+    #[test]
+    fn when_queue_is_truncated_drop_newest_keeps_the_first_messages() {
+        let mut queue = PipeQueue::new();
+
+        fill(&mut queue, 5, DropPolicy::DropNewest);
+        queue.truncate(2, DropPolicy::DropNewest);
+
+        assert_eq!(vec![0, 1], bodies(&queue));
+        assert_eq!(3, queue.dropped);
+    }
+
+    #[test]
+    fn when_queue_is_truncated_drop_oldest_keeps_the_last_messages() {
+        let mut queue = PipeQueue::new();
+
+        fill(&mut queue, 5, DropPolicy::DropOldest);
+        queue.truncate(2, DropPolicy::DropOldest);
+
+        assert_eq!(vec![3, 4], bodies(&queue));
+        assert_eq!(3, queue.dropped);
+    }
+}
@@ -135,6 +135,9 @@ impl Protocol for Pull {
     fn is_recv_ready(&self) -> bool {
         self.inner.is_recv_ready()
     }
+    fn is_duplex(&self) -> bool {
+        true
+    }
     fn close(&mut self, ctx: &mut Context) {
         self.inner.close(ctx)
     }
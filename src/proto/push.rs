@@ -135,6 +135,16 @@ impl Protocol for Push {
     fn is_recv_ready(&self) -> bool {
         false
     }
+    fn is_duplex(&self) -> bool {
+        true
+    }
+    fn has_pending_send(&self) -> bool {
+        match self.state {
+            Some(State::Sending(..))    |
+            Some(State::SendOnHold(..)) => true,
+            _ => false
+        }
+    }
     fn close(&mut self, ctx: &mut Context) {
         self.inner.close(ctx)
     }
@@ -0,0 +1,132 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+//! Raw versions of the request/reply, survey and pair1 protocols.
+//! A raw socket skips request id bookkeeping and backtrace handling,
+//! the whole message header is handed to the user when receiving,
+//! and must be provided by the user when sending.
+
+use std::io;
+use std::sync::mpsc::Sender;
+
+use core::{EndpointId, Message};
+use core::socket::{Protocol, Reply};
+use core::endpoint::Pipe;
+use core::config::ConfigOption;
+use core::context::{Context, Schedulable};
+use super::Timeout;
+use super::req::Req;
+use super::rep::Rep;
+use super::surv::Surveyor;
+use super::resp::Respondent;
+use super::pair1::Pair1;
+
+/// Raw `Req` socket, sends messages with the header provided by the user
+/// and delivers replies with their header, without matching any request id.
+pub type RawReq = Raw<Req>;
+
+/// Raw `Rep` socket, delivers requests with their backtrace in the header,
+/// the reply is routed using the header provided by the user.
+pub type RawRep = Raw<Rep>;
+
+/// Raw `Surveyor` socket, sends surveys with the header provided by the user
+/// and delivers responses with their header, without any deadline.
+pub type RawSurveyor = Raw<Surveyor>;
+
+/// Raw `Respondent` socket, delivers surveys with their backtrace in the header,
+/// the response is routed using the header provided by the user.
+pub type RawRespondent = Raw<Respondent>;
+
+/// Raw `Pair1` socket, delivers messages with their hop count in the header,
+/// and sends messages to any peer after incrementing the hop count of their header.
+pub type RawPair1 = Raw<Pair1>;
+
+/// Protocols that can be switched to raw mode.
+#[doc(hidden)]
+pub trait Rawable : Protocol + From<Sender<Reply>> {
+    fn set_raw(&mut self);
+}
+
+/// Wraps a protocol that has been switched to raw mode at creation.
+pub struct Raw<T> {
+    proto: T
+}
+
+impl<T: Rawable> From<Sender<Reply>> for Raw<T> {
+    fn from(tx: Sender<Reply>) -> Raw<T> {
+        let mut proto = T::from(tx);
+
+        proto.set_raw();
+
+        Raw { proto: proto }
+    }
+}
+
+impl<T: Rawable> Protocol for Raw<T> {
+    fn id(&self) -> u16 {
+        self.proto.id()
+    }
+    fn peer_id(&self) -> u16 {
+        self.proto.peer_id()
+    }
+    fn add_pipe(&mut self, ctx: &mut Context, eid: EndpointId, pipe: Pipe) {
+        self.proto.add_pipe(ctx, eid, pipe)
+    }
+    fn remove_pipe(&mut self, ctx: &mut Context, eid: EndpointId) -> Option<Pipe> {
+        self.proto.remove_pipe(ctx, eid)
+    }
+    fn send(&mut self, ctx: &mut Context, msg: Message, timeout: Timeout) {
+        self.proto.send(ctx, msg, timeout)
+    }
+    fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.proto.on_send_ack(ctx, eid)
+    }
+    fn on_send_timeout(&mut self, ctx: &mut Context) {
+        self.proto.on_send_timeout(ctx)
+    }
+    fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.proto.on_send_ready(ctx, eid)
+    }
+    fn on_send_not_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.proto.on_send_not_ready(ctx, eid)
+    }
+    fn recv(&mut self, ctx: &mut Context, timeout: Timeout) {
+        self.proto.recv(ctx, timeout)
+    }
+    fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, msg: Message) {
+        self.proto.on_recv_ack(ctx, eid, msg)
+    }
+    fn on_recv_timeout(&mut self, ctx: &mut Context) {
+        self.proto.on_recv_timeout(ctx)
+    }
+    fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.proto.on_recv_ready(ctx, eid)
+    }
+    fn on_recv_not_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.proto.on_recv_not_ready(ctx, eid)
+    }
+    fn is_send_ready(&self) -> bool {
+        self.proto.is_send_ready()
+    }
+    fn is_recv_ready(&self) -> bool {
+        self.proto.is_recv_ready()
+    }
+    fn has_pending_send(&self) -> bool {
+        self.proto.has_pending_send()
+    }
+    fn set_option(&mut self, option: ConfigOption) -> io::Result<()> {
+        self.proto.set_option(option)
+    }
+    fn on_timer_tick(&mut self, ctx: &mut Context, task: Schedulable) {
+        self.proto.on_timer_tick(ctx, task)
+    }
+    fn on_device_plugged(&mut self, ctx: &mut Context) {
+        self.proto.on_device_plugged(ctx)
+    }
+    fn close(&mut self, ctx: &mut Context) {
+        self.proto.close(ctx)
+    }
+}
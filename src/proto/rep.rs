@@ -4,17 +4,20 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::rc::Rc;
 use std::sync::mpsc::Sender;
+use std::io;
 
 use byteorder::*;
 
-use core::{EndpointId, Message};
+use core::{BuildIdHasher, ContextId, EndpointId, Message};
 use core::socket::{Protocol, Reply};
+use core::config::ConfigOption;
 use core::endpoint::Pipe;
 use core::context::Context;
 use super::priolist::Priolist;
+use super::raw::Rawable;
 use super::pipes::PipeCollection;
 use super::{Timeout, REQ, REP};
 use super::policy::fair_queue;
@@ -34,6 +37,13 @@ enum State {
     SendOnHold(EndpointId, Rc<Message>, Timeout)
 }
 
+/// Outcome of extracting the backtrace of a raw request.
+enum Decoded {
+    Msg(Message),
+    TtlExceeded,
+    Malformed
+}
+
 struct Inner {
     reply_tx: Sender<Reply>,
     pipes: PipeCollection,
@@ -41,7 +51,21 @@ struct Inner {
     sd: HashSet<EndpointId>,
     ttl: u8,
     backtrace: Vec<u8>,
-    is_device_item: bool
+    is_device_item: bool,
+    contexts: HashMap<ContextId, RepContext, BuildIdHasher>,
+    ctx_recv_queue: VecDeque<ContextId>,
+    ctx_requests: VecDeque<Message>,
+    ctx_reading: HashSet<EndpointId>,
+    ctx_sending: HashMap<EndpointId, ContextId, BuildIdHasher>
+}
+
+/// Request state of a context, `backtrace` is the one of the last received request,
+/// until the reply is sent to the pipe it came from.
+struct RepContext {
+    reply_tx: Sender<Reply>,
+    recv: Option<Timeout>,
+    backtrace: Option<Vec<u8>>,
+    send: Option<(EndpointId, Rc<Message>, Timeout)>
 }
 
 /*****************************************************************************/
@@ -71,6 +95,19 @@ impl Rep {
         }
     }
 
+    fn apply_on_contexts<F>(&mut self, ctx: &mut Context, f: F) where F : FnOnce(&mut Context, &mut Inner) {
+        let was_send_ready = self.is_send_ready();
+        let was_recv_ready = self.is_recv_ready();
+
+        f(ctx, &mut self.inner);
+
+        let is_send_ready = self.is_send_ready();
+        let is_recv_ready = self.is_recv_ready();
+
+        ctx.check_send_ready_change(was_send_ready, is_send_ready);
+        ctx.check_recv_ready_change(was_recv_ready, is_recv_ready);
+    }
+
 }
 
 impl From<Sender<Reply>> for Rep {
@@ -82,6 +119,12 @@ impl From<Sender<Reply>> for Rep {
     }
 }
 
+impl Rawable for Rep {
+    fn set_raw(&mut self) {
+        self.inner.is_device_item = true;
+    }
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* Protocol                                                                  */
@@ -104,6 +147,7 @@ impl Protocol for Rep {
 
         if pipe.is_some() {
             self.apply(ctx, |s, ctx, inner| s.on_pipe_removed(ctx, inner, eid));
+            self.apply_on_contexts(ctx, |ctx, inner| inner.on_context_pipe_removed(ctx, eid));
         }
 
         ctx.check_send_ready_change(was_send_ready, is_send_ready);
@@ -119,6 +163,10 @@ impl Protocol for Rep {
         }
     }
     fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
+        if self.inner.is_context_sending_to(&eid) {
+            return self.inner.on_context_send_ack(ctx, eid);
+        }
+
         self.inner.clear_backtrace();
         
         self.apply(ctx, |s, ctx, inner| s.on_send_ack(ctx, inner, eid))
@@ -127,7 +175,8 @@ impl Protocol for Rep {
         self.apply(ctx, |s, ctx, inner| s.on_send_timeout(ctx, inner))
     }
     fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_send_ready(ctx, inner, eid))
+        self.apply(ctx, |s, ctx, inner| s.on_send_ready(ctx, inner, eid));
+        self.apply_on_contexts(ctx, |ctx, inner| inner.send_for_contexts(ctx, eid))
     }
     fn on_send_not_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
         self.apply(ctx, |s, ctx, inner| s.on_send_not_ready(ctx, inner, eid))
@@ -136,21 +185,32 @@ impl Protocol for Rep {
         self.apply(ctx, |s, ctx, inner| s.recv(ctx, inner, timeout))
     }
     fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, raw_msg: Message) {
-        if let Some(msg) = self.inner.raw_msg_to_msg(raw_msg, eid) {
-            self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg))
+        if self.inner.ctx_reading.remove(&eid) {
+            self.apply_on_contexts(ctx, |ctx, inner| inner.on_context_recv_ack(ctx, eid, raw_msg))
         } else {
-            self.inner.on_recv_ack_malformed(ctx)
+            match self.inner.raw_msg_to_msg(ctx, raw_msg, eid) {
+                Decoded::Msg(msg)    => self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg)),
+                Decoded::TtlExceeded => self.apply(ctx, |s, ctx, inner| s.on_recv_dropped(ctx, inner, eid)),
+                Decoded::Malformed   => self.inner.on_recv_ack_malformed(ctx)
+            }
         }
     }
     fn on_recv_timeout(&mut self, ctx: &mut Context) {
         self.apply(ctx, |s, ctx, inner| s.on_recv_timeout(ctx, inner))
     }
     fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_recv_ready(ctx, inner, eid))
+        self.apply(ctx, |s, ctx, inner| s.on_recv_ready(ctx, inner, eid));
+        self.apply_on_contexts(ctx, |ctx, inner| inner.recv_for_contexts(ctx))
     }
     fn on_recv_not_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
         self.apply(ctx, |s, ctx, inner| s.on_recv_not_ready(ctx, inner, eid))
     }
+    fn set_option(&mut self, opt: ConfigOption) -> io::Result<()> {
+        match opt {
+            ConfigOption::MaxTtl(ttl) => self.inner.set_max_ttl(ttl),
+            _ => Err(invalid_input_io_error("option not supported"))
+        }
+    }
     fn on_device_plugged(&mut self, _: &mut Context) {
         self.inner.is_device_item = true;
     }
@@ -168,6 +228,35 @@ impl Protocol for Rep {
             false
         }
     }
+    fn has_pending_send(&self) -> bool {
+        match self.state {
+            Some(State::Sending(..))    |
+            Some(State::SendOnHold(..)) => true,
+            _ => self.inner.has_pending_context_send()
+        }
+    }
+    fn add_context(&mut self, _: &mut Context, cid: ContextId, tx: Sender<Reply>) -> io::Result<()> {
+        if self.inner.is_device_item {
+            Err(invalid_input_io_error("contexts not supported by raw sockets"))
+        } else {
+            Ok(self.inner.add_context(cid, tx))
+        }
+    }
+    fn remove_context(&mut self, ctx: &mut Context, cid: ContextId) {
+        self.inner.remove_context(ctx, cid)
+    }
+    fn context_send(&mut self, ctx: &mut Context, cid: ContextId, msg: Message, timeout: Timeout) {
+        self.apply_on_contexts(ctx, |ctx, inner| inner.context_send(ctx, cid, msg, timeout))
+    }
+    fn on_context_send_timeout(&mut self, _: &mut Context, cid: ContextId) {
+        self.inner.on_context_send_timeout(cid)
+    }
+    fn context_recv(&mut self, ctx: &mut Context, cid: ContextId, timeout: Timeout) {
+        self.apply_on_contexts(ctx, |ctx, inner| inner.context_recv(ctx, cid, timeout))
+    }
+    fn on_context_recv_timeout(&mut self, _: &mut Context, cid: ContextId) {
+        self.inner.on_context_recv_timeout(cid)
+    }
     fn close(&mut self, ctx: &mut Context) {
         self.inner.close(ctx)
     }
@@ -315,6 +404,18 @@ impl State {
             any => any
         }
     }
+    fn on_recv_dropped(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
+        match self {
+            State::Receiving(id, timeout) => {
+                if id == eid {
+                    State::Idle.recv(ctx, inner, timeout)
+                } else {
+                    State::Receiving(id, timeout)
+                }
+            },
+            any => any
+        }
+    }
     fn on_recv_timeout(self, _: &mut Context, inner: &mut Inner) -> State {
         inner.on_recv_timeout();
 
@@ -352,7 +453,12 @@ impl Inner {
             sd: HashSet::new(),
             ttl: 8,
             backtrace: Vec::new(),
-            is_device_item: false
+            is_device_item: false,
+            contexts: HashMap::default(),
+            ctx_recv_queue: VecDeque::new(),
+            ctx_requests: VecDeque::new(),
+            ctx_reading: HashSet::new(),
+            ctx_sending: HashMap::default()
         }
     }
     fn add_pipe(&mut self, eid: EndpointId, pipe: Pipe) {
@@ -436,7 +542,7 @@ impl Inner {
         self.fq.peek()
     }
  
-    fn raw_msg_to_msg(&self, raw_msg: Message, eid: EndpointId) -> Option<Message> {
+    fn raw_msg_to_msg(&self, ctx: &Context, raw_msg: Message, eid: EndpointId) -> Decoded {
         let (mut header, mut body) = raw_msg.split();
         let mut hops = 0;
         let mut eid_bytes: [u8; 4] = [0; 4];
@@ -449,12 +555,13 @@ impl Inner {
 
         loop {
             if hops >= self.ttl {
-                return None;
+                info!("[{:?}] message dropped after {} hops, max ttl exceeded", ctx, hops);
+                return Decoded::TtlExceeded;
             }
             hops += 1;
 
             if body.len() < 4 {
-                return None;
+                return Decoded::Malformed;
             }
 
             let tail = body.split_off(4);
@@ -463,7 +570,7 @@ impl Inner {
 
             let position = header.len() - 4;
             if header[position] & 0x80 != 0 {
-                return Some(Message::from_header_and_body(header, tail));
+                return Decoded::Msg(Message::from_header_and_body(header, tail));
             }
             body = tail;
         }
@@ -478,15 +585,7 @@ impl Inner {
             header.extend_from_slice(backtrace);
         }
 
-        if header.len() < 4 {
-            return None;
-        }
-
-        let tail = header.split_off(4);
-        let eid_u32 = BigEndian::read_u32(&header);
-        let eid = EndpointId::from(eid_u32 as usize);
-        
-        Some((Message::from_header_and_body(tail, body), eid))
+        route(header, body)
     }
     fn set_backtrace(&mut self, bt: &[u8]) {
         self.backtrace.clear();
@@ -498,11 +597,216 @@ impl Inner {
     fn clear_backtrace(&mut self) {
         self.backtrace.clear();
     }
+    fn set_max_ttl(&mut self, ttl: u8) -> io::Result<()> {
+        if ttl == 0 {
+            Err(invalid_input_io_error("max ttl must be between 1 and 255"))
+        } else {
+            Ok(self.ttl = ttl)
+        }
+    }
     fn close(&mut self, ctx: &mut Context) {
         self.pipes.close_all(ctx)
     }
 }
 
+fn route(mut header: Vec<u8>, body: Vec<u8>) -> Option<(Message, EndpointId)> {
+    if header.len() < 4 {
+        return None;
+    }
+
+    let tail = header.split_off(4);
+    let eid_u32 = BigEndian::read_u32(&header);
+    let eid = EndpointId::from(eid_u32 as usize);
+
+    Some((Message::from_header_and_body(tail, body), eid))
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Contexts                                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+impl RepContext {
+    fn new(tx: Sender<Reply>) -> RepContext {
+        RepContext {
+            reply_tx: tx,
+            recv: None,
+            backtrace: None,
+            send: None
+        }
+    }
+    fn is_waiting_send_to(&self, eid: &EndpointId) -> bool {
+        self.send.as_ref().map_or(false, |&(ref id, _, _)| id == eid)
+    }
+    fn cancel(&mut self, ctx: &mut Context) {
+        if let Some((_, _, Some(sched))) = self.send.take() {
+            ctx.cancel(sched);
+        }
+        if let Some(Some(sched)) = self.recv.take() {
+            ctx.cancel(sched);
+        }
+        self.backtrace = None;
+    }
+    fn on_send_ack(&mut self, ctx: &mut Context) {
+        if let Some((_, _, timeout)) = self.send.take() {
+            let _ = self.reply_tx.send(Reply::Send);
+            if let Some(sched) = timeout {
+                ctx.cancel(sched);
+            }
+        }
+    }
+    fn on_request(&mut self, ctx: &mut Context, mut msg: Message) {
+        self.backtrace = Some(msg.header.clone());
+        msg.header.clear();
+
+        if let Some(timeout) = self.recv.take() {
+            let _ = self.reply_tx.send(Reply::Recv(msg));
+            if let Some(sched) = timeout {
+                ctx.cancel(sched);
+            }
+        }
+    }
+}
+
+impl Inner {
+    fn add_context(&mut self, cid: ContextId, tx: Sender<Reply>) {
+        self.contexts.insert(cid, RepContext::new(tx));
+    }
+    fn remove_context(&mut self, ctx: &mut Context, cid: ContextId) {
+        if let Some(mut c) = self.contexts.remove(&cid) {
+            c.cancel(ctx);
+            self.ctx_recv_queue.retain(|x| *x != cid);
+        }
+    }
+    fn has_pending_context_send(&self) -> bool {
+        self.contexts.values().any(|c| c.send.is_some())
+    }
+    fn is_context_sending_to(&self, eid: &EndpointId) -> bool {
+        self.ctx_sending.contains_key(eid)
+    }
+    fn context_send(&mut self, ctx: &mut Context, cid: ContextId, msg: Message, timeout: Timeout) {
+        let eid = match self.contexts.get_mut(&cid) {
+            Some(c) => {
+                let routed = c.backtrace.take().map(|bt| route(bt, msg.body));
+
+                match routed {
+                    Some(Some((raw_msg, eid))) => {
+                        c.send = Some((eid, Rc::new(raw_msg), timeout));
+                        eid
+                    },
+                    Some(None) => {
+                        let error = invalid_data_io_error("Sending without eid");
+                        let _ = c.reply_tx.send(Reply::Err(error));
+                        if let Some(sched) = timeout {
+                            ctx.cancel(sched);
+                        }
+                        return;
+                    },
+                    None => {
+                        let error = other_io_error("Can't send: no active request");
+                        let _ = c.reply_tx.send(Reply::Err(error));
+                        if let Some(sched) = timeout {
+                            ctx.cancel(sched);
+                        }
+                        return;
+                    }
+                }
+            },
+            None => return
+        };
+
+        self.send_for_contexts(ctx, eid);
+    }
+    fn send_for_contexts(&mut self, ctx: &mut Context, eid: EndpointId) {
+        if !self.is_send_ready_to(&eid) {
+            return;
+        }
+
+        let found = self.contexts.iter()
+            .find(|&(_, c)| c.is_waiting_send_to(&eid))
+            .and_then(|(cid, c)| c.send.as_ref().map(|&(_, ref msg, _)| (*cid, msg.clone())));
+
+        if let Some((cid, msg)) = found {
+            if self.send_to(ctx, msg, eid) {
+                self.ctx_sending.insert(eid, cid);
+            }
+        }
+    }
+    fn on_context_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
+        if let Some(cid) = self.ctx_sending.remove(&eid) {
+            if let Some(c) = self.contexts.get_mut(&cid) {
+                c.on_send_ack(ctx);
+            }
+        }
+    }
+    fn on_context_send_timeout(&mut self, cid: ContextId) {
+        if let Some(c) = self.contexts.get_mut(&cid) {
+            if c.send.take().is_some() {
+                let error = timedout_io_error("Send timed out");
+                let _ = c.reply_tx.send(Reply::Err(error));
+            }
+        }
+    }
+    fn on_context_pipe_removed(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.ctx_sending.remove(&eid);
+        self.ctx_reading.remove(&eid);
+        self.recv_for_contexts(ctx);
+    }
+
+    fn context_recv(&mut self, ctx: &mut Context, cid: ContextId, timeout: Timeout) {
+        if let Some(c) = self.contexts.get_mut(&cid) {
+            c.backtrace = None;
+            c.recv = Some(timeout);
+            if let Some(msg) = self.ctx_requests.pop_front() {
+                c.on_request(ctx, msg);
+                return;
+            }
+        } else {
+            return;
+        }
+
+        self.ctx_recv_queue.push_back(cid);
+        self.recv_for_contexts(ctx);
+    }
+    fn recv_for_contexts(&mut self, ctx: &mut Context) {
+        while self.ctx_reading.len() < self.ctx_recv_queue.len() {
+            match self.recv(ctx) {
+                Some(eid) => self.ctx_reading.insert(eid),
+                None => break
+            };
+        }
+    }
+    fn on_context_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, raw_msg: Message) {
+        if let Decoded::Msg(msg) = self.raw_msg_to_msg(ctx, raw_msg, eid) {
+            self.on_context_request(ctx, msg);
+        }
+
+        self.recv_for_contexts(ctx);
+    }
+    fn on_context_request(&mut self, ctx: &mut Context, msg: Message) {
+        while let Some(cid) = self.ctx_recv_queue.pop_front() {
+            if let Some(c) = self.contexts.get_mut(&cid) {
+                if c.recv.is_some() {
+                    return c.on_request(ctx, msg);
+                }
+            }
+        }
+
+        self.ctx_requests.push_back(msg);
+    }
+    fn on_context_recv_timeout(&mut self, cid: ContextId) {
+        if let Some(c) = self.contexts.get_mut(&cid) {
+            if c.recv.take().is_some() {
+                let error = timedout_io_error("Recv timed out");
+                let _ = c.reply_tx.send(Reply::Err(error));
+            }
+        }
+
+        self.ctx_recv_queue.retain(|x| *x != cid);
+    }
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* tests                                                                     */
@@ -518,7 +822,7 @@ mod tests {
 
     use byteorder::*;
 
-    use core::{EndpointId, Message, Scheduled};
+    use core::{ContextId, EndpointId, Message, Scheduled};
     use core::socket::{Protocol, Reply};
     use core::context::{Event};
     use core::tests::*;
@@ -864,4 +1168,153 @@ mod tests {
 
         ctx_sensor.borrow().assert_no_send_call();
     }
+
+    fn request_from(id: u32) -> Message {
+        let mut body: Vec<u8> = vec![0, 0, 0, 0, 1];
+
+        BigEndian::write_u32(&mut body[0..4], id | 0x80000000);
+
+        Message::from_body(body)
+    }
+
+    #[test]
+    fn context_requests_are_delivered_to_waiting_contexts_in_order() {
+        let (tx, _) = mpsc::channel();
+        let mut rep = Rep::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid1 = EndpointId::from(1);
+        let eid2 = EndpointId::from(2);
+        let (ctx_tx1, ctx_rx1) = mpsc::channel();
+        let (ctx_tx2, ctx_rx2) = mpsc::channel();
+        let cid1 = ContextId::from(1);
+        let cid2 = ContextId::from(2);
+
+        rep.add_pipe(&mut ctx, eid1, new_test_pipe(eid1));
+        rep.add_pipe(&mut ctx, eid2, new_test_pipe(eid2));
+        rep.add_context(&mut ctx, cid1, ctx_tx1).unwrap();
+        rep.add_context(&mut ctx, cid2, ctx_tx2).unwrap();
+        rep.context_recv(&mut ctx, cid1, None);
+        rep.context_recv(&mut ctx, cid2, None);
+        ctx_sensor.borrow().assert_no_recv_call();
+
+        rep.on_recv_ready(&mut ctx, eid1);
+        rep.on_recv_ready(&mut ctx, eid2);
+        {
+            let sensor = ctx_sensor.borrow();
+            sensor.assert_recv_from(eid1, 1);
+            sensor.assert_recv_from(eid2, 1);
+        }
+
+        rep.on_recv_ack(&mut ctx, eid2, request_from(2));
+        rep.on_recv_ack(&mut ctx, eid1, request_from(1));
+
+        match ctx_rx1.try_recv() {
+            Ok(Reply::Recv(msg)) => assert_eq!(0, msg.get_header().len()),
+            _ => panic!("first context should have been sent a Recv reply !")
+        }
+        match ctx_rx2.try_recv() {
+            Ok(Reply::Recv(msg)) => assert_eq!(0, msg.get_header().len()),
+            _ => panic!("second context should have been sent a Recv reply !")
+        }
+    }
+
+    #[test]
+    fn context_replies_are_routed_to_the_pipe_their_request_came_from() {
+        let (tx, _) = mpsc::channel();
+        let mut rep = Rep::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid1 = EndpointId::from(1);
+        let eid2 = EndpointId::from(2);
+        let (ctx_tx1, ctx_rx1) = mpsc::channel();
+        let (ctx_tx2, ctx_rx2) = mpsc::channel();
+        let cid1 = ContextId::from(1);
+        let cid2 = ContextId::from(2);
+
+        rep.add_pipe(&mut ctx, eid1, new_test_pipe(eid1));
+        rep.add_pipe(&mut ctx, eid2, new_test_pipe(eid2));
+        rep.add_context(&mut ctx, cid1, ctx_tx1).unwrap();
+        rep.add_context(&mut ctx, cid2, ctx_tx2).unwrap();
+        rep.context_recv(&mut ctx, cid1, None);
+        rep.context_recv(&mut ctx, cid2, None);
+        rep.on_recv_ready(&mut ctx, eid1);
+        rep.on_recv_ready(&mut ctx, eid2);
+        rep.on_recv_ack(&mut ctx, eid1, request_from(1));
+        rep.on_recv_ack(&mut ctx, eid2, request_from(2));
+        let _ = ctx_rx1.try_recv(); // flush recv reply
+        let _ = ctx_rx2.try_recv(); // flush recv reply
+
+        rep.context_send(&mut ctx, cid2, Message::from_body(vec![2]), None);
+        rep.context_send(&mut ctx, cid1, Message::from_body(vec![1]), None);
+        ctx_sensor.borrow().assert_no_send_call();
+
+        rep.on_send_ready(&mut ctx, eid2);
+        rep.on_send_ready(&mut ctx, eid1);
+        {
+            let sensor = ctx_sensor.borrow();
+            let sends = sensor.get_send_calls();
+
+            assert_eq!(2, sends.len());
+            assert_eq!(eid2, sends[0].0);
+            assert_eq!(&[2], sends[0].1.get_body());
+            assert_eq!(eid1, sends[1].0);
+            assert_eq!(&[1], sends[1].1.get_body());
+        }
+
+        rep.on_send_ack(&mut ctx, eid1);
+        assert!(ctx_rx2.try_recv().is_err());
+        match ctx_rx1.try_recv() {
+            Ok(Reply::Send) => {},
+            _ => panic!("context should have been sent a Send reply !")
+        }
+    }
+
+    #[test]
+    fn context_send_before_recv_notifies_an_error() {
+        let (tx, _) = mpsc::channel();
+        let mut rep = Rep::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let (ctx_tx, ctx_rx) = mpsc::channel();
+        let cid = ContextId::from(1);
+
+        rep.add_context(&mut ctx, cid, ctx_tx).unwrap();
+        rep.context_send(&mut ctx, cid, Message::new(), None);
+
+        match ctx_rx.try_recv() {
+            Ok(Reply::Err(_)) => {},
+            _ => panic!("context should have been sent an Err reply !")
+        }
+        ctx_sensor.borrow().assert_no_send_call();
+    }
+
+    #[test]
+    fn requests_that_went_through_more_devices_than_max_ttl_are_dropped() {
+        let (tx, rx) = mpsc::channel();
+        let mut rep = Rep::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(0);
+        let pipe = new_test_pipe(eid);
+        let request_id = 666 | 0x80000000;
+        let mut body: Vec<u8> = vec![0, 2, 4, 2, 0, 0, 0, 0, 4, 2, 1];
+
+        BigEndian::write_u32(&mut body[4..8], request_id);
+
+        assert!(rep.set_option(ConfigOption::MaxTtl(0)).is_err());
+        rep.set_option(ConfigOption::MaxTtl(1)).unwrap();
+        rep.add_pipe(&mut ctx, eid, pipe);
+        rep.on_recv_ready(&mut ctx, eid);
+        rep.recv(&mut ctx, None);
+        rep.on_recv_ack(&mut ctx, eid, Message::from_body(body));
+
+        assert!(rx.try_recv().is_err());
+        ctx_sensor.borrow().assert_one_recv_from(eid);
+
+        rep.on_recv_ready(&mut ctx, eid);
+
+        assert!(rx.try_recv().is_err());
+        ctx_sensor.borrow().assert_recv_from(eid, 2);
+    }
 }
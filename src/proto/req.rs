@@ -4,7 +4,7 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::rc::Rc;
 use std::sync::mpsc::Sender;
 use std::io;
@@ -14,12 +14,13 @@ use time;
 
 use byteorder::*;
 
-use core::{EndpointId, Message};
+use core::{BuildIdHasher, ContextId, EndpointId, Message};
 use core::socket::{Protocol, Reply};
 use core::config::ConfigOption;
 use core::endpoint::Pipe;
 use core::context::{Context, Schedulable};
 use super::priolist::Priolist;
+use super::raw::Rawable;
 use super::pipes::PipeCollection;
 use super::{Timeout, REQ, REP};
 use super::policy::{load_balancing, fair_queue};
@@ -35,6 +36,8 @@ enum State {
     Sending(EndpointId, Rc<Message>, Timeout, bool),
     SendOnHold(Rc<Message>, Timeout, bool),
     Active(EndpointId, PendingRequest),
+    Prefetching(EndpointId, PendingRequest),
+    Prefetched(Message),
     Receiving(EndpointId, Option<PendingRequest>, Timeout),
     RecvOnHold(Option<EndpointId>, Option<PendingRequest>, Timeout)
 }
@@ -46,8 +49,13 @@ struct Inner {
     fq: Priolist,
     rv: HashSet<EndpointId>,
     req_id_seq: u32,
+    cur_req_id: u32,
     is_device_item: bool,
-    resend_ivl: Duration
+    resend_ivl: Duration,
+    contexts: HashMap<ContextId, ReqContext, BuildIdHasher>,
+    ctx_send_queue: VecDeque<ContextId>,
+    ctx_sending: HashMap<EndpointId, ContextId, BuildIdHasher>,
+    ctx_reading: HashSet<EndpointId>
 }
 
 struct PendingRequest {
@@ -55,6 +63,20 @@ struct PendingRequest {
     retry_timeout: Timeout
 }
 
+/// Request state of a context, the request is kept until its reply is received, to be resent if needed.
+/// While `send` is set, the request is either waiting for a pipe, or being sent to `sent_to`.
+/// Once sent, `sent_to` is the pipe the reply is expected from.
+struct ReqContext {
+    reply_tx: Sender<Reply>,
+    req_id: u32,
+    req: Option<Rc<Message>>,
+    send: Option<(Timeout, bool)>,
+    sent_to: Option<EndpointId>,
+    retry_timeout: Timeout,
+    recv: Option<Timeout>,
+    reply: Option<Message>
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* Req                                                                      */
@@ -82,6 +104,27 @@ impl Req {
         }
     }
 
+    fn apply_on_contexts<F>(&mut self, ctx: &mut Context, f: F) where F : FnOnce(&mut Context, &mut Inner) {
+        let was_send_ready = self.is_send_ready();
+        let was_recv_ready = self.is_recv_ready();
+
+        f(ctx, &mut self.inner);
+
+        let is_send_ready = self.is_send_ready();
+        let is_recv_ready = self.is_recv_ready();
+
+        ctx.check_send_ready_change(was_send_ready, is_send_ready);
+        ctx.check_recv_ready_change(was_recv_ready, is_recv_ready);
+    }
+
+    fn recv_for_contexts(&mut self, ctx: &mut Context, eid: EndpointId) {
+        let is_reading = self.state.as_ref().map_or(false, |s| s.is_reading_from(&eid));
+
+        if !is_reading {
+            self.apply_on_contexts(ctx, |ctx, inner| inner.recv_for_contexts(ctx, eid))
+        }
+    }
+
 }
 
 impl From<Sender<Reply>> for Req {
@@ -93,6 +136,12 @@ impl From<Sender<Reply>> for Req {
     }
 }
 
+impl Rawable for Req {
+    fn set_raw(&mut self) {
+        self.inner.is_device_item = true;
+    }
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* Protocol                                                                  */
@@ -115,6 +164,7 @@ impl Protocol for Req {
 
         if pipe.is_some() {
             self.apply(ctx, |s, ctx, inner| s.on_pipe_removed(ctx, inner, eid));
+            self.apply_on_contexts(ctx, |ctx, inner| inner.on_context_pipe_removed(ctx, eid));
         }
 
         ctx.check_send_ready_change(was_send_ready, is_send_ready);
@@ -128,13 +178,19 @@ impl Protocol for Req {
         self.apply(ctx, |s, ctx, inner| s.send(ctx, inner, Rc::new(raw_msg), timeout, false))
     }
     fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_send_ack(ctx, inner, eid))
+        if self.inner.is_context_sending_to(&eid) {
+            self.apply_on_contexts(ctx, |ctx, inner| inner.on_context_send_ack(ctx, eid));
+            self.recv_for_contexts(ctx, eid)
+        } else {
+            self.apply(ctx, |s, ctx, inner| s.on_send_ack(ctx, inner, eid))
+        }
     }
     fn on_send_timeout(&mut self, ctx: &mut Context) {
         self.apply(ctx, |s, ctx, inner| s.on_send_timeout(ctx, inner))
     }
     fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_send_ready(ctx, inner, eid))
+        self.apply(ctx, |s, ctx, inner| s.on_send_ready(ctx, inner, eid));
+        self.apply_on_contexts(ctx, |ctx, inner| inner.send_for_contexts(ctx))
     }
     fn on_send_not_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
         self.apply(ctx, |s, ctx, inner| s.on_send_not_ready(ctx, inner, eid))
@@ -143,17 +199,38 @@ impl Protocol for Req {
         self.apply(ctx, |s, ctx, inner| s.recv(ctx, inner, timeout))
     }
     fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, raw_msg: Message) {
-        if let Some((msg, req_id)) = self.inner.raw_msg_to_msg(raw_msg) {
-            self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg, req_id))
-        } else {
-            self.inner.on_recv_ack_malformed(ctx)
+        let read_for_contexts = self.inner.ctx_reading.remove(&eid);
+
+        match self.inner.raw_msg_to_msg(raw_msg) {
+            Some((msg, req_id)) => {
+                if read_for_contexts || req_id != self.inner.cur_req_id() {
+                    match self.inner.on_context_reply(ctx, msg, req_id) {
+                        Ok(()) => self.apply(ctx, |s, ctx, inner| s.on_recv_skipped(ctx, inner, eid)),
+                        Err(msg) => if read_for_contexts {
+                            self.apply(ctx, |s, ctx, inner| s.on_reply_read(ctx, inner, eid, msg, req_id))
+                        } else {
+                            self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg, req_id))
+                        }
+                    }
+                } else {
+                    self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg, req_id))
+                }
+            },
+            None => if read_for_contexts {
+                self.apply(ctx, |s, ctx, inner| s.on_recv_skipped(ctx, inner, eid))
+            } else {
+                self.inner.on_recv_ack_malformed(ctx)
+            }
         }
+
+        self.recv_for_contexts(ctx, eid)
     }
     fn on_recv_timeout(&mut self, ctx: &mut Context) {
         self.apply(ctx, |s, ctx, inner| s.on_recv_timeout(ctx, inner))
     }
     fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_recv_ready(ctx, inner, eid))
+        self.apply(ctx, |s, ctx, inner| s.on_recv_ready(ctx, inner, eid));
+        self.recv_for_contexts(ctx, eid)
     }
     fn on_recv_not_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
         self.apply(ctx, |s, ctx, inner| s.on_recv_not_ready(ctx, inner, eid))
@@ -165,8 +242,10 @@ impl Protocol for Req {
         }
     }
     fn on_timer_tick(&mut self, ctx: &mut Context, task: Schedulable) {
-        if let Schedulable::ReqResend = task {
-            self.apply(ctx, |s, ctx, inner| s.on_retry_timeout(ctx, inner))
+        match task {
+            Schedulable::ReqResend => self.apply(ctx, |s, ctx, inner| s.on_retry_timeout(ctx, inner)),
+            Schedulable::ContextResend(cid) => self.apply_on_contexts(ctx, |ctx, inner| inner.on_context_retry_timeout(ctx, cid)),
+            _ => {}
         }
     }
     fn on_device_plugged(&mut self, _: &mut Context) {
@@ -186,6 +265,35 @@ impl Protocol for Req {
             false
         }
     }
+    fn has_pending_send(&self) -> bool {
+        match self.state {
+            Some(State::Sending(..))    |
+            Some(State::SendOnHold(..)) => true,
+            _ => self.inner.has_pending_context_send()
+        }
+    }
+    fn add_context(&mut self, _: &mut Context, cid: ContextId, tx: Sender<Reply>) -> io::Result<()> {
+        if self.inner.is_device_item {
+            Err(invalid_input_io_error("contexts not supported by raw sockets"))
+        } else {
+            Ok(self.inner.add_context(cid, tx))
+        }
+    }
+    fn remove_context(&mut self, ctx: &mut Context, cid: ContextId) {
+        self.inner.remove_context(ctx, cid)
+    }
+    fn context_send(&mut self, ctx: &mut Context, cid: ContextId, msg: Message, timeout: Timeout) {
+        self.apply_on_contexts(ctx, |ctx, inner| inner.context_send(ctx, cid, msg, timeout))
+    }
+    fn on_context_send_timeout(&mut self, _: &mut Context, cid: ContextId) {
+        self.inner.on_context_send_timeout(cid)
+    }
+    fn context_recv(&mut self, ctx: &mut Context, cid: ContextId, timeout: Timeout) {
+        self.inner.context_recv(ctx, cid, timeout)
+    }
+    fn on_context_recv_timeout(&mut self, ctx: &mut Context, cid: ContextId) {
+        self.inner.on_context_recv_timeout(ctx, cid)
+    }
     fn close(&mut self, ctx: &mut Context) {
         self.inner.close(ctx)
     }
@@ -207,6 +315,8 @@ impl State {
             State::Sending(_, _, _, true)  => "Resending",
             State::SendOnHold(_, _, _)     => "SendOnHold",
             State::Active(..)               => "Active",
+            State::Prefetching(..)          => "Prefetching",
+            State::Prefetched(..)           => "Prefetched",
             State::Receiving(..)         => "Receiving",
             State::RecvOnHold(..)        => "RecvOnHold"
         }
@@ -228,6 +338,13 @@ impl State {
                     State::Receiving(id, p, timeout)
                 }
             },
+            State::Prefetching(id, p) => {
+                if eid == id {
+                    State::Active(id, p)
+                } else {
+                    State::Prefetching(id, p)
+                }
+            },
             any => any
         }
     }
@@ -239,8 +356,10 @@ impl State {
 /*****************************************************************************/
 
     fn send(self, ctx: &mut Context, inner: &mut Inner, msg: Rc<Message>, timeout: Timeout, retry: bool) -> State {
-        if let State::Active(_, p) = self {
-            inner.cancel(ctx, p);
+        match self {
+            State::Active(_, p) |
+            State::Prefetching(_, p) => inner.cancel(ctx, p),
+            _ => {}
         }
         if let Some(eid) = inner.send(ctx, msg.clone()) {
             State::Sending(eid, msg, timeout, retry)
@@ -263,7 +382,7 @@ impl State {
                     State::Active(eid, PendingRequest {
                         req: msg,
                         retry_timeout: retry_timeout
-                    })
+                    }).prefetch(ctx, inner)
                 }
             },
             any => any
@@ -301,12 +420,31 @@ impl State {
             inner.recv(ctx).map_or_else(
                 |   | State::RecvOnHold(None, None, timeout),
                 |eid| State::Receiving(eid, None, timeout))
-        } else if let State::Active(eid, p) = self {
-            State::Idle.recv_reply_for(ctx, inner, timeout, eid, p)
         } else {
-            inner.recv_when_inactive(ctx, timeout);
-
-            State::Idle
+            match self {
+                State::Active(eid, p) => State::Idle.recv_reply_for(ctx, inner, timeout, eid, p),
+                State::Prefetching(eid, p) => State::Receiving(eid, Some(p), timeout),
+                State::Prefetched(msg) => {
+                    inner.on_recv_ack(ctx, timeout, msg, None);
+                    State::Idle
+                },
+                _ => {
+                    inner.recv_when_inactive(ctx, timeout);
+                    State::Idle
+                }
+            }
+        }
+    }
+    fn prefetch(self, ctx: &mut Context, inner: &mut Inner) -> State {
+        match self {
+            State::Active(eid, p) => {
+                if inner.is_recv_ready_from(&eid) && inner.recv_reply_from(ctx, eid) {
+                    State::Prefetching(eid, p)
+                } else {
+                    State::Active(eid, p)
+                }
+            },
+            any => any
         }
     }
     fn recv_reply_for(self, ctx: &mut Context, inner: &mut Inner, timeout: Timeout, eid: EndpointId, p: PendingRequest) -> State {
@@ -338,6 +476,68 @@ impl State {
                     State::Receiving(id, Some(p), timeout)
                 }
             },
+            State::Prefetching(id, p) => {
+                if eid != id {
+                    State::Prefetching(id, p)
+                } else if inner.cur_req_id() == req_id {
+                    inner.on_reply_prefetched(ctx, p.retry_timeout);
+                    State::Prefetched(msg)
+                } else {
+                    State::Active(id, p).prefetch(ctx, inner)
+                }
+            },
+            any => any
+        }
+    }
+    fn on_recv_skipped(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
+        match self {
+            State::Receiving(id, Some(p), timeout) => {
+                if eid == id {
+                    State::Idle.recv_reply_for(ctx, inner, timeout, id, p)
+                } else {
+                    State::Receiving(id, Some(p), timeout)
+                }
+            },
+            State::Prefetching(id, p) => {
+                if eid == id {
+                    State::Active(id, p).prefetch(ctx, inner)
+                } else {
+                    State::Prefetching(id, p)
+                }
+            },
+            State::RecvOnHold(Some(id), Some(p), timeout) => {
+                if eid == id {
+                    State::Idle.recv_reply_for(ctx, inner, timeout, id, p)
+                } else {
+                    State::RecvOnHold(Some(id), Some(p), timeout)
+                }
+            },
+            State::Active(id, p) => State::Active(id, p).prefetch(ctx, inner),
+            any => any
+        }
+    }
+    fn on_reply_read(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId, msg: Message, req_id: u32) -> State {
+        if inner.cur_req_id() != req_id {
+            return self.on_recv_skipped(ctx, inner, eid);
+        }
+
+        match self {
+            State::Active(id, p) => {
+                if eid == id {
+                    inner.on_reply_prefetched(ctx, p.retry_timeout);
+                    State::Prefetched(msg)
+                } else {
+                    State::Active(id, p)
+                }
+            },
+            State::RecvOnHold(Some(id), Some(p), timeout) => {
+                if eid == id {
+                    inner.on_recv_ack(ctx, timeout, msg, p.retry_timeout);
+                    State::Idle
+                } else {
+                    State::RecvOnHold(Some(id), Some(p), timeout)
+                }
+            },
             any => any
         }
     }
@@ -365,6 +565,7 @@ impl State {
                     State::RecvOnHold(Some(id), Some(p), timeout)
                 }
             },
+            State::Active(id, p) => State::Active(id, p).prefetch(ctx, inner),
             any => any
         }
     }
@@ -373,19 +574,29 @@ impl State {
         self
     }
     fn on_retry_timeout(self, ctx: &mut Context, inner: &mut Inner) -> State {
-        if let State::Active(_, p) = self {
-            State::Idle.send(ctx, inner, p.req, None, true)
-        } else {
-            self
+        match self {
+            State::Active(_, p) |
+            State::Prefetching(_, p) => State::Idle.send(ctx, inner, p.req, None, true),
+            any => any
         }
     }
     fn is_recv_ready(&self, inner: &Inner) -> bool {
         if inner.is_device_item {
             inner.is_recv_ready()
-        } else if let State::Active(ref eid, _) = *self {
-            inner.is_recv_ready_from(eid)
         } else {
-            false
+            match *self {
+                State::Active(ref eid, _) => inner.is_recv_ready_from(eid),
+                State::Prefetching(..) |
+                State::Prefetched(_) => true,
+                _ => false
+            }
+        }
+    }
+    fn is_reading_from(&self, eid: &EndpointId) -> bool {
+        match *self {
+            State::Receiving(ref id, _, _) |
+            State::Prefetching(ref id, _) => id == eid,
+            _ => false
         }
     }
 }
@@ -398,15 +609,22 @@ impl State {
 
 impl Inner {
     fn new(tx: Sender<Reply>) -> Inner {
+        let seq = time::get_time().nsec as u32;
+
         Inner {
             reply_tx: tx,
             pipes: PipeCollection::new(),
             lb: Priolist::new(),
             fq: Priolist::new(),
             rv: HashSet::new(),
-            req_id_seq: time::get_time().nsec as u32,
+            req_id_seq: seq,
+            cur_req_id: seq | 0x80000000,
             is_device_item: false,
-            resend_ivl: Duration::from_secs(60)
+            resend_ivl: Duration::from_secs(60),
+            contexts: HashMap::default(),
+            ctx_send_queue: VecDeque::new(),
+            ctx_sending: HashMap::default(),
+            ctx_reading: HashSet::new()
         }
     }
     fn add_pipe(&mut self, eid: EndpointId, pipe: Pipe) {
@@ -459,6 +677,10 @@ impl Inner {
         fair_queue::recv(&mut self.fq, &mut self.pipes, ctx)
     }
     fn recv_reply_from(&mut self, ctx: &mut Context, eid: EndpointId) -> bool {
+        if self.ctx_reading.contains(&eid) {
+            return false;
+        }
+
         self.rv.remove(&eid);
         self.pipes.get_mut(&eid).map(|pipe| pipe.recv(ctx)).is_some()
     }
@@ -478,6 +700,11 @@ impl Inner {
             ctx.cancel(sched);
         }
     }
+    fn on_reply_prefetched(&self, ctx: &mut Context, retry_timeout: Timeout) {
+        if let Some(sched) = retry_timeout {
+            ctx.cancel(sched);
+        }
+    }
     fn on_recv_timeout(&self, ctx: &mut Context, retry_timeout: Timeout) {
         let error = timedout_io_error("Recv timed out");
         let _ = self.reply_tx.send(Reply::Err(error));
@@ -522,11 +749,16 @@ impl Inner {
     }
 
     fn cur_req_id(&self) -> u32 {
-        self.req_id_seq | 0x80000000
+        self.cur_req_id
     }
 
     fn next_req_id(&mut self) -> u32 {
-        self.req_id_seq += 1;
+        self.cur_req_id = self.new_req_id();
+        self.cur_req_id
+    }
+
+    fn new_req_id(&mut self) -> u32 {
+        self.req_id_seq = self.req_id_seq.wrapping_add(1);
         self.req_id_seq | 0x80000000
     }
     fn set_resend_ivl(&mut self, ivl: Duration) {
@@ -537,6 +769,228 @@ impl Inner {
     }
 }
 
+/*****************************************************************************/
+/*                                                                           */
+/* Contexts                                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+impl ReqContext {
+    fn new(tx: Sender<Reply>) -> ReqContext {
+        ReqContext {
+            reply_tx: tx,
+            req_id: 0,
+            req: None,
+            send: None,
+            sent_to: None,
+            retry_timeout: None,
+            recv: None,
+            reply: None
+        }
+    }
+    fn is_waiting_reply_from(&self, eid: &EndpointId) -> bool {
+        self.req.is_some() && self.send.is_none() && self.sent_to.as_ref() == Some(eid)
+    }
+    fn cancel_retry(&mut self, ctx: &mut Context) {
+        if let Some(sched) = self.retry_timeout.take() {
+            ctx.cancel(sched);
+        }
+    }
+    fn cancel(&mut self, ctx: &mut Context) {
+        self.cancel_retry(ctx);
+        self.req = None;
+        self.sent_to = None;
+        self.reply = None;
+    }
+    fn on_send_ack(&mut self, ctx: &mut Context) {
+        if let Some((timeout, retry)) = self.send.take() {
+            if !retry {
+                let _ = self.reply_tx.send(Reply::Send);
+            }
+            if let Some(sched) = timeout {
+                ctx.cancel(sched);
+            }
+        }
+    }
+    fn on_reply(&mut self, ctx: &mut Context, msg: Message) {
+        self.on_send_ack(ctx);
+        self.cancel_retry(ctx);
+        self.req = None;
+        self.sent_to = None;
+
+        if let Some(timeout) = self.recv.take() {
+            let _ = self.reply_tx.send(Reply::Recv(msg));
+            if let Some(sched) = timeout {
+                ctx.cancel(sched);
+            }
+        } else {
+            self.reply = Some(msg);
+        }
+    }
+}
+
+impl Inner {
+    fn add_context(&mut self, cid: ContextId, tx: Sender<Reply>) {
+        self.contexts.insert(cid, ReqContext::new(tx));
+    }
+    fn remove_context(&mut self, ctx: &mut Context, cid: ContextId) {
+        if let Some(mut c) = self.contexts.remove(&cid) {
+            c.cancel(ctx);
+            if let Some((Some(sched), _)) = c.send.take() {
+                ctx.cancel(sched);
+            }
+            if let Some(Some(sched)) = c.recv.take() {
+                ctx.cancel(sched);
+            }
+            self.ctx_send_queue.retain(|x| *x != cid);
+        }
+    }
+    fn has_pending_context_send(&self) -> bool {
+        self.contexts.values().any(|c| c.send.is_some())
+    }
+    fn is_context_sending_to(&self, eid: &EndpointId) -> bool {
+        self.ctx_sending.contains_key(eid)
+    }
+
+    fn context_send(&mut self, ctx: &mut Context, cid: ContextId, msg: Message, timeout: Timeout) {
+        let req_id = self.new_req_id();
+
+        if let Some(c) = self.contexts.get_mut(&cid) {
+            c.cancel(ctx);
+            c.req_id = req_id;
+            c.req = Some(Rc::new(encode(msg, req_id)));
+            c.send = Some((timeout, false));
+        } else {
+            return;
+        }
+
+        self.ctx_send_queue.push_back(cid);
+        self.send_for_contexts(ctx);
+    }
+    fn send_for_contexts(&mut self, ctx: &mut Context) {
+        while self.lb.peek() {
+            let cid = match self.ctx_send_queue.pop_front() {
+                Some(cid) => cid,
+                None => return
+            };
+            let req = match self.contexts.get(&cid) {
+                Some(c) if c.send.is_some() => c.req.clone(),
+                _ => None
+            };
+
+            if let Some(req) = req {
+                if let Some(eid) = load_balancing::send(&mut self.lb, &mut self.pipes, ctx, req) {
+                    self.ctx_sending.insert(eid, cid);
+                    if let Some(c) = self.contexts.get_mut(&cid) {
+                        c.sent_to = Some(eid);
+                    }
+                }
+            }
+        }
+    }
+    fn on_context_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
+        if let Some(cid) = self.ctx_sending.remove(&eid) {
+            if let Some(c) = self.contexts.get_mut(&cid) {
+                if c.send.is_some() && c.sent_to == Some(eid) {
+                    c.on_send_ack(ctx);
+                    c.retry_timeout = ctx.schedule(Schedulable::ContextResend(cid), self.resend_ivl).ok();
+                }
+            }
+        }
+    }
+    fn on_context_send_timeout(&mut self, cid: ContextId) {
+        if let Some(c) = self.contexts.get_mut(&cid) {
+            if c.send.take().is_some() {
+                let error = timedout_io_error("Send timed out");
+                let _ = c.reply_tx.send(Reply::Err(error));
+                c.req = None;
+                c.sent_to = None;
+            }
+        }
+
+        self.ctx_send_queue.retain(|x| *x != cid);
+    }
+    fn on_context_retry_timeout(&mut self, ctx: &mut Context, cid: ContextId) {
+        if let Some(c) = self.contexts.get_mut(&cid) {
+            if c.retry_timeout.take().is_none() || c.req.is_none() || c.send.is_some() {
+                return;
+            }
+
+            c.sent_to = None;
+            c.send = Some((None, true));
+        } else {
+            return;
+        }
+
+        self.ctx_send_queue.push_back(cid);
+        self.send_for_contexts(ctx);
+    }
+    fn on_context_pipe_removed(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.ctx_sending.remove(&eid);
+        self.ctx_reading.remove(&eid);
+
+        for (cid, c) in &mut self.contexts {
+            if c.req.is_none() || c.sent_to != Some(eid) {
+                continue;
+            }
+
+            c.sent_to = None;
+            if c.send.is_none() {
+                c.cancel_retry(ctx);
+                c.send = Some((None, true));
+            }
+            self.ctx_send_queue.push_back(*cid);
+        }
+
+        self.send_for_contexts(ctx);
+    }
+
+    fn context_recv(&mut self, ctx: &mut Context, cid: ContextId, timeout: Timeout) {
+        if let Some(c) = self.contexts.get_mut(&cid) {
+            if let Some(msg) = c.reply.take() {
+                let _ = c.reply_tx.send(Reply::Recv(msg));
+            } else if c.req.is_some() {
+                c.recv = Some(timeout);
+                return;
+            } else {
+                let error = other_io_error("Can't recv: no active request");
+                let _ = c.reply_tx.send(Reply::Err(error));
+            }
+        }
+        if let Some(sched) = timeout {
+            ctx.cancel(sched);
+        }
+    }
+    fn recv_for_contexts(&mut self, ctx: &mut Context, eid: EndpointId) {
+        if self.ctx_reading.contains(&eid) {
+            return;
+        }
+        if !self.contexts.values().any(|c| c.is_waiting_reply_from(&eid)) {
+            return;
+        }
+        if self.recv_reply_from(ctx, eid) {
+            self.ctx_reading.insert(eid);
+        }
+    }
+    fn on_context_reply(&mut self, ctx: &mut Context, msg: Message, req_id: u32) -> Result<(), Message> {
+        let found = self.contexts.iter_mut().find(|&(_, ref c)| c.req.is_some() && c.req_id == req_id);
+
+        match found {
+            Some((_, c)) => Ok(c.on_reply(ctx, msg)),
+            None => Err(msg)
+        }
+    }
+    fn on_context_recv_timeout(&mut self, ctx: &mut Context, cid: ContextId) {
+        if let Some(c) = self.contexts.get_mut(&cid) {
+            if c.recv.take().is_some() {
+                let error = timedout_io_error("Recv timed out");
+                let _ = c.reply_tx.send(Reply::Err(error));
+                c.cancel(ctx);
+            }
+        }
+    }
+}
+
 fn encode(msg: Message, req_id: u32) -> Message {
     let mut raw_msg = msg;
     let mut req_id_bytes: [u8; 4] = [0; 4];
@@ -700,6 +1154,153 @@ mod tests {
         assert_eq!(Event::CanRecv(false), ctx_sensor.borrow().get_raised_events()[3]);
     }
 
+    #[test]
+    fn when_active_pipe_is_removed_other_pipes_are_not_read() {
+        let (tx, _) = mpsc::channel();
+        let mut req = Req::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(1);
+        let pipe = new_test_pipe(eid);
+        let eid_o = EndpointId::from(2);
+        let pipe_o = new_test_pipe(eid);
+
+        req.add_pipe(&mut ctx, eid, pipe);
+        req.add_pipe(&mut ctx, eid_o, pipe_o);
+        assert_eq!(0, ctx_sensor.borrow().get_raised_events().len());
+
+        req.on_send_ready(&mut ctx, eid);
+        assert_eq!(1, ctx_sensor.borrow().get_raised_events().len());
+        assert_eq!(Event::CanSend(true), ctx_sensor.borrow().get_raised_events()[0]);
+
+        req.send(&mut ctx, Message::new(), None);
+        assert_eq!(2, ctx_sensor.borrow().get_raised_events().len());
+        assert_eq!(Event::CanSend(false), ctx_sensor.borrow().get_raised_events()[1]);
+
+        req.on_send_ack(&mut ctx, eid);
+        assert_eq!(2, ctx_sensor.borrow().get_raised_events().len());
+
+        req.on_recv_ready(&mut ctx, eid_o);
+        assert_eq!(2, ctx_sensor.borrow().get_raised_events().len());
+
+        req.remove_pipe(&mut ctx, eid);
+        assert_eq!(2, ctx_sensor.borrow().get_raised_events().len());
+
+        req.on_recv_ready(&mut ctx, eid_o);
+        assert_eq!(2, ctx_sensor.borrow().get_raised_events().len());
+        ctx_sensor.borrow().assert_no_recv_call();
+    }
+
+    #[test]
+    fn when_active_pipe_is_recv_ready_reply_is_prefetched() {
+        let (tx, rx) = mpsc::channel();
+        let mut req = Req::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(1);
+        let pipe = new_test_pipe(eid);
+
+        req.add_pipe(&mut ctx, eid, pipe);
+        req.on_send_ready(&mut ctx, eid);
+        req.send(&mut ctx, Message::new(), None);
+        req.on_send_ack(&mut ctx, eid);
+        let _ = rx.try_recv().expect("facade should have been sent a reply !");
+
+        req.on_recv_ready(&mut ctx, eid);
+        ctx_sensor.borrow().assert_one_recv_from(eid);
+        assert_eq!(3, ctx_sensor.borrow().get_raised_events().len());
+        assert_eq!(Event::CanRecv(true), ctx_sensor.borrow().get_raised_events()[2]);
+
+        let good_request_id = (req.inner.req_id_seq) | 0x80000000;
+        let mut body: Vec<u8> = vec![0, 0, 0, 0, 4, 2, 1];
+
+        BigEndian::write_u32(&mut body[0..4], good_request_id);
+
+        req.on_recv_ack(&mut ctx, eid, Message::from_body(body));
+        assert!(rx.try_recv().is_err());
+        assert_eq!(3, ctx_sensor.borrow().get_raised_events().len());
+
+        req.recv(&mut ctx, None);
+        let reply = rx.try_recv().expect("facade should have been sent a reply !");
+        let is_reply_ok = match reply {
+            Reply::Recv(_) => true,
+            _ => false
+        };
+        assert!(is_reply_ok);
+        assert_eq!(4, ctx_sensor.borrow().get_raised_events().len());
+        assert_eq!(Event::CanRecv(false), ctx_sensor.borrow().get_raised_events()[3]);
+        ctx_sensor.borrow().assert_one_recv_from(eid);
+    }
+
+    #[test]
+    fn when_prefetched_reply_has_wrong_request_id_it_is_dropped() {
+        let (tx, rx) = mpsc::channel();
+        let mut req = Req::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(1);
+        let pipe = new_test_pipe(eid);
+
+        req.add_pipe(&mut ctx, eid, pipe);
+        req.on_send_ready(&mut ctx, eid);
+        req.send(&mut ctx, Message::new(), None);
+        req.on_send_ack(&mut ctx, eid);
+        let _ = rx.try_recv().expect("facade should have been sent a reply !");
+        req.on_recv_ready(&mut ctx, eid);
+
+        let bad_request_id = (req.inner.req_id_seq - 1) | 0x80000000;
+        let mut body: Vec<u8> = vec![0, 0, 0, 0, 4, 2, 1];
+
+        BigEndian::write_u32(&mut body[0..4], bad_request_id);
+
+        req.on_recv_ack(&mut ctx, eid, Message::from_body(body));
+        assert!(!req.is_recv_ready());
+        assert_eq!(4, ctx_sensor.borrow().get_raised_events().len());
+        assert_eq!(Event::CanRecv(false), ctx_sensor.borrow().get_raised_events()[3]);
+
+        req.recv(&mut ctx, None);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn when_other_pipe_is_removed_while_prefetching_reply_is_still_received() {
+        let (tx, rx) = mpsc::channel();
+        let mut req = Req::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(1);
+        let pipe = new_test_pipe(eid);
+        let eid_o = EndpointId::from(2);
+        let pipe_o = new_test_pipe(eid_o);
+
+        req.add_pipe(&mut ctx, eid, pipe);
+        req.add_pipe(&mut ctx, eid_o, pipe_o);
+        req.on_send_ready(&mut ctx, eid);
+        req.send(&mut ctx, Message::new(), None);
+        req.on_send_ack(&mut ctx, eid);
+        let _ = rx.try_recv().expect("facade should have been sent a reply !");
+        req.on_recv_ready(&mut ctx, eid);
+        ctx_sensor.borrow().assert_one_recv_from(eid);
+
+        req.remove_pipe(&mut ctx, eid_o);
+
+        let good_request_id = (req.inner.req_id_seq) | 0x80000000;
+        let mut body: Vec<u8> = vec![0, 0, 0, 0, 4, 2, 1];
+
+        BigEndian::write_u32(&mut body[0..4], good_request_id);
+
+        req.on_recv_ack(&mut ctx, eid, Message::from_body(body));
+        assert!(req.is_recv_ready());
+
+        req.recv(&mut ctx, None);
+        let reply = rx.try_recv().expect("facade should have been sent a reply !");
+        let is_reply_ok = match reply {
+            Reply::Recv(msg) => msg.get_body() == &[4, 2, 1],
+            _ => false
+        };
+        assert!(is_reply_ok);
+    }
+
     #[test]
     fn when_in_regular_mode_send_will_append_request_id_to_the_header() {
         let (tx, _) = mpsc::channel();
@@ -999,4 +1600,157 @@ mod tests {
         assert_eq!(4, app_msg.get_header().len());
         assert_eq!(2, app_msg.get_body().len());
      }
-}
\ No newline at end of file
+
+    fn sent_req_id(sensor: &TestContextSensor, index: usize) -> u32 {
+        let &(_, ref msg) = &sensor.get_send_calls()[index];
+
+        BigEndian::read_u32(msg.get_header())
+    }
+
+    fn reply_with_req_id(req_id: u32, body: u8) -> Message {
+        let mut raw = vec![0; 4];
+
+        BigEndian::write_u32(&mut raw, req_id);
+        raw.push(body);
+
+        Message::from_body(raw)
+    }
+
+    #[test]
+    fn context_requests_are_sent_to_ready_pipes_and_acked_to_their_context() {
+        let (tx, _) = mpsc::channel();
+        let mut req = Req::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid1 = EndpointId::from(1);
+        let eid2 = EndpointId::from(2);
+        let (ctx_tx1, ctx_rx1) = mpsc::channel();
+        let (ctx_tx2, ctx_rx2) = mpsc::channel();
+        let cid1 = ContextId::from(1);
+        let cid2 = ContextId::from(2);
+
+        req.add_pipe(&mut ctx, eid1, new_test_pipe(eid1));
+        req.add_pipe(&mut ctx, eid2, new_test_pipe(eid2));
+        req.add_context(&mut ctx, cid1, ctx_tx1).unwrap();
+        req.add_context(&mut ctx, cid2, ctx_tx2).unwrap();
+        req.context_send(&mut ctx, cid1, Message::from_body(vec![1]), None);
+        req.context_send(&mut ctx, cid2, Message::from_body(vec![2]), None);
+        ctx_sensor.borrow().assert_no_send_call();
+
+        req.on_send_ready(&mut ctx, eid1);
+        req.on_send_ready(&mut ctx, eid2);
+        {
+            let sensor = ctx_sensor.borrow();
+            sensor.assert_send_to(eid1, 1);
+            sensor.assert_send_to(eid2, 1);
+            assert!(sent_req_id(&sensor, 0) != sent_req_id(&sensor, 1));
+        }
+
+        req.on_send_ack(&mut ctx, eid2);
+        assert!(ctx_rx1.try_recv().is_err());
+        match ctx_rx2.try_recv() {
+            Ok(Reply::Send) => {},
+            _ => panic!("context should have been sent a Send reply !")
+        }
+    }
+
+    #[test]
+    fn context_replies_are_matched_by_request_id() {
+        let (tx, _) = mpsc::channel();
+        let mut req = Req::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(1);
+        let (ctx_tx1, ctx_rx1) = mpsc::channel();
+        let (ctx_tx2, ctx_rx2) = mpsc::channel();
+        let cid1 = ContextId::from(1);
+        let cid2 = ContextId::from(2);
+
+        req.add_pipe(&mut ctx, eid, new_test_pipe(eid));
+        req.add_context(&mut ctx, cid1, ctx_tx1).unwrap();
+        req.add_context(&mut ctx, cid2, ctx_tx2).unwrap();
+        req.on_send_ready(&mut ctx, eid);
+        req.context_send(&mut ctx, cid1, Message::from_body(vec![1]), None);
+        req.on_send_ack(&mut ctx, eid);
+        req.on_send_ready(&mut ctx, eid);
+        req.context_send(&mut ctx, cid2, Message::from_body(vec![2]), None);
+        req.on_send_ack(&mut ctx, eid);
+
+        let req_id1 = sent_req_id(&ctx_sensor.borrow(), 0);
+        let req_id2 = sent_req_id(&ctx_sensor.borrow(), 1);
+
+        req.context_recv(&mut ctx, cid1, None);
+        req.on_recv_ready(&mut ctx, eid);
+        req.on_recv_ack(&mut ctx, eid, reply_with_req_id(req_id2, 20));
+        req.on_recv_ready(&mut ctx, eid);
+        req.on_recv_ack(&mut ctx, eid, reply_with_req_id(req_id1, 10));
+        ctx_sensor.borrow().assert_recv_from(eid, 2);
+
+        let _ = ctx_rx1.try_recv();
+        match ctx_rx1.try_recv() {
+            Ok(Reply::Recv(msg)) => assert_eq!(&[10], msg.get_body()),
+            _ => panic!("context should have been sent a Recv reply !")
+        }
+
+        let _ = ctx_rx2.try_recv();
+        req.context_recv(&mut ctx, cid2, None);
+        match ctx_rx2.try_recv() {
+            Ok(Reply::Recv(msg)) => assert_eq!(&[20], msg.get_body()),
+            _ => panic!("context should have been sent a Recv reply !")
+        }
+    }
+
+    #[test]
+    fn context_reply_read_by_the_socket_is_routed_to_the_context() {
+        let (tx, rx) = mpsc::channel();
+        let mut req = Req::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(1);
+        let (ctx_tx, ctx_rx) = mpsc::channel();
+        let cid = ContextId::from(1);
+
+        req.add_pipe(&mut ctx, eid, new_test_pipe(eid));
+        req.add_context(&mut ctx, cid, ctx_tx).unwrap();
+        req.on_send_ready(&mut ctx, eid);
+        req.context_send(&mut ctx, cid, Message::from_body(vec![1]), None);
+        req.on_send_ack(&mut ctx, eid);
+        req.on_send_ready(&mut ctx, eid);
+        req.send(&mut ctx, Message::from_body(vec![2]), None);
+        req.on_send_ack(&mut ctx, eid);
+
+        let ctx_req_id = sent_req_id(&ctx_sensor.borrow(), 0);
+        let req_id = sent_req_id(&ctx_sensor.borrow(), 1);
+
+        req.recv(&mut ctx, None);
+        req.on_recv_ack(&mut ctx, eid, reply_with_req_id(ctx_req_id, 10));
+        req.on_recv_ready(&mut ctx, eid);
+        req.on_recv_ack(&mut ctx, eid, reply_with_req_id(req_id, 20));
+        ctx_sensor.borrow().assert_recv_from(eid, 2);
+
+        let _ = rx.try_recv();
+        match rx.try_recv() {
+            Ok(Reply::Recv(msg)) => assert_eq!(&[20], msg.get_body()),
+            _ => panic!("facade should have been sent a Recv reply !")
+        }
+
+        let _ = ctx_rx.try_recv();
+        req.context_recv(&mut ctx, cid, None);
+        match ctx_rx.try_recv() {
+            Ok(Reply::Recv(msg)) => assert_eq!(&[10], msg.get_body()),
+            _ => panic!("context should have been sent a Recv reply !")
+        }
+    }
+
+    #[test]
+    fn raw_req_does_not_support_contexts() {
+        let (tx, _) = mpsc::channel();
+        let mut req = Req::from(tx);
+        let mut ctx = TestContext::with_sensor(Rc::new(RefCell::new(TestContextSensor::default())));
+        let (ctx_tx, _) = mpsc::channel();
+
+        req.set_raw();
+
+        assert!(req.add_context(&mut ctx, ContextId::from(1), ctx_tx).is_err());
+    }
+}
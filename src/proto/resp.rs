@@ -7,14 +7,17 @@
 use std::collections::HashSet;
 use std::rc::Rc;
 use std::sync::mpsc::Sender;
+use std::io;
 
 use byteorder::*;
 
 use core::{EndpointId, Message};
 use core::socket::{Protocol, Reply};
+use core::config::ConfigOption;
 use core::endpoint::Pipe;
 use core::context::{Context, Event};
 use super::priolist::Priolist;
+use super::raw::Rawable;
 use super::pipes::PipeCollection;
 use super::{Timeout, SURVEYOR, RESPONDENT};
 use super::policy::fair_queue;
@@ -34,6 +37,13 @@ enum State {
     SendOnHold(EndpointId, Rc<Message>, Timeout)
 }
 
+/// Outcome of extracting the backtrace of a raw survey.
+enum Decoded {
+    Msg(Message),
+    TtlExceeded,
+    Malformed
+}
+
 struct Inner {
     reply_tx: Sender<Reply>,
     pipes: PipeCollection,
@@ -82,6 +92,12 @@ impl From<Sender<Reply>> for Respondent {
     }
 }
 
+impl Rawable for Respondent {
+    fn set_raw(&mut self) {
+        self.inner.is_device_item = true;
+    }
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* Protocol                                                                  */
@@ -136,10 +152,10 @@ impl Protocol for Respondent {
         self.apply(ctx, |s, ctx, inner| s.recv(ctx, inner, timeout))
     }
     fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, raw_msg: Message) {
-        if let Some(msg) = self.inner.raw_msg_to_msg(raw_msg, eid) {
-            self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg))
-        } else {
-            self.inner.on_recv_ack_malformed(ctx)
+        match self.inner.raw_msg_to_msg(ctx, raw_msg, eid) {
+            Decoded::Msg(msg)    => self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg)),
+            Decoded::TtlExceeded => self.apply(ctx, |s, ctx, inner| s.on_recv_dropped(ctx, inner, eid)),
+            Decoded::Malformed   => self.inner.on_recv_ack_malformed(ctx)
         }
     }
     fn on_recv_timeout(&mut self, ctx: &mut Context) {
@@ -151,6 +167,12 @@ impl Protocol for Respondent {
     fn on_recv_not_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
         self.apply(ctx, |s, ctx, inner| s.on_recv_not_ready(ctx, inner, eid))
     }
+    fn set_option(&mut self, opt: ConfigOption) -> io::Result<()> {
+        match opt {
+            ConfigOption::MaxTtl(ttl) => self.inner.set_max_ttl(ttl),
+            _ => Err(invalid_input_io_error("option not supported"))
+        }
+    }
     fn on_device_plugged(&mut self, _: &mut Context) {
         self.inner.is_device_item = true;
     }
@@ -168,6 +190,13 @@ impl Protocol for Respondent {
             false
         }
     }
+    fn has_pending_send(&self) -> bool {
+        match self.state {
+            Some(State::Sending(..))    |
+            Some(State::SendOnHold(..)) => true,
+            _ => false
+        }
+    }
     fn close(&mut self, ctx: &mut Context) {
         self.inner.close(ctx)
     }
@@ -315,6 +344,18 @@ impl State {
             any => any
         }
     }
+    fn on_recv_dropped(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
+        match self {
+            State::Receiving(id, timeout) => {
+                if id == eid {
+                    State::Idle.recv(ctx, inner, timeout)
+                } else {
+                    State::Receiving(id, timeout)
+                }
+            },
+            any => any
+        }
+    }
     fn on_recv_timeout(self, _: &mut Context, inner: &mut Inner) -> State {
         inner.on_recv_timeout();
 
@@ -440,7 +481,7 @@ impl Inner {
         self.fq.peek()
     }
 
-    fn raw_msg_to_msg(&self, raw_msg: Message, eid: EndpointId) -> Option<Message> {
+    fn raw_msg_to_msg(&self, ctx: &Context, raw_msg: Message, eid: EndpointId) -> Decoded {
         let (mut header, mut body) = raw_msg.split();
         let mut hops = 0;
         let mut eid_bytes: [u8; 4] = [0; 4];
@@ -453,12 +494,13 @@ impl Inner {
 
         loop {
             if hops >= self.ttl {
-                return None;
+                info!("[{:?}] message dropped after {} hops, max ttl exceeded", ctx, hops);
+                return Decoded::TtlExceeded;
             }
             hops += 1;
 
             if body.len() < 4 {
-                return None;
+                return Decoded::Malformed;
             }
 
             let tail = body.split_off(4);
@@ -467,7 +509,7 @@ impl Inner {
 
             let position = header.len() - 4;
             if header[position] & 0x80 != 0 {
-                return Some(Message::from_header_and_body(header, tail));
+                return Decoded::Msg(Message::from_header_and_body(header, tail));
             }
             body = tail;
         }
@@ -502,7 +544,101 @@ impl Inner {
     fn clear_backtrace(&mut self) {
         self.backtrace.clear();
     }
+    fn set_max_ttl(&mut self, ttl: u8) -> io::Result<()> {
+        if ttl == 0 {
+            Err(invalid_input_io_error("max ttl must be between 1 and 255"))
+        } else {
+            Ok(self.ttl = ttl)
+        }
+    }
     fn close(&mut self, ctx: &mut Context) {
         self.pipes.close_all(ctx)
     }
 }
+
+/*****************************************************************************/
+/*                                                                           */
+/* tests                                                                     */
+/*                                                                           */
+/*****************************************************************************/
+
+#[cfg(test)]
+mod tests {
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::mpsc;
+
+    use byteorder::*;
+
+    use core::{EndpointId, Message};
+    use core::socket::{Protocol, Reply};
+    use core::config::ConfigOption;
+    use core::tests::*;
+
+    use super::*;
+
+    fn survey_through_one_device() -> Message {
+        let survey_id = 666 | 0x80000000;
+        let mut body: Vec<u8> = vec![0, 2, 4, 2, 0, 0, 0, 0, 4, 2, 1];
+
+        BigEndian::write_u32(&mut body[4..8], survey_id);
+
+        Message::from_body(body)
+    }
+
+    #[test]
+    fn max_ttl_must_be_between_1_and_255() {
+        let (tx, _) = mpsc::channel();
+        let mut resp = Respondent::from(tx);
+
+        assert!(resp.set_option(ConfigOption::MaxTtl(0)).is_err());
+        assert!(resp.set_option(ConfigOption::MaxTtl(1)).is_ok());
+        assert!(resp.set_option(ConfigOption::MaxTtl(255)).is_ok());
+    }
+
+    #[test]
+    fn surveys_that_went_through_more_devices_than_max_ttl_are_dropped() {
+        let (tx, rx) = mpsc::channel();
+        let mut resp = Respondent::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(0);
+        let pipe = new_test_pipe(eid);
+
+        resp.set_option(ConfigOption::MaxTtl(1)).unwrap();
+        resp.add_pipe(&mut ctx, eid, pipe);
+        resp.on_recv_ready(&mut ctx, eid);
+        resp.recv(&mut ctx, None);
+        resp.on_recv_ack(&mut ctx, eid, survey_through_one_device());
+
+        assert!(rx.try_recv().is_err());
+        ctx_sensor.borrow().assert_one_recv_from(eid);
+
+        resp.on_recv_ready(&mut ctx, eid);
+
+        assert!(rx.try_recv().is_err());
+        ctx_sensor.borrow().assert_recv_from(eid, 2);
+    }
+
+    #[test]
+    fn surveys_that_went_through_less_devices_than_max_ttl_are_received() {
+        let (tx, rx) = mpsc::channel();
+        let mut resp = Respondent::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(0);
+        let pipe = new_test_pipe(eid);
+
+        resp.set_option(ConfigOption::MaxTtl(2)).unwrap();
+        resp.add_pipe(&mut ctx, eid, pipe);
+        resp.on_recv_ready(&mut ctx, eid);
+        resp.recv(&mut ctx, None);
+        resp.on_recv_ack(&mut ctx, eid, survey_through_one_device());
+
+        match rx.try_recv() {
+            Ok(Reply::Recv(msg)) => assert_eq!(&[4, 2, 1], msg.get_body()),
+            _ => panic!("facade should have been sent a Recv reply !")
+        }
+    }
+}
@@ -4,7 +4,6 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::collections::HashSet;
 use std::rc::Rc;
 use std::sync::mpsc::Sender;
 use std::io;
@@ -15,6 +14,7 @@ use core::config::ConfigOption;
 use core::endpoint::Pipe;
 use core::context::Context;
 use super::priolist::Priolist;
+use super::trie::Trie;
 use super::pipes::PipeCollection;
 use super::{Timeout, PUB, SUB};
 use super::policy::fair_queue;
@@ -35,7 +35,7 @@ struct Inner {
     reply_tx: Sender<Reply>,
     pipes: PipeCollection,
     fq: Priolist,
-    subscriptions: HashSet<Vec<u8>>
+    subscriptions: Trie
 }
 
 /*****************************************************************************/
@@ -71,7 +71,7 @@ impl From<Sender<Reply>> for Sub {
                 reply_tx: tx,
                 pipes: PipeCollection::new(),
                 fq: Priolist::new(),
-                subscriptions: HashSet::new()
+                subscriptions: Trie::new()
             },
             state: Some(State::Idle)
         }
@@ -140,13 +140,21 @@ impl Protocol for Sub {
     fn is_recv_ready(&self) -> bool {
         self.inner.is_recv_ready()
     }
+    fn is_duplex(&self) -> bool {
+        true
+    }
     fn set_option(&mut self, opt: ConfigOption) -> io::Result<()> {
         match opt {
-            ConfigOption::Subscribe(x)   => Ok(self.inner.subscribe(x)),
-            ConfigOption::Unsubscribe(x) => Ok(self.inner.unsubscribe(x)),
+            ConfigOption::Subscribe(x)        => Ok(self.inner.subscribe(x.into_bytes())),
+            ConfigOption::Unsubscribe(x)      => Ok(self.inner.unsubscribe(x.into_bytes())),
+            ConfigOption::SubscribeBytes(x)   => Ok(self.inner.subscribe(x)),
+            ConfigOption::UnsubscribeBytes(x) => Ok(self.inner.unsubscribe(x)),
             _ => Err(invalid_input_io_error("option not supported"))
         }
     }
+    fn subscriptions(&self) -> io::Result<Vec<Vec<u8>>> {
+        Ok(self.inner.subscriptions.topics())
+    }
     fn close(&mut self, ctx: &mut Context) {
         self.inner.close(ctx)
     }
@@ -298,18 +306,16 @@ impl Inner {
         self.fq.peek()
     }
 
-    fn subscribe(&mut self, subscription :String) {
-        self.subscriptions.insert(subscription.into_bytes());
+    fn subscribe(&mut self, subscription: Vec<u8>) {
+        self.subscriptions.insert(&subscription);
     }
 
-    fn unsubscribe(&mut self, subscription :String) {
-        self.subscriptions.remove(&subscription.into_bytes());
+    fn unsubscribe(&mut self, subscription: Vec<u8>) {
+        self.subscriptions.remove(&subscription);
     }
 
     fn accept(&self, msg: &Message) -> bool {
-        let payload = msg.get_body();
-        
-        self.subscriptions.iter().any(|s| payload.starts_with(s))
+        self.subscriptions.matches(msg.get_body())
     }
     fn close(&mut self, ctx: &mut Context) {
         self.pipes.close_all(ctx)
@@ -392,6 +398,54 @@ mod tests {
         assert_eq!(Event::CanRecv(true), raised_evts[2]);
     }
 
+    #[test]
+    fn binary_subscriptions_match_nul_and_high_bit_prefixes() {
+        let (tx, rx) = mpsc::channel();
+        let mut sub = Sub::from(tx);
+        let _ = sub.set_option(ConfigOption::SubscribeBytes(vec![0x00, 0xFF, 0x80]));
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(0);
+        let pipe = new_test_pipe(eid);
+
+        sub.add_pipe(&mut ctx, eid, pipe);
+        sub.on_recv_ready(&mut ctx, eid);
+        sub.recv(&mut ctx, None);
+        sub.on_recv_ack(&mut ctx, eid, Message::from_body(vec![0x00, 0xFF, 0x81, 1]));
+        sub.on_recv_ready(&mut ctx, eid);
+        sub.on_recv_ack(&mut ctx, eid, Message::from_body(vec![0x00, 0xFF, 0x80, 2]));
+
+        let reply = rx.recv().expect("facade should have been sent a reply !");
+        match reply {
+            Reply::Recv(msg) => assert_eq!(&[0x00, 0xFF, 0x80, 2], msg.get_body()),
+            _ => panic!("expected a recv reply")
+        }
+        assert!(rx.try_recv().is_err());
+
+        let _ = sub.set_option(ConfigOption::UnsubscribeBytes(vec![0x00, 0xFF, 0x80]));
+        assert!(!sub.inner.accept(&Message::from_body(vec![0x00, 0xFF, 0x80, 3])));
+    }
+
+    #[test]
+    fn topic_subscribed_twice_stays_active_until_unsubscribed_twice() {
+        let (tx, _) = mpsc::channel();
+        let mut sub = Sub::from(tx);
+        let msg = Message::from_body(vec![65, 66, 67]);
+
+        sub.set_option(ConfigOption::Subscribe(String::from("AB"))).unwrap();
+        sub.set_option(ConfigOption::SubscribeBytes(vec![65, 66])).unwrap();
+        sub.set_option(ConfigOption::Subscribe(String::from("B"))).unwrap();
+        assert_eq!(vec![vec![65, 66], vec![66]], sub.subscriptions().unwrap());
+
+        sub.set_option(ConfigOption::Unsubscribe(String::from("AB"))).unwrap();
+        assert!(sub.inner.accept(&msg));
+        assert_eq!(vec![vec![65, 66], vec![66]], sub.subscriptions().unwrap());
+
+        sub.set_option(ConfigOption::UnsubscribeBytes(vec![65, 66])).unwrap();
+        assert!(!sub.inner.accept(&msg));
+        assert_eq!(vec![vec![66]], sub.subscriptions().unwrap());
+    }
+
     /*//#[test]
     fn when_recv_ack_event_is_raised_if_there_is_another_pipe_ready() {
         let (tx, _) = mpsc::channel();
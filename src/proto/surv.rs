@@ -4,7 +4,7 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::rc::Rc;
 use std::sync::mpsc::Sender;
 use std::time::Duration;
@@ -14,12 +14,13 @@ use time;
 
 use byteorder::*;
 
-use core::{EndpointId, Message};
+use core::{BuildIdHasher, ContextId, EndpointId, Message};
 use core::socket::{Protocol, Reply};
 use core::config::ConfigOption;
 use core::endpoint::Pipe;
 use core::context::{Context, Schedulable};
 use super::priolist::Priolist;
+use super::raw::Rawable;
 use super::pipes::PipeCollection;
 use super::{Timeout, SURVEYOR, RESPONDENT};
 use super::policy::{broadcast, fair_queue};
@@ -44,7 +45,11 @@ struct Inner {
     fq: Priolist,
     survey_id_seq: u32,
     is_device_item: bool,
-    deadline: Duration
+    deadline: Duration,
+    responses: VecDeque<Message>,
+    contexts: HashMap<ContextId, SurveyContext, BuildIdHasher>,
+    ctx_reading: HashSet<EndpointId>,
+    collect: Option<CollectedSurvey>
 }
 
 struct PendingSurvey {
@@ -52,6 +57,22 @@ struct PendingSurvey {
     timeout: Timeout
 }
 
+/// Survey whose responses are all handed to the user at once, when the deadline is reached.
+struct CollectedSurvey {
+    id: u32,
+    responses: Vec<Message>,
+    surveyed: usize
+}
+
+/// Survey state of a context, responses are queued until the context receives them
+/// or the survey deadline is reached.
+struct SurveyContext {
+    reply_tx: Sender<Reply>,
+    survey: Option<PendingSurvey>,
+    responses: VecDeque<Message>,
+    recv: Option<Timeout>
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* Surveyor                                                                      */
@@ -79,6 +100,19 @@ impl Surveyor {
         }
     }
 
+    fn apply_on_contexts<F>(&mut self, ctx: &mut Context, f: F) where F : FnOnce(&mut Context, &mut Inner) {
+        let was_send_ready = self.is_send_ready();
+        let was_recv_ready = self.is_recv_ready();
+
+        f(ctx, &mut self.inner);
+
+        let is_send_ready = self.is_send_ready();
+        let is_recv_ready = self.is_recv_ready();
+
+        ctx.check_send_ready_change(was_send_ready, is_send_ready);
+        ctx.check_recv_ready_change(was_recv_ready, is_recv_ready);
+    }
+
 }
 
 impl From<Sender<Reply>> for Surveyor {
@@ -90,6 +124,12 @@ impl From<Sender<Reply>> for Surveyor {
     }
 }
 
+impl Rawable for Surveyor {
+    fn set_raw(&mut self) {
+        self.inner.is_device_item = true;
+    }
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* Protocol                                                                  */
@@ -112,6 +152,7 @@ impl Protocol for Surveyor {
 
         if pipe.is_some() {
             self.apply(ctx, |s, ctx, inner| s.on_pipe_removed(ctx, inner, eid));
+            self.apply_on_contexts(ctx, |ctx, inner| inner.on_context_pipe_removed(ctx, eid));
         }
 
         ctx.check_send_ready_change(was_send_ready, is_send_ready);
@@ -140,17 +181,29 @@ impl Protocol for Surveyor {
         self.apply(ctx, |s, ctx, inner| s.recv(ctx, inner, timeout))
     }
     fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, raw_msg: Message) {
-        if let Some((msg, survey_id)) = self.inner.raw_msg_to_msg(raw_msg) {
-            self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg, survey_id))
+        if self.inner.ctx_reading.remove(&eid) {
+            if let Some((msg, survey_id)) = self.inner.raw_msg_to_msg(raw_msg) {
+                if let Err(msg) = self.inner.on_context_response(ctx, msg, survey_id) {
+                    self.apply(ctx, |s, ctx, inner| s.on_response_read(ctx, inner, msg, survey_id))
+                }
+            }
+        } else if let Some((msg, survey_id)) = self.inner.raw_msg_to_msg(raw_msg) {
+            match self.inner.on_context_response(ctx, msg, survey_id) {
+                Ok(()) => self.apply(ctx, |s, ctx, inner| s.on_recv_skipped(ctx, inner, eid)),
+                Err(msg) => self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg, survey_id))
+            }
         } else {
             self.inner.on_recv_ack_malformed(ctx)
         }
+
+        self.apply_on_contexts(ctx, |ctx, inner| inner.recv_for_contexts(ctx))
     }
     fn on_recv_timeout(&mut self, ctx: &mut Context) {
         self.apply(ctx, |s, ctx, inner| s.on_recv_timeout(ctx, inner))
     }
     fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_recv_ready(ctx, inner, eid))
+        self.apply(ctx, |s, ctx, inner| s.on_recv_ready(ctx, inner, eid));
+        self.apply_on_contexts(ctx, |ctx, inner| inner.recv_for_contexts(ctx))
     }
     fn on_recv_not_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
         self.apply(ctx, |s, ctx, inner| s.on_recv_not_ready(ctx, inner, eid))
@@ -162,8 +215,11 @@ impl Protocol for Surveyor {
         }
     }
     fn on_timer_tick(&mut self, ctx: &mut Context, task: Schedulable) {
-        if let Schedulable::SurveyCancel = task {
-            self.apply(ctx, |s, ctx, inner| s.on_survey_timeout(ctx, inner))
+        match task {
+            Schedulable::SurveyCancel => self.apply(ctx, |s, ctx, inner| s.on_survey_timeout(ctx, inner)),
+            Schedulable::SurveyCollect => self.inner.on_survey_collected(),
+            Schedulable::ContextSurveyCancel(cid) => self.inner.on_context_survey_timeout(cid),
+            _ => {}
         }
     }
     fn on_device_plugged(&mut self, _: &mut Context) {
@@ -183,6 +239,38 @@ impl Protocol for Surveyor {
             false
         }
     }
+    fn survey(&mut self, ctx: &mut Context, msg: Message) -> io::Result<()> {
+        if self.inner.is_device_item {
+            return Err(invalid_input_io_error("survey not supported by raw sockets"));
+        }
+
+        let was_recv_ready = self.is_recv_ready();
+        let res = self.inner.survey(ctx, msg);
+        let is_recv_ready = self.is_recv_ready();
+
+        ctx.check_recv_ready_change(was_recv_ready, is_recv_ready);
+
+        res
+    }
+    fn add_context(&mut self, _: &mut Context, cid: ContextId, tx: Sender<Reply>) -> io::Result<()> {
+        if self.inner.is_device_item {
+            Err(invalid_input_io_error("contexts not supported by raw sockets"))
+        } else {
+            Ok(self.inner.add_context(cid, tx))
+        }
+    }
+    fn remove_context(&mut self, ctx: &mut Context, cid: ContextId) {
+        self.inner.remove_context(ctx, cid)
+    }
+    fn context_send(&mut self, ctx: &mut Context, cid: ContextId, msg: Message, timeout: Timeout) {
+        self.apply_on_contexts(ctx, |ctx, inner| inner.context_send(ctx, cid, msg, timeout))
+    }
+    fn context_recv(&mut self, ctx: &mut Context, cid: ContextId, timeout: Timeout) {
+        self.inner.context_recv(ctx, cid, timeout)
+    }
+    fn on_context_recv_timeout(&mut self, _: &mut Context, cid: ContextId) {
+        self.inner.on_context_recv_timeout(cid)
+    }
     fn close(&mut self, ctx: &mut Context) {
         self.inner.close(ctx)
     }
@@ -272,7 +360,10 @@ impl State {
         }
     }
     fn recv_reply_for(self, ctx: &mut Context, inner: &mut Inner, timeout: Timeout, p: PendingSurvey) -> State {
-        if let Some(eid) = inner.recv(ctx) {
+        if let Some(msg) = inner.responses.pop_front() {
+            inner.on_recv_ack(ctx, timeout, msg);
+            State::Active(p)
+        } else if let Some(eid) = inner.recv(ctx) {
             State::Receiving(eid, Some(p), timeout)
         } else {
             State::RecvOnHold(Some(p), timeout)
@@ -303,6 +394,43 @@ impl State {
             any => any
         }
     }
+    fn on_response_read(self, ctx: &mut Context, inner: &mut Inner, msg: Message, survey_id: u32) -> State {
+        match self {
+            State::RecvOnHold(Some(p), timeout) => {
+                if p.id == survey_id {
+                    inner.on_recv_ack(ctx, timeout, msg);
+                    State::Active(p)
+                } else {
+                    State::RecvOnHold(Some(p), timeout)
+                }
+            },
+            State::Active(p) => {
+                if p.id == survey_id {
+                    inner.responses.push_back(msg);
+                }
+                State::Active(p)
+            },
+            State::Receiving(id, Some(p), timeout) => {
+                if p.id == survey_id {
+                    inner.responses.push_back(msg);
+                }
+                State::Receiving(id, Some(p), timeout)
+            },
+            any => any
+        }
+    }
+    fn on_recv_skipped(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
+        match self {
+            State::Receiving(id, Some(p), timeout) => {
+                if id == eid {
+                    State::Idle.recv_reply_for(ctx, inner, timeout, p)
+                } else {
+                    State::Receiving(id, Some(p), timeout)
+                }
+            },
+            any => any
+        }
+    }
     fn on_recv_timeout(self, _: &mut Context, inner: &mut Inner) -> State {
         inner.on_recv_timeout();
 
@@ -327,7 +455,9 @@ impl State {
         inner.on_recv_not_ready(eid);
         self
     }
-    fn on_survey_timeout(self, _: &mut Context, _: &mut Inner) -> State {
+    fn on_survey_timeout(self, _: &mut Context, inner: &mut Inner) -> State {
+        inner.responses.clear();
+
         if let State::Active(_) = self {
             State::Idle
         } else {
@@ -338,7 +468,7 @@ impl State {
         if inner.is_device_item {
             inner.is_recv_ready()
         } else if let State::Active(..) = *self {
-            inner.is_recv_ready()
+            inner.is_recv_ready() || !inner.responses.is_empty()
         } else {
             false
         }
@@ -361,7 +491,11 @@ impl Inner {
             fq: Priolist::new(),
             survey_id_seq: time::get_time().nsec as u32,
             is_device_item: false,
-            deadline: Duration::from_secs(1)
+            deadline: Duration::from_secs(1),
+            responses: VecDeque::new(),
+            contexts: HashMap::default(),
+            ctx_reading: HashSet::new(),
+            collect: None
         }
     }
     fn add_pipe(&mut self, eid: EndpointId, pipe: Pipe) {
@@ -374,6 +508,7 @@ impl Inner {
         self.pipes.remove(&eid)
     }
     fn send(&mut self, ctx: &mut Context, msg: Rc<Message>, timeout: Timeout) -> PendingSurvey {
+        self.responses.clear();
         broadcast::send_to_all(&mut self.bc, &mut self.pipes, ctx, msg);
 
         let _ = self.reply_tx.send(Reply::Send);
@@ -470,6 +605,176 @@ impl Inner {
     }
 }
 
+/*****************************************************************************/
+/*                                                                           */
+/* Collect                                                                   */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Inner {
+    fn survey(&mut self, ctx: &mut Context, msg: Message) -> io::Result<()> {
+        if self.collect.is_some() {
+            return Err(other_io_error("Can't survey: a survey is already being collected"));
+        }
+
+        let survey_id = self.next_survey_id();
+        let raw_msg = encode(msg, survey_id);
+        let surveyed = self.bc.len();
+
+        try!(ctx.schedule(Schedulable::SurveyCollect, self.deadline));
+        broadcast::send_to_all(&mut self.bc, &mut self.pipes, ctx, Rc::new(raw_msg));
+
+        self.collect = Some(CollectedSurvey {
+            id: survey_id,
+            responses: Vec::new(),
+            surveyed: surveyed
+        });
+        self.recv_for_contexts(ctx);
+
+        Ok(())
+    }
+    fn on_survey_collected(&mut self) {
+        if let Some(collect) = self.collect.take() {
+            let _ = self.reply_tx.send(Reply::Survey(collect.responses, collect.surveyed));
+        }
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Contexts                                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+impl SurveyContext {
+    fn new(tx: Sender<Reply>) -> SurveyContext {
+        SurveyContext {
+            reply_tx: tx,
+            survey: None,
+            responses: VecDeque::new(),
+            recv: None
+        }
+    }
+    fn is_surveying(&self, survey_id: u32) -> bool {
+        self.survey.as_ref().map_or(false, |p| p.id == survey_id)
+    }
+    fn cancel(&mut self, ctx: &mut Context) {
+        if let Some(PendingSurvey { timeout: Some(sched), .. }) = self.survey.take() {
+            ctx.cancel(sched);
+        }
+        self.responses.clear();
+    }
+    fn on_response(&mut self, ctx: &mut Context, msg: Message) {
+        if let Some(timeout) = self.recv.take() {
+            let _ = self.reply_tx.send(Reply::Recv(msg));
+            if let Some(sched) = timeout {
+                ctx.cancel(sched);
+            }
+        } else {
+            self.responses.push_back(msg);
+        }
+    }
+}
+
+impl Inner {
+    fn add_context(&mut self, cid: ContextId, tx: Sender<Reply>) {
+        self.contexts.insert(cid, SurveyContext::new(tx));
+    }
+    fn remove_context(&mut self, ctx: &mut Context, cid: ContextId) {
+        if let Some(mut c) = self.contexts.remove(&cid) {
+            c.cancel(ctx);
+            if let Some(Some(sched)) = c.recv.take() {
+                ctx.cancel(sched);
+            }
+        }
+    }
+    fn has_context_survey(&self) -> bool {
+        self.contexts.values().any(|c| c.survey.is_some())
+    }
+    fn context_send(&mut self, ctx: &mut Context, cid: ContextId, msg: Message, timeout: Timeout) {
+        if !self.contexts.contains_key(&cid) {
+            return;
+        }
+
+        let survey_id = self.next_survey_id();
+        let raw_msg = encode(msg, survey_id);
+
+        broadcast::send_to_all(&mut self.bc, &mut self.pipes, ctx, Rc::new(raw_msg));
+
+        let deadline = ctx.schedule(Schedulable::ContextSurveyCancel(cid), self.deadline).ok();
+        if let Some(c) = self.contexts.get_mut(&cid) {
+            c.cancel(ctx);
+            c.survey = Some(PendingSurvey { id: survey_id, timeout: deadline });
+            let _ = c.reply_tx.send(Reply::Send);
+        }
+        if let Some(sched) = timeout {
+            ctx.cancel(sched);
+        }
+
+        self.recv_for_contexts(ctx);
+    }
+    fn on_context_survey_timeout(&mut self, cid: ContextId) {
+        if let Some(c) = self.contexts.get_mut(&cid) {
+            c.survey = None;
+            c.responses.clear();
+            if c.recv.take().is_some() {
+                let error = timedout_io_error("Survey timed out");
+                let _ = c.reply_tx.send(Reply::Err(error));
+            }
+        }
+    }
+    fn on_context_pipe_removed(&mut self, ctx: &mut Context, eid: EndpointId) {
+        self.ctx_reading.remove(&eid);
+        self.recv_for_contexts(ctx);
+    }
+
+    fn context_recv(&mut self, ctx: &mut Context, cid: ContextId, timeout: Timeout) {
+        if let Some(c) = self.contexts.get_mut(&cid) {
+            if let Some(msg) = c.responses.pop_front() {
+                let _ = c.reply_tx.send(Reply::Recv(msg));
+            } else if c.survey.is_some() {
+                c.recv = Some(timeout);
+                return;
+            } else {
+                let error = other_io_error("Can't recv: no active survey");
+                let _ = c.reply_tx.send(Reply::Err(error));
+            }
+        }
+        if let Some(sched) = timeout {
+            ctx.cancel(sched);
+        }
+    }
+    fn recv_for_contexts(&mut self, ctx: &mut Context) {
+        if !self.has_context_survey() && self.collect.is_none() {
+            return;
+        }
+
+        while let Some(eid) = self.recv(ctx) {
+            self.ctx_reading.insert(eid);
+        }
+    }
+    fn on_context_response(&mut self, ctx: &mut Context, msg: Message, survey_id: u32) -> Result<(), Message> {
+        if let Some(ref mut collect) = self.collect {
+            if collect.id == survey_id {
+                return Ok(collect.responses.push(msg));
+            }
+        }
+
+        match self.contexts.values_mut().find(|c| c.is_surveying(survey_id)) {
+            Some(c) => Ok(c.on_response(ctx, msg)),
+            None => Err(msg)
+        }
+    }
+    fn on_context_recv_timeout(&mut self, cid: ContextId) {
+        if let Some(c) = self.contexts.get_mut(&cid) {
+            if c.recv.take().is_some() {
+                let error = timedout_io_error("Recv timed out");
+                let _ = c.reply_tx.send(Reply::Err(error));
+            }
+        }
+    }
+}
+
 fn encode(msg: Message, survey_id: u32) -> Message {
     let mut raw_msg = msg;
     let mut survey_id_bytes: [u8; 4] = [0; 4];
@@ -514,9 +819,9 @@ mod tests {
 
     use byteorder::*;
 
-    use core::{EndpointId, Message, Scheduled};
+    use core::{ContextId, EndpointId, Message, Scheduled};
     use core::socket::{Protocol, Reply};
-    use core::context::{Event};
+    use core::context::{Event, Schedulable};
     use core::tests::*;
 
     use super::*;
@@ -799,4 +1104,158 @@ mod tests {
     }
 
     // test CanRecv events ...
-}
\ No newline at end of file
+
+    fn sent_survey_id(sensor: &TestContextSensor, index: usize) -> u32 {
+        BigEndian::read_u32(&sensor.get_send_calls()[index].1.get_header()[0..4])
+    }
+
+    fn response_to(survey_id: u32, payload: u8) -> Message {
+        let mut body: Vec<u8> = vec![0, 0, 0, 0, payload];
+
+        BigEndian::write_u32(&mut body[0..4], survey_id);
+
+        Message::from_body(body)
+    }
+
+    #[test]
+    fn context_responses_are_queued_by_survey_id() {
+        let (tx, _) = mpsc::channel();
+        let mut surv = Surveyor::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(0);
+        let (ctx_tx1, ctx_rx1) = mpsc::channel();
+        let (ctx_tx2, ctx_rx2) = mpsc::channel();
+        let cid1 = ContextId::from(1);
+        let cid2 = ContextId::from(2);
+
+        surv.add_pipe(&mut ctx, eid, new_test_pipe(eid));
+        surv.add_context(&mut ctx, cid1, ctx_tx1).unwrap();
+        surv.add_context(&mut ctx, cid2, ctx_tx2).unwrap();
+        surv.on_send_ready(&mut ctx, eid);
+        surv.context_send(&mut ctx, cid1, Message::from_body(vec![1]), None);
+        surv.on_send_ack(&mut ctx, eid);
+        surv.on_send_ready(&mut ctx, eid);
+        surv.context_send(&mut ctx, cid2, Message::from_body(vec![2]), None);
+        surv.on_send_ack(&mut ctx, eid);
+
+        let survey_id1 = sent_survey_id(&ctx_sensor.borrow(), 0);
+        let survey_id2 = sent_survey_id(&ctx_sensor.borrow(), 1);
+        assert!(survey_id1 != survey_id2);
+        assert!(ctx_rx1.try_recv().is_ok());
+        assert!(ctx_rx2.try_recv().is_ok());
+
+        surv.on_recv_ready(&mut ctx, eid);
+        surv.on_recv_ack(&mut ctx, eid, response_to(survey_id2, 20));
+        surv.on_recv_ready(&mut ctx, eid);
+        surv.on_recv_ack(&mut ctx, eid, response_to(survey_id1, 10));
+        ctx_sensor.borrow().assert_recv_from(eid, 2);
+
+        surv.context_recv(&mut ctx, cid1, None);
+        surv.context_recv(&mut ctx, cid2, None);
+
+        match ctx_rx1.try_recv() {
+            Ok(Reply::Recv(msg)) => assert_eq!(&[10], msg.get_body()),
+            _ => panic!("first context should have been sent a Recv reply !")
+        }
+        match ctx_rx2.try_recv() {
+            Ok(Reply::Recv(msg)) => assert_eq!(&[20], msg.get_body()),
+            _ => panic!("second context should have been sent a Recv reply !")
+        }
+    }
+
+    #[test]
+    fn context_survey_deadline_fails_the_pending_recv() {
+        let (tx, _) = mpsc::channel();
+        let mut surv = Surveyor::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(0);
+        let (ctx_tx, ctx_rx) = mpsc::channel();
+        let cid = ContextId::from(1);
+
+        surv.add_pipe(&mut ctx, eid, new_test_pipe(eid));
+        surv.add_context(&mut ctx, cid, ctx_tx).unwrap();
+        surv.on_send_ready(&mut ctx, eid);
+        surv.context_send(&mut ctx, cid, Message::from_body(vec![1]), None);
+        let _ = ctx_rx.try_recv(); // flush send reply
+
+        surv.context_recv(&mut ctx, cid, None);
+        assert!(ctx_rx.try_recv().is_err());
+
+        surv.on_timer_tick(&mut ctx, Schedulable::ContextSurveyCancel(cid));
+        match ctx_rx.try_recv() {
+            Ok(Reply::Err(e)) => assert_eq!(io::ErrorKind::TimedOut, e.kind()),
+            _ => panic!("context should have been sent an Err reply !")
+        }
+    }
+
+    #[test]
+    fn response_read_for_contexts_is_delivered_to_the_socket_survey() {
+        let (tx, rx) = mpsc::channel();
+        let mut surv = Surveyor::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(0);
+        let (ctx_tx, _) = mpsc::channel();
+        let cid = ContextId::from(1);
+
+        surv.add_pipe(&mut ctx, eid, new_test_pipe(eid));
+        surv.add_context(&mut ctx, cid, ctx_tx).unwrap();
+        surv.on_send_ready(&mut ctx, eid);
+        surv.send(&mut ctx, Message::new(), None);
+        let _ = rx.try_recv(); // flush send reply
+        surv.on_send_ready(&mut ctx, eid);
+        surv.context_send(&mut ctx, cid, Message::from_body(vec![1]), None);
+
+        let survey_id = sent_survey_id(&ctx_sensor.borrow(), 0);
+
+        surv.on_recv_ready(&mut ctx, eid);
+        surv.on_recv_ack(&mut ctx, eid, response_to(survey_id, 42));
+        surv.recv(&mut ctx, None);
+
+        match rx.try_recv() {
+            Ok(Reply::Recv(msg)) => assert_eq!(&[42], msg.get_body()),
+            _ => panic!("facade should have been sent a Recv reply !")
+        }
+    }
+
+    #[test]
+    fn survey_collects_the_responses_until_the_deadline() {
+        let (tx, rx) = mpsc::channel();
+        let mut surv = Surveyor::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid1 = EndpointId::from(1);
+        let eid2 = EndpointId::from(2);
+
+        surv.add_pipe(&mut ctx, eid1, new_test_pipe(eid1));
+        surv.add_pipe(&mut ctx, eid2, new_test_pipe(eid2));
+        surv.on_send_ready(&mut ctx, eid1);
+        surv.on_send_ready(&mut ctx, eid2);
+        ctx.set_schedule_result(Scheduled::from(1));
+        surv.survey(&mut ctx, Message::from_body(vec![1])).unwrap();
+
+        let survey_id = sent_survey_id(&ctx_sensor.borrow(), 0);
+        assert_eq!(survey_id, sent_survey_id(&ctx_sensor.borrow(), 1));
+
+        surv.on_recv_ready(&mut ctx, eid1);
+        surv.on_recv_ready(&mut ctx, eid2);
+        surv.on_recv_ack(&mut ctx, eid2, response_to(survey_id, 20));
+        surv.on_recv_ack(&mut ctx, eid1, response_to(survey_id ^ 1, 0));
+        surv.on_recv_ready(&mut ctx, eid1);
+        surv.on_recv_ack(&mut ctx, eid1, response_to(survey_id, 10));
+        assert!(rx.try_recv().is_err());
+
+        surv.on_timer_tick(&mut ctx, Schedulable::SurveyCollect);
+        match rx.try_recv() {
+            Ok(Reply::Survey(responses, surveyed)) => {
+                assert_eq!(2, surveyed);
+                assert_eq!(2, responses.len());
+                assert_eq!(&[20], responses[0].get_body());
+                assert_eq!(&[10], responses[1].get_body());
+            },
+            _ => panic!("facade should have been sent a Survey reply !")
+        }
+    }
+}
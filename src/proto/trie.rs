@@ -0,0 +1,269 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+/*
+Prefix trie used by the sub protocol to filter incoming messages.
+Each node is reached by one byte of a topic, children are kept sorted by byte.
+
+### INSERT
+ - walk the topic bytes, creating the missing nodes
+ - increment the subscription count of the last node
+
+### REMOVE
+ - walk the topic bytes, abort if a node is missing
+ - decrement the subscription count of the last node
+ - prune the nodes that are neither subscribed nor have children
+
+### TOPICS
+ - walk the whole trie depth first, children in byte order
+ - collect the path to each subscribed node
+
+### MATCHES
+ - walk the payload bytes, stop at the first missing node
+ - succeed as soon as a subscribed node is reached (root included)
+
+Matching a message costs at most the length of the longest topic,
+whatever the number of subscriptions.
+*/
+
+pub struct Trie {
+    root: Node
+}
+
+struct Node {
+    count: usize,
+    children: Vec<(u8, Node)>
+}
+
+impl Trie {
+
+    pub fn new() -> Trie {
+        Trie {
+            root: Node::new()
+        }
+    }
+
+    pub fn insert(&mut self, topic: &[u8]) {
+        let mut node = &mut self.root;
+
+        for &byte in topic {
+            node = node.child_or_insert(byte);
+        }
+
+        node.count += 1;
+    }
+
+    pub fn remove(&mut self, topic: &[u8]) {
+        self.root.remove(topic);
+    }
+
+    pub fn matches(&self, payload: &[u8]) -> bool {
+        let mut node = &self.root;
+
+        if node.is_subscribed() {
+            return true;
+        }
+
+        for byte in payload {
+            match node.child(*byte) {
+                Some(child) => node = child,
+                None        => return false
+            }
+
+            if node.is_subscribed() {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    pub fn topics(&self) -> Vec<Vec<u8>> {
+        let mut topics = Vec::new();
+        let mut path = Vec::new();
+
+        self.root.collect(&mut path, &mut topics);
+
+        topics
+    }
+}
+
+impl Node {
+    fn new() -> Node {
+        Node {
+            count: 0,
+            children: Vec::new()
+        }
+    }
+    fn find(&self, byte: u8) -> Result<usize, usize> {
+        self.children.binary_search_by_key(&byte, |&(b, _)| b)
+    }
+    fn child(&self, byte: u8) -> Option<&Node> {
+        self.find(byte).ok().map(|index| &self.children[index].1)
+    }
+    fn child_or_insert(&mut self, byte: u8) -> &mut Node {
+        let index = match self.find(byte) {
+            Ok(index) => index,
+            Err(index) => {
+                self.children.insert(index, (byte, Node::new()));
+                index
+            }
+        };
+
+        &mut self.children[index].1
+    }
+    fn is_subscribed(&self) -> bool {
+        self.count > 0
+    }
+    fn is_empty(&self) -> bool {
+        !self.is_subscribed() && self.children.is_empty()
+    }
+    fn remove(&mut self, topic: &[u8]) {
+        if topic.is_empty() {
+            if self.count > 0 {
+                self.count -= 1;
+            }
+            return;
+        }
+
+        if let Ok(index) = self.find(topic[0]) {
+            let prune = {
+                let child = &mut self.children[index].1;
+
+                child.remove(&topic[1..]);
+                child.is_empty()
+            };
+
+            if prune {
+                self.children.remove(index);
+            }
+        }
+    }
+    fn collect(&self, path: &mut Vec<u8>, topics: &mut Vec<Vec<u8>>) {
+        if self.is_subscribed() {
+            topics.push(path.clone());
+        }
+
+        for &(byte, ref child) in &self.children {
+            path.push(byte);
+            child.collect(path, topics);
+            path.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::Trie;
+
+    #[test]
+    fn empty_trie_matches_nothing() {
+        let trie = Trie::new();
+
+        assert!(!trie.matches(b""));
+        assert!(!trie.matches(b"ABC"));
+    }
+
+    #[test]
+    fn empty_topic_matches_everything() {
+        let mut trie = Trie::new();
+
+        trie.insert(b"");
+        assert!(trie.matches(b""));
+        assert!(trie.matches(b"ABC"));
+    }
+
+    #[test]
+    fn topic_matches_payloads_starting_with_it() {
+        let mut trie = Trie::new();
+
+        trie.insert(b"AB");
+        assert!(trie.matches(b"AB"));
+        assert!(trie.matches(b"ABC"));
+        assert!(!trie.matches(b"A"));
+        assert!(!trie.matches(b"AC"));
+        assert!(!trie.matches(b"BAB"));
+    }
+
+    #[test]
+    fn shorter_topic_matches_even_when_a_longer_one_is_subscribed() {
+        let mut trie = Trie::new();
+
+        trie.insert(b"ABCD");
+        trie.insert(b"AB");
+        assert!(trie.matches(b"ABX"));
+        assert!(trie.matches(b"ABCDE"));
+    }
+
+    #[test]
+    fn removed_topic_does_not_match_anymore() {
+        let mut trie = Trie::new();
+
+        trie.insert(b"AB");
+        trie.insert(b"ABCD");
+        trie.remove(b"AB");
+        assert!(!trie.matches(b"ABX"));
+        assert!(trie.matches(b"ABCDE"));
+
+        trie.remove(b"ABCD");
+        assert!(!trie.matches(b"ABCDE"));
+        assert!(trie.root.children.is_empty());
+    }
+
+    #[test]
+    fn removing_an_unknown_topic_does_nothing() {
+        let mut trie = Trie::new();
+
+        trie.insert(b"ABCD");
+        trie.remove(b"AB");
+        trie.remove(b"XY");
+        assert!(trie.matches(b"ABCD"));
+    }
+
+    #[test]
+    fn topic_inserted_twice_must_be_removed_twice() {
+        let mut trie = Trie::new();
+
+        trie.insert(b"AB");
+        trie.insert(b"AB");
+        trie.remove(b"AB");
+        assert!(trie.matches(b"ABC"));
+
+        trie.remove(b"AB");
+        assert!(!trie.matches(b"ABC"));
+
+        trie.remove(b"AB");
+        trie.insert(b"AB");
+        assert!(trie.matches(b"ABC"));
+    }
+
+    #[test]
+    fn topics_are_listed_once_in_byte_order() {
+        let mut trie = Trie::new();
+
+        trie.insert(b"B");
+        trie.insert(b"AB");
+        trie.insert(b"A");
+        trie.insert(b"AB");
+        trie.insert(b"");
+
+        let expected: Vec<Vec<u8>> = vec![vec![], b"A".to_vec(), b"AB".to_vec(), b"B".to_vec()];
+        assert_eq!(expected, trie.topics());
+    }
+
+    #[test]
+    fn binary_topics_are_supported() {
+        let mut trie = Trie::new();
+
+        trie.insert(&[0x00, 0xFF]);
+        trie.insert(&[0x80]);
+        assert!(trie.matches(&[0x00, 0xFF, 0x00]));
+        assert!(trie.matches(&[0x80, 0x00]));
+        assert!(!trie.matches(&[0x00, 0xFE]));
+        assert!(!trie.matches(&[0xFF]));
+    }
+}
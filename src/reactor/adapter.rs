@@ -121,6 +121,14 @@ impl PipeController {
         }
     }
 
+    pub fn local_addr(&self) -> Option<String> {
+        self.pipe.local_addr()
+    }
+
+    pub fn remote_addr(&self) -> Option<String> {
+        self.pipe.remote_addr()
+    }
+
     fn create_context<'a, 'b>(&self, registrar: &'b mut Registrar, signal_bus: &'a mut EventLoopBus<Signal>) -> EndpointEventLoopContext<'a, 'b> {
         EndpointEventLoopContext {
             socket_id: self.socket_id,
@@ -147,6 +155,10 @@ impl AcceptorController {
         }
     }
 
+    pub fn local_addr(&self) -> Option<String> {
+        self.acceptor.local_addr()
+    }
+
     fn create_context<'a, 'b>(&self, registrar: &'b mut Registrar, signal_bus: &'a mut EventLoopBus<Signal>) -> EndpointEventLoopContext<'a, 'b> {
         EndpointEventLoopContext {
             socket_id: self.socket_id,
@@ -173,6 +185,10 @@ impl EndpointCollection {
             ok_or_else(|| invalid_input_io_error("invalid scheme"))
     }
 
+    pub fn get_pipe(&self, eid: EndpointId) -> Option<&PipeController> {
+        self.pipes.get(&eid)
+    }
+
     pub fn get_pipe_mut(&mut self, eid: EndpointId) -> Option<&mut PipeController> {
         self.pipes.get_mut(&eid)
     }
@@ -199,6 +215,10 @@ impl EndpointCollection {
         self.pipes.remove(&eid);
     }
 
+    pub fn get_acceptor(&self, eid: EndpointId) -> Option<&AcceptorController> {
+        self.acceptors.get(&eid)
+    }
+
     pub fn get_acceptor_mut(&mut self, eid: EndpointId) -> Option<&mut AcceptorController> {
         self.acceptors.get_mut(&eid)
     }
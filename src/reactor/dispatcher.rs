@@ -9,7 +9,7 @@ use std::sync::mpsc::Sender;
 use std::io;
 use std::time::Duration;
 
-use mio::{Token, Ready, PollOpt};
+use mio::{Token, Ready, PollOpt, SetReadiness};
 use mio_extras::timer::{Timer, Builder};
 use mio_extras::channel::{Receiver};
 
@@ -25,6 +25,7 @@ use super::adapter::{
     DeviceEventLoopContext,
     ProbeEventLoopContext };
 use sequence::Sequence;
+use io_error::*;
 
 const CHANNEL_TOKEN: Token = Token(::std::usize::MAX - 1);
 const BUS_TOKEN: Token     = Token(::std::usize::MAX - 2);
@@ -39,7 +40,10 @@ pub struct Dispatcher {
     // request handlers
     sockets: session::Session,
     endpoints: EndpointCollection,
-    schedule: Schedule
+    schedule: Schedule,
+
+    // readiness handles of the sockets
+    readiness: HashMap<SocketId, SetReadiness, BuildIdHasher>
 }
 
 impl Dispatcher {
@@ -71,7 +75,8 @@ impl Dispatcher {
             timer: clock,
             sockets: session::Session::new(id_seq.clone(), tx),
             endpoints: EndpointCollection::new(id_seq.clone(), transports),
-            schedule: Schedule::new(timeout_eq)
+            schedule: Schedule::new(timeout_eq),
+            readiness: HashMap::default()
         }
 
     }
@@ -159,6 +164,10 @@ impl Dispatcher {
             context::Schedulable::Rebind(eid, spec)    => self.apply_on_socket(sid, |socket, ctx| socket.rebind(ctx, eid, spec)),
             context::Schedulable::SendTimeout          => self.apply_on_socket(sid, |socket, ctx| socket.on_send_timeout(ctx)),
             context::Schedulable::RecvTimeout          => self.apply_on_socket(sid, |socket, ctx| socket.on_recv_timeout(ctx)),
+            context::Schedulable::Linger               => self.apply_on_socket(sid, |socket, ctx| socket.on_linger_timeout(ctx)),
+            context::Schedulable::DeferredTimeout(id)  => self.apply_on_socket(sid, |socket, ctx| socket.on_deferred_timeout(ctx, id)),
+            context::Schedulable::ContextSendTimeout(cid) => self.apply_on_socket(sid, |socket, ctx| socket.on_context_send_timeout(ctx, cid)),
+            context::Schedulable::ContextRecvTimeout(cid) => self.apply_on_socket(sid, |socket, ctx| socket.on_context_recv_timeout(ctx, cid)),
             other                                      => self.apply_on_socket(sid, |socket, ctx| socket.on_timer_tick(ctx, other))
         }
     }
@@ -209,7 +218,7 @@ impl Dispatcher {
             session::Request::Shutdown => el.shutdown()
         }
     }
-    fn process_socket_request(&mut self, _: &mut EventLoop, id: SocketId, request: socket::Request) {
+    fn process_socket_request(&mut self, el: &mut EventLoop, id: SocketId, request: socket::Request) {
         match request {
             socket::Request::Connect(url)     => self.apply_on_socket(id, |socket, ctx| socket.connect(ctx, url)),
             socket::Request::Bind(url)        => self.apply_on_socket(id, |socket, ctx| socket.bind(ctx, url)),
@@ -217,18 +226,67 @@ impl Dispatcher {
             socket::Request::Send(msg, true)  => self.apply_on_socket(id, |socket, ctx| socket.try_send(ctx, msg)),
             socket::Request::Recv(false)      => self.apply_on_socket(id, |socket, ctx| socket.recv(ctx)),
             socket::Request::Recv(true)       => self.apply_on_socket(id, |socket, ctx| socket.try_recv(ctx)),
+            socket::Request::Survey(msg)      => self.apply_on_socket(id, |socket, ctx| socket.survey(ctx, msg)),
             socket::Request::SetOption(x)     => self.apply_on_socket(id, |socket, ctx| socket.set_option(ctx, x)),
+            socket::Request::Subscriptions    => self.apply_on_socket(id, |socket, ctx| socket.subscriptions(ctx)),
+            socket::Request::DroppedMessages  => self.apply_on_socket(id, |socket, ctx| socket.dropped_messages(ctx)),
+            socket::Request::CreateContext    => self.apply_on_socket(id, |socket, ctx| socket.create_context(ctx)),
+            socket::Request::ContextSend(cid, msg) => self.apply_on_socket(id, |socket, ctx| socket.context_send(ctx, cid, msg)),
+            socket::Request::ContextRecv(cid) => self.apply_on_socket(id, |socket, ctx| socket.context_recv(ctx, cid)),
+            socket::Request::CloseContext(cid) => self.apply_on_socket(id, |socket, ctx| socket.close_context(ctx, cid)),
+            socket::Request::Cancel           => self.apply_on_socket(id, |socket, ctx| socket.cancel(ctx)),
+            socket::Request::SetReplyWaker(w) => self.apply_on_socket(id, |socket, ctx| socket.set_reply_waker(ctx, w)),
+            socket::Request::SetReadiness(r)  => self.set_socket_readiness(id, r),
+            socket::Request::Shared(tx, req)  => {
+                self.apply_on_socket(id, |socket, _| socket.set_requester(Some(tx)));
+                self.process_socket_request(el, id, *req);
+                self.apply_on_socket(id, |socket, _| socket.set_requester(None));
+            },
             socket::Request::Close            => self.apply_on_socket(id, |socket, ctx| socket.close(ctx)),
         }
     }
     fn process_endpoint_request(&mut self, _: &mut EventLoop, sid: SocketId, eid: EndpointId, request: endpoint::Request) {
-        let endpoint::Request::Close(remote) = request;
+        match request {
+            endpoint::Request::Close(remote) => self.apply_on_socket(sid, |socket, ctx| if remote {
+                socket.close_pipe(ctx, eid)
+            } else {
+                socket.close_acceptor(ctx, eid)
+            }),
+            endpoint::Request::LocalAddr(remote) => {
+                let addr = self.get_endpoint_local_addr(eid, remote);
+
+                self.reply_to_endpoint(sid, eid, endpoint::Reply::LocalAddr(addr))
+            },
+            endpoint::Request::RemoteAddr(remote) => {
+                let addr = self.get_endpoint_remote_addr(eid, remote);
 
-        self.apply_on_socket(sid, |socket, ctx| if remote {
-            socket.close_pipe(ctx, eid)
-        } else {
-            socket.close_acceptor(ctx, eid)
-        });
+                self.reply_to_endpoint(sid, eid, endpoint::Reply::RemoteAddr(addr))
+            },
+            endpoint::Request::State => self.apply_on_socket(sid, |socket, _| {
+                let reply = match socket.get_endpoint_state(eid) {
+                    Some(state) => endpoint::Reply::State(state),
+                    None => endpoint::Reply::Err(other_io_error("unknown endpoint"))
+                };
+
+                socket.send_endpoint_reply(eid, reply)
+            }),
+            endpoint::Request::Url => self.apply_on_socket(sid, |socket, _| {
+                let reply = match socket.get_endpoint_url(eid) {
+                    Some(url) => endpoint::Reply::Url(url),
+                    None => endpoint::Reply::Err(other_io_error("unknown endpoint"))
+                };
+
+                socket.send_endpoint_reply(eid, reply)
+            }),
+            endpoint::Request::AcceptedPipes => {
+                let pipe_ids = self.sockets.get_socket_mut(sid).map_or_else(Vec::new, |socket| socket.get_accepted_pipes(eid));
+                let pipes = pipe_ids.into_iter()
+                    .map(|pid| (pid, self.endpoints.get_pipe(pid).and_then(|p| p.remote_addr())))
+                    .collect();
+
+                self.reply_to_endpoint(sid, eid, endpoint::Reply::AcceptedPipes(pipes))
+            }
+        }
     }
     fn process_device_request(&mut self, _: &mut EventLoop, id: DeviceId, request: device::Request) {
         if let device::Request::Check = request { 
@@ -270,6 +328,7 @@ impl Dispatcher {
 /*****************************************************************************/
     fn process_pipe_evt(&mut self, _: &mut EventLoop, sid: SocketId, eid: EndpointId, evt: pipe::Event) {
         match evt {
+            pipe::Event::Connected     => self.apply_on_socket(sid, |socket, _| socket.on_pipe_connected(eid)),
             pipe::Event::Opened        => self.apply_on_socket(sid, |socket, ctx| socket.on_pipe_opened(ctx, eid)),
             pipe::Event::CanSend(x)    => self.apply_on_socket(sid, |socket, ctx| socket.on_send_ready(ctx, eid, x)),
             pipe::Event::Sent          => self.apply_on_socket(sid, |socket, ctx| socket.on_send_ack(ctx, eid)),
@@ -298,15 +357,69 @@ impl Dispatcher {
             context::Event::CanRecv(x) => {
                 self.apply_on_device_link(sid, |device| device.on_socket_can_recv(sid, x));
                 self.apply_on_probe_link(sid, |probe, ctx| probe.on_socket_can_recv(ctx, sid, x));
+                self.update_socket_readiness(sid, Ready::readable(), x);
             },
             context::Event::CanSend(x) => {
                 self.apply_on_probe_link(sid, |probe, ctx| probe.on_socket_can_send(ctx, sid, x));
-
+                self.update_socket_readiness(sid, Ready::writable(), x);
             },
-            context::Event::Closed => self.sockets.remove_socket(sid)
+            context::Event::Closed => {
+                self.readiness.remove(&sid);
+                self.sockets.remove_socket(sid)
+            }
+        }
+    }
+
+    fn set_socket_readiness(&mut self, sid: SocketId, readiness: SetReadiness) {
+        if let Some(socket) = self.sockets.get_socket_mut(sid) {
+            let mut ready = Ready::empty();
+
+            if socket.is_recv_ready() {
+                ready.insert(Ready::readable());
+            }
+            if socket.is_send_ready() {
+                ready.insert(Ready::writable());
+            }
+
+            let _ = readiness.set_readiness(ready);
+            self.readiness.insert(sid, readiness);
         }
     }
 
+    fn update_socket_readiness(&self, sid: SocketId, kind: Ready, is_ready: bool) {
+        if let Some(readiness) = self.readiness.get(&sid) {
+            let mut ready = readiness.readiness();
+
+            if is_ready {
+                ready.insert(kind);
+            } else {
+                ready.remove(kind);
+            }
+
+            let _ = readiness.set_readiness(ready);
+        }
+    }
+
+    fn get_endpoint_local_addr(&self, eid: EndpointId, remote: bool) -> Option<String> {
+        if remote {
+            self.endpoints.get_pipe(eid).and_then(|p| p.local_addr())
+        } else {
+            self.endpoints.get_acceptor(eid).and_then(|a| a.local_addr())
+        }
+    }
+
+    fn get_endpoint_remote_addr(&self, eid: EndpointId, remote: bool) -> Option<String> {
+        if remote {
+            self.endpoints.get_pipe(eid).and_then(|p| p.remote_addr())
+        } else {
+            None
+        }
+    }
+
+    fn reply_to_endpoint(&mut self, sid: SocketId, eid: EndpointId, reply: endpoint::Reply) {
+        self.apply_on_socket(sid, |socket, _| socket.send_endpoint_reply(eid, reply))
+    }
+
     fn apply_on_socket<F>(&mut self, id: SocketId, f: F) 
     where F : FnOnce(&mut socket::Socket, &mut SocketEventLoopContext) {
         if let Some(socket) = self.sockets.get_socket_mut(id) {
@@ -318,6 +431,7 @@ impl Dispatcher {
                 &mut self.timer);
 
             f(socket, &mut ctx);
+            socket.run_deferred_operations(&mut ctx);
         }
     }
 
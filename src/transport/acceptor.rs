@@ -27,6 +27,8 @@ pub trait Acceptor {
     fn ready(&mut self, ctx: &mut Context, events: Ready);
     fn open(&mut self, ctx: &mut Context);
     fn close(&mut self, ctx: &mut Context);
+    /// Address the acceptor is listening on, if the transport has one.
+    fn local_addr(&self) -> Option<String> { None }
 }
 
 pub trait Context : EndpointRegistrar {
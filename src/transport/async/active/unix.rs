@@ -71,7 +71,7 @@ impl<S : AsyncPipeStub> Active<S> {
         ctx.raise(Event::Received(msg));
     }
     fn readable_changed(&mut self, ctx: &mut Context, events: Ready) -> Result<()> {
-        if events.is_readable() == false {
+        if events.is_readable() == false && self.stub.has_buffered_recv() == false {
             return Ok(self.change_can_recv(ctx, false));
         }
 
@@ -92,6 +92,12 @@ impl<S : AsyncPipeStub> Active<S> {
             ctx.raise(Event::CanRecv(can_recv));
         }
     }
+    // no readiness event will come for data the stub has already buffered
+    fn buffered_recv_changed(&mut self, ctx: &mut Context) {
+        if self.stub.has_buffered_recv() && self.stub.has_pending_recv() == false {
+            self.change_can_recv(ctx, true);
+        }
+    }
 
     fn hang_up_changed(&mut self, hup: bool) -> Result<()> {
         if hup {
@@ -114,10 +120,12 @@ fn interest() -> Ready {
 
 impl<S : AsyncPipeStub + 'static> PipeState<S> for Active<S> {
     fn name(&self) -> &'static str {"Active"}
+    fn stub(&self) -> Option<&S> { Some(&self.stub) }
 
     fn enter(&mut self, ctx: &mut Context) {
         ctx.reregister(self.stub.deref(), interest(), PollOpt::level());
         ctx.raise(Event::Opened);
+        self.buffered_recv_changed(ctx);
     }
     fn close(self: Box<Self>, ctx: &mut Context) -> Box<PipeState<S>> {
         ctx.deregister(self.stub.deref());
@@ -138,6 +146,10 @@ impl<S : AsyncPipeStub + 'static> PipeState<S> for Active<S> {
         let progress = self.stub.start_recv();
         let res = self.on_recv_progress(ctx, progress);
 
+        if res.is_ok() {
+            self.buffered_recv_changed(ctx);
+        }
+
         no_transition_if_ok(self, ctx, res)
     }
     fn ready(mut self: Box<Self>, ctx: &mut Context, events: Ready) -> Box<PipeState<S>> {
@@ -71,7 +71,7 @@ impl<S : AsyncPipeStub> Active<S> {
         self.raise_and_resync_readiness(ctx, Event::Received(msg));
     }
     fn readable_changed(&mut self, ctx: &mut Context, events: Ready) -> Result<()> {
-        if events.is_readable() == false {
+        if events.is_readable() == false && self.stub.has_buffered_recv() == false {
             return Ok(self.change_can_recv(ctx, false));
         }
 
@@ -88,13 +88,21 @@ impl<S : AsyncPipeStub> Active<S> {
             ctx.raise(Event::CanRecv(can_recv));
         }
     }
+    // no readiness event will come for data the stub has already buffered
+    fn buffered_recv_changed(&mut self, ctx: &mut Context) {
+        if self.stub.has_buffered_recv() && self.stub.has_pending_recv() == false {
+            self.change_can_recv(ctx, true);
+        }
+    }
 }
 
 impl<S : AsyncPipeStub + 'static> PipeState<S> for Active<S> {
     fn name(&self) -> &'static str {"Active"}
+    fn stub(&self) -> Option<&S> { Some(&self.stub) }
 
     fn enter(&mut self, ctx: &mut Context) {
         self.raise_and_resync_readiness(ctx, Event::Opened);
+        self.buffered_recv_changed(ctx);
     }
     fn close(self: Box<Self>, ctx: &mut Context) -> Box<PipeState<S>> {
         ctx.deregister(self.stub.deref());
@@ -115,6 +123,10 @@ impl<S : AsyncPipeStub + 'static> PipeState<S> for Active<S> {
 
         self.can_recv_msg = false;
 
+        if res.is_ok() {
+            self.buffered_recv_changed(ctx);
+        }
+
         no_transition_if_ok(self, ctx, res)
     }
     fn ready(mut self: Box<Self>, ctx: &mut Context, events: Ready) -> Box<PipeState<S>> {
@@ -4,7 +4,7 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::io::Result;
+use std::io::{Result, ErrorKind};
 
 use mio::{Ready, PollOpt};
 
@@ -12,21 +12,44 @@ use transport::async::stub::*;
 use transport::async::state::*;
 use transport::async::active::Active; 
 use transport::async::dead::Dead; 
-use transport::pipe::Context;
+use transport::pipe::{Event, Context};
 
 pub struct HandshakeTx<S : AsyncPipeStub + 'static> {
     stub: S,
-    proto_ids: (u16, u16)
+    proto_ids: (u16, u16),
+    interest: Ready,
+    is_prepared: bool,
+    is_connected: bool
 }
 
 impl<S : AsyncPipeStub> HandshakeTx<S> {
     pub fn new(s: S, pids: (u16, u16)) -> HandshakeTx<S> {
         HandshakeTx { 
             stub: s,
-            proto_ids: pids
+            proto_ids: pids,
+            interest: Ready::writable(),
+            is_prepared: false,
+            is_connected: false
         }
     }
 
+    fn prepare_handshake(&mut self, ctx: &mut Context) -> Result<bool> {
+        let interest = match try!(self.stub.prepare_handshake()) {
+            Some(interest) => interest,
+            None => {
+                self.is_prepared = true;
+                Ready::writable()
+            }
+        };
+
+        if self.interest != interest {
+            self.interest = interest;
+            ctx.reregister(self.stub.deref(), interest, PollOpt::level());
+        }
+
+        Ok(self.is_prepared)
+    }
+
     fn send_handshake(&mut self) -> Result<()> {
         let pids = self.proto_ids;
 
@@ -42,6 +65,7 @@ impl<S : AsyncPipeStub> Into<HandshakeRx<S>> for HandshakeTx<S> {
 
 impl<S : AsyncPipeStub> PipeState<S> for HandshakeTx<S> {
     fn name(&self) -> &'static str {"HandshakeTx"}
+    fn stub(&self) -> Option<&S> { Some(&self.stub) }
 
     fn enter(&mut self, ctx: &mut Context) {
         ctx.register(self.stub.deref(), Ready::writable(), PollOpt::level());
@@ -55,9 +79,26 @@ impl<S : AsyncPipeStub> PipeState<S> for HandshakeTx<S> {
         Box::new(Dead)
     }
     fn ready(mut self: Box<Self>, ctx: &mut Context, events: Ready) -> Box<PipeState<S>> {
+        if !self.is_connected && self.stub.is_connected() {
+            self.is_connected = true;
+            ctx.raise(Event::Connected);
+        }
+
+        if !self.is_prepared {
+            match self.prepare_handshake(ctx) {
+                Ok(true) => {},
+                Ok(false) => return self,
+                Err(e) => return self.error(ctx, e)
+            }
+        }
+
         if events.is_writable() {
             let res = self.send_handshake();
 
+            if is_in_progress(&res) {
+                return self;
+            }
+
             transition_if_ok::<HandshakeTx<S>, HandshakeRx<S>, S>(self, ctx, res)
         } else {
             self
@@ -67,14 +108,25 @@ impl<S : AsyncPipeStub> PipeState<S> for HandshakeTx<S> {
 
 pub struct HandshakeRx<S> {
     stub: S,
-    proto_ids: (u16, u16)
+    proto_ids: (u16, u16),
+    interest: Ready
 }
 
 impl<S: AsyncPipeStub> HandshakeRx<S> {
     pub fn new(s: S, pids: (u16, u16)) -> HandshakeRx<S> {
         HandshakeRx {
             stub: s,
-            proto_ids: pids
+            proto_ids: pids,
+            interest: Ready::readable()
+        }
+    }
+
+    fn update_interest(&mut self, ctx: &mut Context) {
+        let interest = self.stub.recv_handshake_interest();
+
+        if self.interest != interest {
+            self.interest = interest;
+            ctx.reregister(self.stub.deref(), interest, PollOpt::level());
         }
     }
 
@@ -94,9 +146,10 @@ impl<S : AsyncPipeStub> Into<Active<S>> for HandshakeRx<S> {
 impl<S : AsyncPipeStub + 'static> PipeState<S> for HandshakeRx<S> {
 
     fn name(&self) -> &'static str {"HandshakeRx"}
+    fn stub(&self) -> Option<&S> { Some(&self.stub) }
 
     fn enter(&mut self, ctx: &mut Context) {
-        ctx.reregister(self.stub.deref(), Ready::readable(), PollOpt::level());
+        ctx.reregister(self.stub.deref(), self.interest, PollOpt::level());
     }
     fn close(self: Box<Self>, ctx: &mut Context) -> Box<PipeState<S>> {
         ctx.deregister(self.stub.deref());
@@ -104,9 +157,14 @@ impl<S : AsyncPipeStub + 'static> PipeState<S> for HandshakeRx<S> {
         Box::new(Dead)
     }
     fn ready(mut self: Box<Self>, ctx: &mut Context, events: Ready) -> Box<PipeState<S>> {
-        if events.is_readable() {
+        if events.is_readable() || events.is_writable() {
             let res = self.recv_handshake();
-            
+
+            if is_in_progress(&res) {
+                self.update_interest(ctx);
+                return self;
+            }
+
             transition_if_ok::<HandshakeRx<S>, Active<S>, S>(self, ctx, res)
         } else {
             self
@@ -114,6 +172,13 @@ impl<S : AsyncPipeStub + 'static> PipeState<S> for HandshakeRx<S> {
     }
 }
 
+fn is_in_progress(res: &Result<()>) -> bool {
+    match *res {
+        Err(ref e) => e.kind() == ErrorKind::WouldBlock,
+        Ok(..) => false
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::rc::Rc;
@@ -121,6 +186,7 @@ mod tests {
 
     use mio;
 
+    use transport::pipe;
     use transport::tests::*;
     use transport::async::state::*;
     use transport::async::tests::*;
@@ -225,4 +291,91 @@ mod tests {
         assert_eq!(1, sensor.borrow().get_received_handshakes());
         assert_eq!("Active", new_state.name());
     }
+
+    #[test]
+    fn on_writable_an_unfinished_handshake_should_not_cause_a_transition() {
+        let mut stub = TestStepStream::new();
+        stub.set_handshake_in_progress(true);
+        let state = Box::new(HandshakeTx::new(stub, (4, 2)));
+        let mut ctx = TestPipeContext::new();
+        let new_state = state.ready(&mut ctx, mio::Ready::writable());
+
+        assert_eq!(1, ctx.get_raised_events().len());
+        assert!(match ctx.get_raised_events()[0] { pipe::Event::Connected => true, _ => false });
+        assert_eq!("HandshakeTx", new_state.name());
+
+        let new_state = new_state.ready(&mut ctx, mio::Ready::writable());
+
+        assert_eq!(1, ctx.get_raised_events().len());
+        assert_eq!("HandshakeTx", new_state.name());
+    }
+
+    #[test]
+    fn on_readable_an_unfinished_handshake_should_not_cause_a_transition() {
+        let mut stub = TestStepStream::new();
+        stub.set_handshake_in_progress(true);
+        let state = Box::new(HandshakeRx::new(stub, (4, 2)));
+        let mut ctx = TestPipeContext::new();
+        let new_state = state.ready(&mut ctx, mio::Ready::readable());
+
+        assert_eq!(0, ctx.get_raised_events().len());
+        assert_eq!("HandshakeRx", new_state.name());
+    }
+
+    #[test]
+    fn on_writable_an_unprepared_stub_should_wait_for_the_readiness_it_requires() {
+        let sensor = Rc::new(RefCell::new(TestStepStreamSensor::new()));
+        let mut stub = TestStepStream::with_sensor(sensor.clone());
+        stub.set_prepare_interests(vec![mio::Ready::readable()]);
+        let state = Box::new(HandshakeTx::new(stub, (4, 2)));
+        let mut ctx = TestPipeContext::new();
+        let new_state = state.ready(&mut ctx, mio::Ready::writable());
+
+        assert_eq!(0, sensor.borrow().get_sent_handshakes().len());
+        assert_eq!(1, ctx.get_reregistrations().len());
+        assert_eq!(mio::Ready::readable(), ctx.get_reregistrations()[0].0);
+        assert_eq!("HandshakeTx", new_state.name());
+    }
+
+    #[test]
+    fn once_prepared_the_stub_should_send_the_handshake_when_writable() {
+        let sensor = Rc::new(RefCell::new(TestStepStreamSensor::new()));
+        let mut stub = TestStepStream::with_sensor(sensor.clone());
+        stub.set_prepare_interests(vec![mio::Ready::readable()]);
+        let state = Box::new(HandshakeTx::new(stub, (4, 2)));
+        let mut ctx = TestPipeContext::new();
+        let state = state.ready(&mut ctx, mio::Ready::writable());
+        let state = state.ready(&mut ctx, mio::Ready::readable());
+
+        assert_eq!(0, sensor.borrow().get_sent_handshakes().len());
+        assert_eq!(2, ctx.get_reregistrations().len());
+        assert_eq!(mio::Ready::writable(), ctx.get_reregistrations()[1].0);
+        assert_eq!("HandshakeTx", state.name());
+
+        let new_state = state.ready(&mut ctx, mio::Ready::writable());
+
+        assert_eq!(1, sensor.borrow().get_sent_handshakes().len());
+        assert_eq!("HandshakeRx", new_state.name());
+    }
+
+    #[test]
+    fn on_readable_an_unfinished_handshake_should_wait_for_the_readiness_it_requires() {
+        let sensor = Rc::new(RefCell::new(TestStepStreamSensor::new()));
+        let mut stub = TestStepStream::with_sensor(sensor.clone());
+        stub.set_handshake_in_progress(true);
+        stub.set_recv_interest(mio::Ready::writable());
+        let state = Box::new(HandshakeRx::new(stub, (4, 2)));
+        let mut ctx = TestPipeContext::new();
+        let state = state.ready(&mut ctx, mio::Ready::readable());
+
+        assert_eq!(1, ctx.get_reregistrations().len());
+        assert_eq!(mio::Ready::writable(), ctx.get_reregistrations()[0].0);
+        assert_eq!("HandshakeRx", state.name());
+
+        let state = state.ready(&mut ctx, mio::Ready::writable());
+
+        assert_eq!(2, sensor.borrow().get_received_handshakes());
+        assert_eq!(1, ctx.get_reregistrations().len());
+        assert_eq!("HandshakeRx", state.name());
+    }
 }
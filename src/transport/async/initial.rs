@@ -32,6 +32,7 @@ impl<S : AsyncPipeStub> Into<HandshakeTx<S>> for Initial<S> {
 impl<S : AsyncPipeStub + 'static> PipeState<S> for Initial<S> {
 
     fn name(&self) -> &'static str {"Initial"}
+    fn stub(&self) -> Option<&S> { Some(&self.stub) }
     
     fn open(self: Box<Self>, ctx: &mut Context) -> Box<PipeState<S>> {
         transition::<Initial<S>, HandshakeTx<S>, S>(self, ctx)
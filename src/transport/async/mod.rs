@@ -51,6 +51,10 @@ impl<S : AsyncPipeStub + 'static> AsyncPipe<S> {
             #[cfg(debug_assertions)] debug!("[{:?}] switch from {} to {}", ctx, old_name, new_name);
         }
     }
+
+    fn stub(&self) -> Option<&S> {
+        self.state.as_ref().and_then(|s| s.stub())
+    }
 }
 
 impl<S : AsyncPipeStub> pipe::Pipe for AsyncPipe<S> {
@@ -74,4 +78,12 @@ impl<S : AsyncPipeStub> pipe::Pipe for AsyncPipe<S> {
     fn recv(&mut self, ctx: &mut Context) {
         self.apply(ctx, |s, ctx| s.recv(ctx))
     }
+
+    fn local_addr(&self) -> Option<String> {
+        self.stub().and_then(|s| s.local_addr())
+    }
+
+    fn remote_addr(&self) -> Option<String> {
+        self.stub().and_then(|s| s.peer_addr())
+    }
 }
@@ -17,6 +17,7 @@ use transport::pipe::{Event, Context};
 pub trait PipeState<S : AsyncPipeStub + 'static> {
 
     fn name(&self) -> &'static str;
+    fn stub(&self) -> Option<&S> { None }
     fn open(self: Box<Self>, ctx: &mut Context) -> Box<PipeState<S>> {
         error!("[{:?}] open while {}", ctx, self.name());
         Box::new(Dead)
@@ -10,12 +10,15 @@ use std::io::{Result, Read, Write, ErrorKind};
 
 use byteorder::{ BigEndian, ByteOrder };
 
-use mio::Evented;
+use mio::{Evented, Ready};
 
 use core::Message;
 use io_error::*;
 
 pub trait AsyncPipeStub : Sender + Receiver + Handshake + Deref<Target=Evented> {
+    fn local_addr(&self) -> Option<String> { None }
+    fn peer_addr(&self) -> Option<String> { None }
+    fn is_connected(&self) -> bool { true }
     #[cfg(windows)]
     fn read_and_write_void(&mut self);
     #[cfg(windows)]
@@ -32,11 +35,21 @@ pub trait Receiver {
     fn start_recv(&mut self) -> Result<Option<Message>>;
     fn resume_recv(&mut self) -> Result<Option<Message>>;
     fn has_pending_recv(&self) -> bool;
+    /// Whether received data is buffered by the stub itself,
+    /// where it does not make the underlying stream readable.
+    fn has_buffered_recv(&self) -> bool { false }
 }
 
+/// A handshake operation returning a `WouldBlock` error is not finished yet,
+/// it will be called again when the stub is ready.
 pub trait Handshake {
+    /// Runs whatever the transport must do before the SP handshake, like securing the stream.
+    /// Returns the readiness to wait for while not done, or `None` once finished.
+    fn prepare_handshake(&mut self) -> Result<Option<Ready>> { Ok(None) }
     fn send_handshake(&mut self, pids: (u16, u16)) -> Result<()>;
     fn recv_handshake(&mut self, pids: (u16, u16)) -> Result<()>;
+    /// Returns the readiness to wait for while the handshake is being received.
+    fn recv_handshake_interest(&self) -> Ready { Ready::readable() }
 }
 
 pub fn send_and_check_handshake<T:Write>(stream: &mut T, pids: (u16, u16)) -> Result<()> {
@@ -45,7 +58,7 @@ pub fn send_and_check_handshake<T:Write>(stream: &mut T, pids: (u16, u16)) -> Re
 
     match try!(stream.write(&handshake)) {
         8 => Ok(()),
-        _ => Err(would_block_io_error("failed to send handshake"))
+        _ => Err(other_io_error("failed to send handshake"))
     }
 }
 
@@ -89,6 +89,9 @@ pub struct TestStepStream {
     sensor: Rc<RefCell<TestStepStreamSensor>>,
     send_handshake_ok: bool,
     recv_handshake_ok: bool,
+    handshake_in_progress: bool,
+    prepare_interests: Vec<mio::Ready>,
+    recv_interest: mio::Ready,
     pending_send: bool,
     pending_recv: bool
 }
@@ -103,10 +106,22 @@ impl TestStepStream {
             sensor: sensor,
             send_handshake_ok: true,
             recv_handshake_ok: true,
+            handshake_in_progress: false,
+            prepare_interests: Vec::new(),
+            recv_interest: mio::Ready::readable(),
             pending_send: false,
             pending_recv: false
         }
     }
+    pub fn set_handshake_in_progress(&mut self, in_progress: bool) {
+        self.handshake_in_progress = in_progress;
+    }
+    pub fn set_prepare_interests(&mut self, interests: Vec<mio::Ready>) {
+        self.prepare_interests = interests;
+    }
+    pub fn set_recv_interest(&mut self, interest: mio::Ready) {
+        self.recv_interest = interest;
+    }
 }
 
 impl stub::AsyncPipeStub for TestStepStream {
@@ -134,14 +149,22 @@ impl Deref for TestStepStream {
 }
 
 impl stub::Handshake for TestStepStream {
+    fn prepare_handshake(&mut self) -> io::Result<Option<mio::Ready>> {
+        if self.prepare_interests.is_empty() { Ok(None) } else { Ok(Some(self.prepare_interests.remove(0))) }
+    }
     fn send_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
         self.sensor.borrow_mut().push_sent_handshake(pids);
+        if self.handshake_in_progress { return Err(would_block_io_error("test")); }
         if self.send_handshake_ok { Ok(()) } else { Err(other_io_error("test")) }
     }
     fn recv_handshake(&mut self, _: (u16, u16)) -> io::Result<()> {
         self.sensor.borrow_mut().push_received_handshake();
+        if self.handshake_in_progress { return Err(would_block_io_error("test")); }
         if self.recv_handshake_ok { Ok(()) } else { Err(other_io_error("test")) }
     }
+    fn recv_handshake_interest(&self) -> mio::Ready {
+        self.recv_interest
+    }
 }
 
 impl stub::Sender for TestStepStream {
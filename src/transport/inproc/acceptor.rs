@@ -0,0 +1,134 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use mio::{Registration, SetReadiness, Ready, PollOpt};
+
+use transport::pipe;
+use transport::acceptor::*;
+use super::pipe::{InprocPipe, Port};
+
+/// The part of an inproc acceptor the registry can reach to hand over incoming connections.
+pub struct Listener {
+    addr: String,
+    pids: (u16, u16),
+    backlog: Vec<InprocPipe>,
+    readiness: SetReadiness
+}
+
+pub struct InprocAcceptor {
+    addr: String,
+    listener: Rc<RefCell<Listener>>,
+    registration: Registration
+}
+
+impl Listener {
+    pub fn accept(&mut self, remote: &Rc<RefCell<Port>>) {
+        let remote_pids = remote.borrow().get_pids();
+
+        if !is_peer(self.pids, remote_pids) {
+            return remote.borrow_mut().refuse();
+        }
+
+        let pipe = InprocPipe::new(&self.addr, self.pids);
+
+        Port::link(remote, pipe.port());
+
+        self.backlog.push(pipe);
+        let _ = self.readiness.set_readiness(Ready::readable());
+    }
+
+    fn drain(&mut self) -> Vec<InprocPipe> {
+        self.backlog.drain(..).collect()
+    }
+}
+
+/// Checks what the SP handshake would: each side must speak the protocol the other expects.
+fn is_peer(local: (u16, u16), remote: (u16, u16)) -> bool {
+    let (proto_id, peer_proto_id) = local;
+    let (remote_proto_id, remote_peer_proto_id) = remote;
+
+    proto_id == remote_peer_proto_id && peer_proto_id == remote_proto_id
+}
+
+impl InprocAcceptor {
+    pub fn new(addr: &str, pids: (u16, u16)) -> InprocAcceptor {
+        let (registration, readiness) = Registration::new2();
+        let listener = Listener {
+            addr: addr.to_owned(),
+            pids: pids,
+            backlog: Vec::new(),
+            readiness: readiness
+        };
+
+        InprocAcceptor {
+            addr: addr.to_owned(),
+            listener: Rc::new(RefCell::new(listener)),
+            registration: registration
+        }
+    }
+
+    pub fn listener(&self) -> &Rc<RefCell<Listener>> {
+        &self.listener
+    }
+
+    fn accept(&mut self, ctx: &mut Context) {
+        let pipes: Vec<Box<pipe::Pipe>> = self.listener.borrow_mut().
+            drain().
+            into_iter().
+            map(|p| Box::new(p) as Box<pipe::Pipe>).
+            collect();
+
+        if pipes.is_empty() == false {
+            ctx.raise(Event::Accepted(pipes));
+        }
+    }
+}
+
+impl Acceptor for InprocAcceptor {
+    fn ready(&mut self, ctx: &mut Context, events: Ready) {
+        if events.is_readable() {
+            self.accept(ctx);
+        }
+    }
+
+    fn open(&mut self, ctx: &mut Context) {
+        ctx.register(&self.registration, Ready::readable(), PollOpt::edge());
+        ctx.raise(Event::Opened);
+
+        self.accept(ctx);
+    }
+
+    fn close(&mut self, ctx: &mut Context) {
+        ctx.deregister(&self.registration);
+
+        super::unbind(&self.addr);
+        for pipe in self.listener.borrow_mut().drain() {
+            pipe.port().borrow_mut().unlink();
+        }
+
+        ctx.raise(Event::Closed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_peer;
+
+    #[test]
+    fn matching_protocols_are_peers() {
+        assert!(is_peer((48, 49), (49, 48)));
+        assert!(is_peer((112, 112), (112, 112)));
+    }
+
+    #[test]
+    fn mismatching_protocols_are_not_peers() {
+        assert!(!is_peer((48, 49), (48, 49)));
+        assert!(!is_peer((16, 16), (32, 33)));
+    }
+}
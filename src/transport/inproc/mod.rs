@@ -0,0 +1,115 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+//! This module provides a transport between sockets of the same session.
+//! Messages are handed over to the peer pipe without any serialization.
+
+mod pipe;
+mod acceptor;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::io;
+
+use self::pipe::{InprocPipe, Port};
+use self::acceptor::{InprocAcceptor, Listener};
+use transport::{Transport, Destination};
+use transport::pipe::Pipe;
+use transport::acceptor::Acceptor;
+use io_error::*;
+
+// All the endpoints of a session live in the thread running its event loop,
+// so the registry of bound addresses is kept per thread, hence per session.
+thread_local!(static REGISTRY: RefCell<Registry> = RefCell::new(Registry::default()));
+
+pub struct Inproc;
+
+#[derive(Default)]
+struct Registry {
+    listeners: HashMap<String, Rc<RefCell<Listener>>>,
+    pending: HashMap<String, Vec<Rc<RefCell<Port>>>>
+}
+
+impl Registry {
+    fn connect(&mut self, addr: &str, port: &Rc<RefCell<Port>>) {
+        if let Some(listener) = self.listeners.get(addr) {
+            return listener.borrow_mut().accept(port);
+        }
+
+        self.pending.entry(addr.to_owned()).or_default().push(port.clone());
+    }
+
+    fn bind(&mut self, addr: &str, listener: &Rc<RefCell<Listener>>) -> io::Result<()> {
+        if self.listeners.contains_key(addr) {
+            return Err(addr_in_use_io_error(addr));
+        }
+
+        if let Some(ports) = self.pending.remove(addr) {
+            let mut listener = listener.borrow_mut();
+
+            for port in ports {
+                listener.accept(&port);
+            }
+        }
+
+        self.listeners.insert(addr.to_owned(), listener.clone());
+
+        Ok(())
+    }
+
+    fn forget(&mut self, addr: &str, port: &Rc<RefCell<Port>>) {
+        let is_empty = match self.pending.get_mut(addr) {
+            Some(ports) => {
+                ports.retain(|p| !Rc::ptr_eq(p, port));
+                ports.is_empty()
+            },
+            None => false
+        };
+
+        if is_empty {
+            self.pending.remove(addr);
+        }
+    }
+
+    fn unbind(&mut self, addr: &str) {
+        self.listeners.remove(addr);
+    }
+}
+
+fn connect(addr: &str, port: &Rc<RefCell<Port>>) {
+    REGISTRY.with(|r| r.borrow_mut().connect(addr, port))
+}
+
+fn bind(addr: &str, listener: &Rc<RefCell<Listener>>) -> io::Result<()> {
+    REGISTRY.with(|r| r.borrow_mut().bind(addr, listener))
+}
+
+fn forget(addr: &str, port: &Rc<RefCell<Port>>) {
+    REGISTRY.with(|r| r.borrow_mut().forget(addr, port))
+}
+
+fn unbind(addr: &str) {
+    REGISTRY.with(|r| r.borrow_mut().unbind(addr))
+}
+
+impl Transport for Inproc {
+    fn connect(&self, dest: &Destination) -> io::Result<Box<Pipe>> {
+        let pipe = InprocPipe::new(dest.addr, dest.pids);
+
+        connect(dest.addr, pipe.port());
+
+        Ok(Box::new(pipe))
+    }
+
+    fn bind(&self, dest: &Destination) -> io::Result<Box<Acceptor>> {
+        let acceptor = InprocAcceptor::new(dest.addr, dest.pids);
+
+        try!(bind(dest.addr, acceptor.listener()));
+
+        Ok(Box::new(acceptor))
+    }
+}
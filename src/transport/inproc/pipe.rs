@@ -0,0 +1,461 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::{Rc, Weak};
+use std::io;
+
+use mio::{Registration, SetReadiness, Ready, PollOpt};
+
+use core::Message;
+use transport::pipe::{Pipe, Event, Context};
+use io_error::*;
+
+/// Number of messages a pipe can hold before its peer stops being send ready.
+const INBOX_CAPACITY: usize = 256;
+
+/// The half of an inproc link that is shared with the peer pipe and the registry.
+/// Each time something changes on a port, the owning pipe is notified via its readiness.
+pub struct Port {
+    pids: (u16, u16),
+    link: Link,
+    inbox: VecDeque<Message>,
+    readiness: SetReadiness
+}
+
+enum Link {
+    Pending,
+    Refused,
+    Established(Weak<RefCell<Port>>),
+    Lost
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Status {
+    Pending,
+    Refused,
+    Established,
+    Lost
+}
+
+pub struct InprocPipe {
+    addr: String,
+    port: Rc<RefCell<Port>>,
+    registration: Registration,
+    is_active: bool,
+    is_dead: bool,
+    can_send: bool,
+    can_recv: bool,
+    pending_recv: bool
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Port                                                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Port {
+    fn new(pids: (u16, u16), readiness: SetReadiness) -> Port {
+        Port {
+            pids: pids,
+            link: Link::Pending,
+            inbox: VecDeque::new(),
+            readiness: readiness
+        }
+    }
+
+    pub fn link(left: &Rc<RefCell<Port>>, right: &Rc<RefCell<Port>>) {
+        left.borrow_mut().on_linked(Rc::downgrade(right));
+        right.borrow_mut().on_linked(Rc::downgrade(left));
+    }
+
+    fn on_linked(&mut self, peer: Weak<RefCell<Port>>) {
+        self.link = Link::Established(peer);
+        self.notify();
+    }
+
+    pub fn refuse(&mut self) {
+        self.link = Link::Refused;
+        self.notify();
+    }
+
+    pub fn unlink(&mut self) {
+        if let Some(peer) = self.peer() {
+            peer.borrow_mut().on_peer_lost();
+        }
+
+        self.link = Link::Lost;
+        self.inbox.clear();
+    }
+
+    fn on_peer_lost(&mut self) {
+        self.link = Link::Lost;
+        self.notify();
+    }
+
+    pub fn get_pids(&self) -> (u16, u16) {
+        self.pids
+    }
+
+    fn status(&self) -> Status {
+        match self.link {
+            Link::Pending => Status::Pending,
+            Link::Refused => Status::Refused,
+            Link::Established(ref peer) => {
+                if peer.upgrade().is_some() {
+                    Status::Established
+                } else {
+                    Status::Lost
+                }
+            },
+            Link::Lost => Status::Lost
+        }
+    }
+
+    fn peer(&self) -> Option<Rc<RefCell<Port>>> {
+        match self.link {
+            Link::Established(ref peer) => peer.upgrade(),
+            _ => None
+        }
+    }
+
+    fn push(&mut self, msg: Message) {
+        self.inbox.push_back(msg);
+        self.notify();
+    }
+
+    fn pop(&mut self) -> Option<Message> {
+        let was_full = !self.has_room();
+        let msg = self.inbox.pop_front();
+
+        if was_full && msg.is_some() {
+            if let Some(peer) = self.peer() {
+                peer.borrow().notify();
+            }
+        }
+
+        msg
+    }
+
+    fn has_msg(&self) -> bool {
+        !self.inbox.is_empty()
+    }
+
+    fn has_room(&self) -> bool {
+        self.inbox.len() < INBOX_CAPACITY
+    }
+
+    fn peer_has_room(&self) -> bool {
+        self.peer().map_or(false, |peer| peer.borrow().has_room())
+    }
+
+    fn notify(&self) {
+        let _ = self.readiness.set_readiness(Ready::readable());
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Pipe                                                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+impl InprocPipe {
+    pub fn new(addr: &str, pids: (u16, u16)) -> InprocPipe {
+        let (registration, readiness) = Registration::new2();
+
+        InprocPipe {
+            addr: addr.to_owned(),
+            port: Rc::new(RefCell::new(Port::new(pids, readiness))),
+            registration: registration,
+            is_active: false,
+            is_dead: false,
+            can_send: false,
+            can_recv: false,
+            pending_recv: false
+        }
+    }
+
+    pub fn port(&self) -> &Rc<RefCell<Port>> {
+        &self.port
+    }
+
+    fn sync(&mut self, ctx: &mut Context) {
+        if self.is_dead {
+            return;
+        }
+
+        let status = self.port.borrow().status();
+
+        match status {
+            Status::Pending     => {},
+            Status::Refused     => self.fail(ctx, invalid_data_io_error("received bad handshake")),
+            Status::Lost        => self.fail(ctx, other_io_error("peer closed")),
+            Status::Established => {
+                self.activate(ctx);
+                self.check_send(ctx);
+                self.check_recv(ctx);
+            }
+        }
+    }
+
+    fn activate(&mut self, ctx: &mut Context) {
+        if !self.is_active {
+            self.is_active = true;
+            ctx.raise(Event::Opened);
+        }
+    }
+
+    fn check_send(&mut self, ctx: &mut Context) {
+        if !self.can_send && self.port.borrow().peer_has_room() {
+            self.can_send = true;
+            ctx.raise(Event::CanSend(true));
+        }
+    }
+
+    fn check_recv(&mut self, ctx: &mut Context) {
+        if self.pending_recv {
+            let received = self.port.borrow_mut().pop();
+
+            if let Some(msg) = received {
+                self.pending_recv = false;
+                ctx.raise(Event::Received(msg));
+            }
+        }
+
+        if !self.pending_recv && !self.can_recv && self.port.borrow().has_msg() {
+            self.can_recv = true;
+            ctx.raise(Event::CanRecv(true));
+        }
+    }
+
+    fn fail(&mut self, ctx: &mut Context, err: io::Error) {
+        self.is_dead = true;
+        self.can_send = false;
+        self.can_recv = false;
+
+        ctx.raise(Event::Error(err));
+    }
+}
+
+impl Pipe for InprocPipe {
+    fn ready(&mut self, ctx: &mut Context, _: Ready) {
+        self.sync(ctx)
+    }
+
+    fn open(&mut self, ctx: &mut Context) {
+        ctx.register(&self.registration, Ready::readable(), PollOpt::edge());
+
+        self.sync(ctx)
+    }
+
+    fn close(&mut self, ctx: &mut Context) {
+        ctx.deregister(&self.registration);
+
+        self.is_dead = true;
+        self.port.borrow_mut().unlink();
+        super::forget(&self.addr, &self.port);
+
+        ctx.raise(Event::Closed);
+    }
+
+    fn send(&mut self, ctx: &mut Context, msg: Rc<Message>) {
+        self.can_send = false;
+
+        let peer = self.port.borrow().peer();
+
+        if let Some(peer) = peer {
+            peer.borrow_mut().push(into_message(msg));
+            ctx.raise(Event::Sent);
+
+            self.check_send(ctx);
+        } else {
+            self.fail(ctx, other_io_error("peer closed"));
+        }
+    }
+
+    fn recv(&mut self, ctx: &mut Context) {
+        self.can_recv = false;
+
+        let received = self.port.borrow_mut().pop();
+
+        if let Some(msg) = received {
+            ctx.raise(Event::Received(msg));
+
+            self.check_recv(ctx);
+        } else {
+            self.pending_recv = true;
+        }
+    }
+}
+
+/// Protocols expect the header of a received message to still be at the start of the body,
+/// as it would be when read from a stream. The message can also be shared by several pipes
+/// when broadcasting, in which case each peer gets its own copy.
+fn into_message(msg: Rc<Message>) -> Message {
+    match Rc::try_unwrap(msg) {
+        Ok(msg) => {
+            if msg.get_header().is_empty() {
+                msg
+            } else {
+                let (mut header, body) = msg.split();
+
+                header.extend_from_slice(&body);
+                Message::from_body(header)
+            }
+        },
+        Err(msg) => {
+            let mut payload = Vec::with_capacity(msg.len());
+
+            payload.extend_from_slice(msg.get_header());
+            payload.extend_from_slice(msg.get_body());
+            Message::from_body(payload)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use core::Message;
+    use transport::pipe;
+    use transport::pipe::Pipe;
+    use transport::tests::*;
+
+    use super::*;
+
+    fn new_linked_pipes() -> (InprocPipe, InprocPipe) {
+        let left = InprocPipe::new("test", (1, 2));
+        let right = InprocPipe::new("test", (2, 1));
+
+        Port::link(left.port(), right.port());
+
+        (left, right)
+    }
+
+    #[test]
+    fn open_while_pending_should_register_and_raise_nothing() {
+        let mut pipe = InprocPipe::new("test", (1, 2));
+        let mut ctx = TestPipeContext::new();
+
+        pipe.open(&mut ctx);
+
+        assert_eq!(1, ctx.get_registrations().len());
+        assert_eq!(0, ctx.get_raised_events().len());
+    }
+
+    #[test]
+    fn open_when_linked_should_raise_opened_and_can_send() {
+        let (mut left, _right) = new_linked_pipes();
+        let mut ctx = TestPipeContext::new();
+
+        left.open(&mut ctx);
+
+        let evts = ctx.get_raised_events();
+        assert_eq!(2, evts.len());
+        assert!(match evts[0] { pipe::Event::Opened => true, _ => false });
+        assert!(match evts[1] { pipe::Event::CanSend(true) => true, _ => false });
+    }
+
+    #[test]
+    fn open_when_refused_should_raise_an_error() {
+        let mut pipe = InprocPipe::new("test", (1, 2));
+        let mut ctx = TestPipeContext::new();
+
+        pipe.port().borrow_mut().refuse();
+        pipe.open(&mut ctx);
+
+        let evts = ctx.get_raised_events();
+        assert_eq!(1, evts.len());
+        assert!(match evts[0] { pipe::Event::Error(_) => true, _ => false });
+    }
+
+    #[test]
+    fn send_should_hand_the_message_over_to_the_peer() {
+        let (mut left, mut right) = new_linked_pipes();
+        let mut left_ctx = TestPipeContext::new();
+        let mut right_ctx = TestPipeContext::new();
+
+        left.open(&mut left_ctx);
+        right.open(&mut right_ctx);
+        left.send(&mut left_ctx, Rc::new(Message::from_body(vec![65, 66, 67])));
+        right.ready(&mut right_ctx, Ready::readable());
+        right.recv(&mut right_ctx);
+
+        let left_evts = left_ctx.get_raised_events();
+        assert_eq!(4, left_evts.len());
+        assert!(match left_evts[2] { pipe::Event::Sent => true, _ => false });
+        assert!(match left_evts[3] { pipe::Event::CanSend(true) => true, _ => false });
+
+        let right_evts = right_ctx.get_raised_events();
+        assert_eq!(4, right_evts.len());
+        assert!(match right_evts[2] { pipe::Event::CanRecv(true) => true, _ => false });
+        assert!(match right_evts[3] {
+            pipe::Event::Received(ref msg) => msg.get_body() == [65, 66, 67],
+            _ => false
+        });
+    }
+
+    #[test]
+    fn recv_before_any_message_should_complete_when_one_arrives() {
+        let (mut left, mut right) = new_linked_pipes();
+        let mut left_ctx = TestPipeContext::new();
+        let mut right_ctx = TestPipeContext::new();
+
+        left.open(&mut left_ctx);
+        right.open(&mut right_ctx);
+        right.recv(&mut right_ctx);
+        left.send(&mut left_ctx, Rc::new(Message::from_body(vec![65, 66, 67])));
+        right.ready(&mut right_ctx, Ready::readable());
+
+        let right_evts = right_ctx.get_raised_events();
+        assert_eq!(3, right_evts.len());
+        assert!(match right_evts[2] { pipe::Event::Received(_) => true, _ => false });
+    }
+
+    #[test]
+    fn send_should_stop_being_ready_when_peer_inbox_is_full() {
+        let (mut left, mut right) = new_linked_pipes();
+        let mut left_ctx = TestPipeContext::new();
+        let mut right_ctx = TestPipeContext::new();
+
+        left.open(&mut left_ctx);
+        right.open(&mut right_ctx);
+
+        for _ in 0..INBOX_CAPACITY {
+            left.send(&mut left_ctx, Rc::new(Message::new()));
+        }
+
+        let sends = left_ctx.get_raised_events().iter().filter(|e| match **e { pipe::Event::CanSend(true) => true, _ => false }).count();
+        assert_eq!(INBOX_CAPACITY, sends);
+
+        right.ready(&mut right_ctx, Ready::readable());
+        right.recv(&mut right_ctx);
+        left.ready(&mut left_ctx, Ready::readable());
+
+        let sends = left_ctx.get_raised_events().iter().filter(|e| match **e { pipe::Event::CanSend(true) => true, _ => false }).count();
+        assert_eq!(INBOX_CAPACITY + 1, sends);
+    }
+
+    #[test]
+    fn close_should_cause_an_error_on_the_peer() {
+        let (mut left, mut right) = new_linked_pipes();
+        let mut left_ctx = TestPipeContext::new();
+        let mut right_ctx = TestPipeContext::new();
+
+        left.open(&mut left_ctx);
+        right.open(&mut right_ctx);
+        left.close(&mut left_ctx);
+        right.ready(&mut right_ctx, Ready::readable());
+
+        assert_eq!(1, left_ctx.get_deregistrations());
+        assert!(match *left_ctx.get_raised_events().last().unwrap() { pipe::Event::Closed => true, _ => false });
+        assert!(match *right_ctx.get_raised_events().last().unwrap() { pipe::Event::Error(_) => true, _ => false });
+    }
+}
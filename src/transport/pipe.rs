@@ -21,6 +21,7 @@ pub enum Command {
 }
 
 pub enum Event {
+    Connected,
     Opened,
     Closed,
     CanSend(bool),
@@ -36,6 +37,10 @@ pub trait Pipe {
     fn close(&mut self, ctx: &mut Context);
     fn send(&mut self, ctx: &mut Context, msg: Rc<Message>);
     fn recv(&mut self, ctx: &mut Context);
+    /// Local address of the underlying connection, if the transport has one.
+    fn local_addr(&self) -> Option<String> { None }
+    /// Address of the peer, if the transport has one and the connection is established.
+    fn remote_addr(&self) -> Option<String> { None }
 }
 
 pub trait Context : EndpointRegistrar + fmt::Debug {
@@ -68,6 +73,7 @@ impl fmt::Debug for Event {
 impl Event {
     pub fn name(&self) -> &'static str {
         match *self {
+            Event::Connected   => "Connected",
             Event::Opened      => "Opened",
             Event::Closed      => "Closed",
             Event::CanSend(_)  => "CanSend",
@@ -81,4 +81,8 @@ impl acceptor::Acceptor for TcpAcceptor {
         ctx.deregister(&self.listener);
         ctx.raise(Event::Closed);
     }
+
+    fn local_addr(&self) -> Option<String> {
+        self.listener.local_addr().ok().map(|addr| addr.to_string())
+    }
 }
\ No newline at end of file
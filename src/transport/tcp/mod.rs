@@ -6,7 +6,7 @@
 
 mod stub;
 mod send;
-mod recv;
+pub mod recv;
 mod acceptor;
 
 use std::str::FromStr;
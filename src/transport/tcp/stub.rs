@@ -143,6 +143,18 @@ impl Handshake for TcpPipeStub {
 }
 
 impl AsyncPipeStub for TcpPipeStub {
+    fn local_addr(&self) -> Option<String> {
+        self.stream.local_addr().ok().map(|addr| addr.to_string())
+    }
+
+    fn peer_addr(&self) -> Option<String> {
+        self.stream.peer_addr().ok().map(|addr| addr.to_string())
+    }
+
+    fn is_connected(&self) -> bool {
+        self.stream.peer_addr().is_ok()
+    }
+
     #[cfg(windows)]
     fn read_and_write_void(&mut self) {
         let mut buffer: [u8; 0] = [0; 0];
@@ -0,0 +1,99 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::sync::Arc;
+use std::io;
+
+use mio;
+use mio::tcp::{TcpListener, TcpStream};
+
+use rustls::{ServerConfig, ServerConnection, Connection};
+
+use transport::*;
+use transport::acceptor::*;
+use transport::async::AsyncPipe;
+use super::stream::TlsStream;
+use super::stub::TlsPipeStub;
+use io_error::*;
+
+pub struct TlsAcceptor {
+    listener: TcpListener,
+    config: Arc<ServerConfig>,
+    proto_ids: (u16, u16),
+    no_delay: bool,
+    recv_max_size: u64
+}
+
+impl TlsAcceptor {
+
+    pub fn new(l: TcpListener, config: Arc<ServerConfig>, dest: &Destination) -> TlsAcceptor {
+        TlsAcceptor {
+            listener: l,
+            config: config,
+            proto_ids: dest.pids,
+            no_delay: dest.tcp_no_delay,
+            recv_max_size: dest.recv_max_size
+        }
+    }
+
+    fn accept(&mut self, ctx: &mut Context) {
+        let mut pipes = Vec::new();
+
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    let _ = stream.set_nodelay(self.no_delay);
+
+                    match self.create_pipe(stream) {
+                        Ok(pipe) => pipes.push(pipe),
+                        Err(e) => ctx.raise(Event::Error(e))
+                    }
+                },
+                Err(e) => {
+                    if e.kind() == io::ErrorKind::WouldBlock {
+                        break;
+                    } else {
+                        ctx.raise(Event::Error(e));
+                    }
+                }
+            }
+        }
+
+        if pipes.is_empty() == false {
+            ctx.raise(Event::Accepted(pipes));
+        }
+    }
+
+    fn create_pipe(&self, stream: TcpStream) -> io::Result<Box<pipe::Pipe>> {
+        let conn = try!(ServerConnection::new(self.config.clone()).map_err(other_io_error));
+        let stream = TlsStream::new(Connection::Server(conn), stream);
+        let stub = TlsPipeStub::new(stream, self.recv_max_size);
+
+        Ok(Box::new(AsyncPipe::new(stub, self.proto_ids)))
+    }
+}
+
+impl acceptor::Acceptor for TlsAcceptor {
+    fn ready(&mut self, ctx: &mut Context, events: mio::Ready) {
+        if events.is_readable() {
+            self.accept(ctx);
+        }
+    }
+
+    fn open(&mut self, ctx: &mut Context) {
+        ctx.register(&self.listener, mio::Ready::readable(), mio::PollOpt::edge());
+        ctx.raise(Event::Opened);
+    }
+
+    fn close(&mut self, ctx: &mut Context) {
+        ctx.deregister(&self.listener);
+        ctx.raise(Event::Closed);
+    }
+
+    fn local_addr(&self) -> Option<String> {
+        self.listener.local_addr().ok().map(|addr| addr.to_string())
+    }
+}
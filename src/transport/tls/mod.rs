@@ -0,0 +1,271 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+//! This module provides a TLS over TCP transport, the TLS handshake being completed
+//! before the SP one. Messages are framed exactly like with the TCP transport.
+
+mod stream;
+mod stub;
+mod send;
+mod acceptor;
+
+use std::str::FromStr;
+use std::convert::TryFrom;
+use std::sync::Arc;
+use std::io;
+use std::net;
+use std::net::ToSocketAddrs;
+
+use mio::tcp::{TcpListener, TcpStream};
+
+use rustls;
+use rustls::{ClientConfig, ServerConfig, RootCertStore, ClientConnection, Connection};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, DnsName};
+use rustls::server::WebPkiClientVerifier;
+use rustls_pemfile;
+
+use self::stream::TlsStream;
+use self::stub::TlsPipeStub;
+use self::acceptor::TlsAcceptor;
+use transport::{Transport, Destination};
+use transport::pipe::Pipe;
+use transport::acceptor::Acceptor;
+use transport::async::AsyncPipe;
+use io_error::*;
+
+/// Secures TCP connections with certificates given as PEM.
+/// The same certificate is used by bound endpoints to identify themselves,
+/// and by connected endpoints when the remote side requires client authentication.
+/// Connected endpoints authenticate the server against the host of the url.
+pub struct Tls {
+    identity: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+    client_ca: Option<Arc<RootCertStore>>,
+    trusted: RootCertStore
+}
+
+impl Tls {
+    /// Creates a transport from a certificate chain and its private key.
+    /// When a client authority is specified, bound endpoints only accept
+    /// clients presenting a certificate signed by it.
+    pub fn new(cert_pem: &[u8], key_pem: &[u8], client_ca_pem: Option<&[u8]>) -> io::Result<Tls> {
+        let client_ca = match client_ca_pem {
+            Some(pem) => Some(Arc::new(try!(parse_roots(pem)))),
+            None => None
+        };
+        let identity = (try!(parse_certs(cert_pem)), try!(parse_key(key_pem)));
+        let tls = Tls {
+            identity: Some(identity),
+            client_ca: client_ca,
+            trusted: RootCertStore::empty()
+        };
+
+        try!(tls.server_config());
+
+        Ok(tls)
+    }
+
+    /// Creates a transport without certificate, that can only be used by connected endpoints.
+    /// No certificate is presented when the remote side requires client authentication.
+    pub fn client() -> Tls {
+        Tls {
+            identity: None,
+            client_ca: None,
+            trusted: RootCertStore::empty()
+        }
+    }
+
+    /// Adds authorities used by connected endpoints to authenticate the server.
+    pub fn trust(mut self, ca_pem: &[u8]) -> io::Result<Tls> {
+        for cert in try!(parse_certs(ca_pem)) {
+            try!(self.trusted.add(cert).map_err(invalid_input_io_error));
+        }
+
+        Ok(self)
+    }
+
+    fn server_config(&self) -> io::Result<Arc<ServerConfig>> {
+        let (certs, key) = match self.identity {
+            Some((ref certs, ref key)) => (certs.clone(), key.clone_key()),
+            None => return Err(invalid_input_io_error("no certificate to identify the bound endpoint"))
+        };
+        let provider = crypto_provider();
+        let builder = try!(ServerConfig::builder_with_provider(provider.clone()).
+            with_safe_default_protocol_versions().
+            map_err(invalid_input_io_error));
+        let builder = match self.client_ca {
+            Some(ref roots) => {
+                let verifier = try!(WebPkiClientVerifier::builder_with_provider(roots.clone(), provider).
+                    build().
+                    map_err(invalid_input_io_error));
+
+                builder.with_client_cert_verifier(verifier)
+            },
+            None => builder.with_no_client_auth()
+        };
+        let config = try!(builder.
+            with_single_cert(certs, key).
+            map_err(invalid_input_io_error));
+
+        Ok(Arc::new(config))
+    }
+
+    fn client_config(&self) -> io::Result<Arc<ClientConfig>> {
+        let builder = try!(ClientConfig::builder_with_provider(crypto_provider()).
+            with_safe_default_protocol_versions().
+            map_err(invalid_input_io_error)).
+            with_root_certificates(self.trusted.clone());
+        let config = match self.identity {
+            Some((ref certs, ref key)) => try!(builder.
+                with_client_auth_cert(certs.clone(), key.clone_key()).
+                map_err(invalid_input_io_error)),
+            None => builder.with_no_client_auth()
+        };
+
+        Ok(Arc::new(config))
+    }
+
+    fn connect(&self, host: &str, addr: &net::SocketAddr, dest: &Destination) -> io::Result<Box<Pipe>> {
+        let server_name = try!(parse_server_name(host));
+        let conn = try!(ClientConnection::new(try!(self.client_config()), server_name).map_err(invalid_input_io_error));
+        let stream = try!(TcpStream::connect(addr));
+        try!(stream.set_nodelay(dest.tcp_no_delay));
+        let stream = TlsStream::new(Connection::Client(conn), stream);
+        let stub = TlsPipeStub::new(stream, dest.recv_max_size);
+        let pipe = AsyncPipe::new(stub, dest.pids);
+
+        Ok(Box::new(pipe))
+    }
+
+    fn bind(&self, addr: &net::SocketAddr, dest: &Destination) -> io::Result<Box<Acceptor>> {
+        let config = try!(self.server_config());
+        let listener = try!(TcpListener::bind(addr));
+        let acceptor = TlsAcceptor::new(listener, config, dest);
+
+        Ok(Box::new(acceptor))
+    }
+}
+
+fn crypto_provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn parse_certs(pem: &[u8]) -> io::Result<Vec<CertificateDer<'static>>> {
+    let mut reader = pem;
+    let certs: Vec<CertificateDer<'static>> = try!(rustls_pemfile::certs(&mut reader).collect());
+
+    if certs.is_empty() {
+        Err(invalid_input_io_error("no certificate found"))
+    } else {
+        Ok(certs)
+    }
+}
+
+fn parse_key(pem: &[u8]) -> io::Result<PrivateKeyDer<'static>> {
+    let mut reader = pem;
+
+    match try!(rustls_pemfile::private_key(&mut reader)) {
+        Some(key) => Ok(key),
+        None => Err(invalid_input_io_error("no private key found"))
+    }
+}
+
+/// Splits the host from the port, removing the brackets around an IPv6 address.
+fn split_host(addr: &str) -> io::Result<&str> {
+    match addr.rfind(':') {
+        Some(i) => Ok(addr[..i].trim_start_matches('[').trim_end_matches(']')),
+        None => Err(invalid_input_io_error(addr.to_owned()))
+    }
+}
+
+fn parse_server_name(host: &str) -> io::Result<ServerName<'static>> {
+    match net::IpAddr::from_str(host) {
+        Ok(ip) => Ok(ServerName::from(ip)),
+        Err(_) => DnsName::try_from(host.to_owned()).
+            map(ServerName::DnsName).
+            map_err(invalid_input_io_error)
+    }
+}
+
+fn resolve(addr: &str) -> io::Result<net::SocketAddr> {
+    match try!(addr.to_socket_addrs()).next() {
+        Some(addr) => Ok(addr),
+        None => Err(invalid_input_io_error(addr.to_owned()))
+    }
+}
+
+fn parse_roots(pem: &[u8]) -> io::Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+
+    for cert in try!(parse_certs(pem)) {
+        try!(roots.add(cert).map_err(invalid_input_io_error));
+    }
+
+    Ok(roots)
+}
+
+impl Transport for Tls {
+    fn connect(&self, dest: &Destination) -> io::Result<Box<Pipe>> {
+        let host = try!(split_host(dest.addr));
+        let addr = try!(resolve(dest.addr));
+
+        self.connect(host, &addr, dest)
+    }
+
+    fn bind(&self, dest: &Destination) -> io::Result<Box<Acceptor>> {
+        match net::SocketAddr::from_str(dest.addr) {
+            Ok(addr) => self.bind(&addr, dest),
+            Err(_) => Err(invalid_input_io_error(dest.addr))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use rustls::pki_types::ServerName;
+
+    use super::*;
+
+    #[test]
+    fn missing_certificate_should_be_refused() {
+        let err = Tls::new(b"", b"", None).err().expect("Tls::new should have failed !");
+
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    }
+
+    #[test]
+    fn certificate_and_key_should_be_accepted() {
+        let cert = include_bytes!("../../../test/certs/server.pem");
+        let key = include_bytes!("../../../test/certs/server.key");
+        let ca = include_bytes!("../../../test/certs/ca.pem");
+
+        Tls::new(cert, key, Some(ca)).unwrap().trust(ca).unwrap();
+    }
+
+    #[test]
+    fn client_without_certificate_should_not_bind() {
+        let err = Tls::client().server_config().err().expect("server_config should have failed !");
+
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    }
+
+    #[test]
+    fn server_name_should_keep_the_host_of_the_url() {
+        assert_eq!("localhost", split_host("localhost:5454").unwrap());
+        assert_eq!("::1", split_host("[::1]:5454").unwrap());
+
+        match parse_server_name("localhost").unwrap() {
+            ServerName::DnsName(name) => assert_eq!("localhost", name.as_ref()),
+            _ => panic!("a dns name was expected")
+        }
+        match parse_server_name("127.0.0.1").unwrap() {
+            ServerName::IpAddress(_) => {},
+            _ => panic!("an ip address was expected")
+        }
+    }
+}
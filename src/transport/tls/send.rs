@@ -0,0 +1,69 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::rc::Rc;
+use std::io;
+
+use byteorder::{ BigEndian, ByteOrder };
+
+use core::Message;
+use transport::async::stub::*;
+
+/// Writes the message in a single buffer, so it is encrypted in records of its own,
+/// and completes only once the records have been written to the socket.
+pub struct SendOperation {
+    buffer: Vec<u8>,
+    written: usize
+}
+
+impl SendOperation {
+    pub fn new(msg: Rc<Message>) -> SendOperation {
+        let mut buffer = vec![0u8; 8];
+
+        BigEndian::write_u64(&mut buffer, msg.len() as u64);
+        buffer.extend_from_slice(msg.get_header());
+        buffer.extend_from_slice(msg.get_body());
+
+        SendOperation {
+            buffer: buffer,
+            written: 0
+        }
+    }
+
+    pub fn run<T:io::Write>(&mut self, stream: &mut T) -> io::Result<bool> {
+        if !try!(stream.write_buffer(&self.buffer, &mut self.written)) {
+            return Ok(false);
+        }
+
+        match stream.flush() {
+            Ok(()) => Ok(true),
+            Err(e) => {
+                if e.kind() == io::ErrorKind::WouldBlock {
+                    Ok(false)
+                } else {
+                    Err(e)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[test]
+    fn message_should_be_prefixed_by_its_length() {
+        let msg = Message::from_header_and_body(vec![1, 2], vec![65, 66, 67]);
+        let mut operation = SendOperation::new(Rc::new(msg));
+        let mut stream = Vec::new();
+
+        assert!(operation.run(&mut stream).unwrap());
+        assert_eq!(vec![0, 0, 0, 0, 0, 0, 0, 5, 1, 2, 65, 66, 67], stream);
+    }
+}
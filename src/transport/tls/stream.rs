@@ -0,0 +1,203 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::io;
+use std::io::{Read, Write};
+
+use byteorder::{ BigEndian, ByteOrder };
+
+use mio::Ready;
+use mio::tcp::{TcpStream, Shutdown};
+
+use rustls::Connection;
+
+use io_error::*;
+
+const RECORD_HEADER_LEN: usize = 5;
+const RECORD_MAX_LEN: usize = RECORD_HEADER_LEN + 16384 + 2048;
+
+/// Plaintext view of a TLS connection over a non-blocking socket.
+///
+/// Records are read one at a time, and only when the decrypted data has been consumed.
+/// A record may hold more than one message, so the plaintext left behind is tracked:
+/// it does not make the socket readable.
+pub struct TlsStream {
+    conn: Connection,
+    socket: TcpStream,
+    record: Vec<u8>,
+    record_read: usize,
+    plaintext: usize
+}
+
+impl TlsStream {
+    pub fn new(conn: Connection, socket: TcpStream) -> TlsStream {
+        TlsStream {
+            conn: conn,
+            socket: socket,
+            record: vec![0u8; RECORD_HEADER_LEN],
+            record_read: 0,
+            plaintext: 0
+        }
+    }
+
+    /// Returns whether decrypted data is waiting to be read.
+    pub fn has_plaintext(&self) -> bool {
+        self.plaintext > 0
+    }
+
+    pub fn socket(&self) -> &TcpStream {
+        &self.socket
+    }
+
+    #[cfg(windows)]
+    pub fn socket_mut(&mut self) -> &mut TcpStream {
+        &mut self.socket
+    }
+
+    /// Advances the TLS handshake, returns the readiness it is waiting for or `None` when done.
+    pub fn handshake(&mut self) -> io::Result<Option<Ready>> {
+        loop {
+            if !try!(self.flush_tls()) {
+                return Ok(Some(Ready::writable()));
+            }
+
+            if !self.conn.is_handshaking() {
+                return Ok(None);
+            }
+
+            if !try!(self.read_record()) {
+                return Ok(Some(Ready::readable()));
+            }
+        }
+    }
+
+    pub fn shutdown(&mut self) {
+        self.conn.send_close_notify();
+
+        let _ = self.flush_tls();
+        let _ = self.socket.shutdown(Shutdown::Both);
+    }
+
+    /// Writes the pending records, returns whether they were all written.
+    fn flush_tls(&mut self) -> io::Result<bool> {
+        while self.conn.wants_write() {
+            match self.conn.write_tls(&mut self.socket) {
+                Ok(_) => {},
+                Err(e) => {
+                    if e.kind() == io::ErrorKind::WouldBlock {
+                        return Ok(false);
+                    } else {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Reads and processes a single record, returns whether it was complete.
+    fn read_record(&mut self) -> io::Result<bool> {
+        loop {
+            let record_len = if self.record_read < RECORD_HEADER_LEN {
+                RECORD_HEADER_LEN
+            } else {
+                RECORD_HEADER_LEN + BigEndian::read_u16(&self.record[3..5]) as usize
+            };
+
+            if self.record_read == record_len && record_len > RECORD_HEADER_LEN {
+                break;
+            }
+
+            if record_len > RECORD_MAX_LEN {
+                return Err(invalid_data_io_error("tls record is too long"));
+            }
+
+            self.record.resize(record_len, 0);
+
+            match self.socket.read(&mut self.record[self.record_read..record_len]) {
+                Ok(0) => return Err(other_io_error("connection closed")),
+                Ok(x) => self.record_read += x,
+                Err(e) => {
+                    if e.kind() == io::ErrorKind::WouldBlock {
+                        return Ok(false);
+                    } else {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        let res = self.process_record();
+
+        self.record.truncate(RECORD_HEADER_LEN);
+        self.record_read = 0;
+
+        // processing may have produced alerts or handshake messages to send back
+        let _ = self.flush_tls();
+
+        res.map(|_| true)
+    }
+
+    fn process_record(&mut self) -> io::Result<()> {
+        let mut record = &self.record[..self.record_read];
+
+        while !record.is_empty() {
+            try!(self.conn.read_tls(&mut record));
+
+            let state = try!(self.conn.process_new_packets().map_err(invalid_data_io_error));
+
+            self.plaintext = state.plaintext_bytes_to_read();
+        }
+
+        Ok(())
+    }
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.conn.reader().read(buf) {
+                Ok(x) => {
+                    self.plaintext = self.plaintext.saturating_sub(x);
+
+                    return Ok(x)
+                },
+                Err(e) => {
+                    if e.kind() != io::ErrorKind::WouldBlock {
+                        return Err(e);
+                    }
+                }
+            }
+
+            if !try!(self.read_record()) {
+                return Err(would_block_io_error("no tls record available"));
+            }
+        }
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !try!(self.flush_tls()) {
+            return Err(would_block_io_error("tls records are pending"));
+        }
+
+        let written = try!(self.conn.writer().write(buf));
+
+        try!(self.flush_tls());
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if try!(self.flush_tls()) {
+            Ok(())
+        } else {
+            Err(would_block_io_error("tls records are pending"))
+        }
+    }
+}
@@ -0,0 +1,180 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::ops::Deref;
+use std::rc::Rc;
+use std::io;
+use std::io::Write;
+#[cfg(windows)]
+use std::io::Read;
+
+use mio;
+
+use core::Message;
+use transport::tls::stream::TlsStream;
+use transport::tls::send::SendOperation;
+use transport::tcp::recv::RecvOperation;
+use transport::async::stub::*;
+use io_error::*;
+
+/*****************************************************************************/
+/*                                                                           */
+/* TlsPipeStub                                                               */
+/*                                                                           */
+/*****************************************************************************/
+
+pub struct TlsPipeStub {
+    stream: TlsStream,
+    recv_max_size: u64,
+    send_operation: Option<SendOperation>,
+    recv_operation: Option<RecvOperation>,
+    is_handshake_sent: bool
+}
+
+impl Deref for TlsPipeStub {
+    type Target = mio::Evented;
+    fn deref(&self) -> &Self::Target {
+        self.stream.socket()
+    }
+}
+
+impl TlsPipeStub {
+    pub fn new(stream: TlsStream, recv_max_size: u64) -> TlsPipeStub {
+        TlsPipeStub {
+            stream: stream,
+            recv_max_size: recv_max_size,
+            send_operation: None,
+            recv_operation: None,
+            is_handshake_sent: false
+        }
+    }
+
+    fn run_send_operation(&mut self, mut send_operation: SendOperation) -> io::Result<bool> {
+        if try!(send_operation.run(&mut self.stream)) {
+            Ok(true)
+        } else {
+            self.send_operation = Some(send_operation);
+            Ok(false)
+        }
+    }
+
+    fn run_recv_operation(&mut self, mut recv_operation: RecvOperation) -> io::Result<Option<Message>> {
+        match try!(recv_operation.run(&mut self.stream)) {
+            Some(msg) => Ok(Some(msg)),
+            None => {
+                self.recv_operation = Some(recv_operation);
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Drop for TlsPipeStub {
+    fn drop(&mut self) {
+        self.stream.shutdown();
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Sender for TlsPipeStub                                                    */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Sender for TlsPipeStub {
+    fn start_send(&mut self, msg: Rc<Message>) -> io::Result<bool> {
+        let send_operation = SendOperation::new(msg);
+
+        self.run_send_operation(send_operation)
+    }
+
+    fn resume_send(&mut self) -> io::Result<bool> {
+        if let Some(send_operation) = self.send_operation.take() {
+            self.run_send_operation(send_operation)
+        } else {
+            Err(other_io_error("Cannot resume send: no pending operation"))
+        }
+    }
+
+    fn has_pending_send(&self) -> bool {
+        self.send_operation.is_some()
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Receiver for TlsPipeStub                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Receiver for TlsPipeStub {
+    fn start_recv(&mut self) -> io::Result<Option<Message>> {
+        let recv_operation = RecvOperation::new(self.recv_max_size);
+
+        self.run_recv_operation(recv_operation)
+    }
+
+    fn resume_recv(&mut self) -> io::Result<Option<Message>> {
+        if let Some(recv_operation) = self.recv_operation.take() {
+            self.run_recv_operation(recv_operation)
+        } else {
+            Err(other_io_error("Cannot resume recv: no pending operation"))
+        }
+    }
+
+    fn has_pending_recv(&self) -> bool {
+        self.recv_operation.is_some()
+    }
+
+    fn has_buffered_recv(&self) -> bool {
+        self.stream.has_plaintext()
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Handshake for TlsPipeStub                                                 */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Handshake for TlsPipeStub {
+    fn prepare_handshake(&mut self) -> io::Result<Option<mio::Ready>> {
+        self.stream.handshake()
+    }
+    fn send_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
+        if !self.is_handshake_sent {
+            try!(send_and_check_handshake(&mut self.stream, pids));
+            self.is_handshake_sent = true;
+        }
+
+        self.stream.flush()
+    }
+    fn recv_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
+        recv_and_check_handshake(&mut self.stream, pids)
+    }
+}
+
+impl AsyncPipeStub for TlsPipeStub {
+    fn local_addr(&self) -> Option<String> {
+        self.stream.socket().local_addr().ok().map(|addr| addr.to_string())
+    }
+
+    fn peer_addr(&self) -> Option<String> {
+        self.stream.socket().peer_addr().ok().map(|addr| addr.to_string())
+    }
+
+    fn is_connected(&self) -> bool {
+        self.stream.socket().peer_addr().is_ok()
+    }
+
+    #[cfg(windows)]
+    fn read_and_write_void(&mut self) {
+        let mut buffer: [u8; 0] = [0; 0];
+
+        let _ = self.stream.socket_mut().read(&mut buffer);
+        let _ = self.stream.socket_mut().write(&buffer);
+    }
+}
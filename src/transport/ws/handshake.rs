@@ -0,0 +1,511 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::io;
+use std::io::{Read, Write};
+
+use byteorder::{ BigEndian, ByteOrder };
+
+use proto;
+use transport::async::stub::*;
+use transport::ws::send::MaskGenerator;
+use io_error::*;
+
+const WS_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const MAX_HEADER_SIZE: usize = 8 * 1024;
+
+/*****************************************************************************/
+/*                                                                           */
+/* Client side of the opening handshake                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+pub struct ClientHandshake {
+    host: String,
+    path: String,
+    key: String,
+    request: Vec<u8>,
+    written: usize,
+    response: Vec<u8>
+}
+
+impl ClientHandshake {
+    pub fn new(host: &str, path: &str, mask_gen: &mut MaskGenerator) -> ClientHandshake {
+        let mut nonce = [0u8; 16];
+
+        for chunk in nonce.chunks_mut(4) {
+            chunk.copy_from_slice(&mask_gen.next_key());
+        }
+
+        ClientHandshake {
+            host: host.to_owned(),
+            path: path.to_owned(),
+            key: base64_encode(&nonce),
+            request: Vec::new(),
+            written: 0,
+            response: Vec::new()
+        }
+    }
+
+    /// Writes the upgrade request, asking the server to speak the protocol of the expected peer.
+    pub fn send<T:Write>(&mut self, stream: &mut T, pids: (u16, u16)) -> io::Result<()> {
+        if self.request.is_empty() {
+            let (_, peer_proto_id) = pids;
+            let request = format!(
+                "GET {} HTTP/1.1\r\n\
+                 Host: {}\r\n\
+                 Upgrade: websocket\r\n\
+                 Connection: Upgrade\r\n\
+                 Sec-WebSocket-Key: {}\r\n\
+                 Sec-WebSocket-Version: 13\r\n\
+                 Sec-WebSocket-Protocol: {}\r\n\
+                 \r\n",
+                self.path, self.host, self.key, subprotocol(peer_proto_id));
+
+            self.request = request.into_bytes();
+        }
+
+        if try!(stream.write_buffer(&self.request, &mut self.written)) {
+            Ok(())
+        } else {
+            Err(would_block_io_error("handshake request not sent yet"))
+        }
+    }
+
+    pub fn recv<T:Read>(&mut self, stream: &mut T, pids: (u16, u16)) -> io::Result<()> {
+        try!(read_http_header(stream, &mut self.response));
+
+        let (_, peer_proto_id) = pids;
+        let response = try!(HttpHeader::parse(&self.response));
+
+        if !response.start_line.starts_with("HTTP/1.1 101") {
+            return Err(invalid_data_io_error("websocket upgrade refused"));
+        }
+
+        try!(response.check_upgrade());
+
+        if response.get("sec-websocket-accept") != Some(&accept_key(&self.key)) {
+            return Err(invalid_data_io_error("bad websocket accept key"));
+        }
+
+        if response.get("sec-websocket-protocol") != Some(&subprotocol(peer_proto_id)) {
+            return Err(invalid_data_io_error("received bad handshake"));
+        }
+
+        Ok(())
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Server side of the opening handshake                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+pub struct ServerHandshake {
+    path: String,
+    request: Vec<u8>,
+    response: Vec<u8>,
+    written: usize
+}
+
+impl ServerHandshake {
+    pub fn new(path: &str) -> ServerHandshake {
+        ServerHandshake {
+            path: path.to_owned(),
+            request: Vec::new(),
+            response: Vec::new(),
+            written: 0
+        }
+    }
+
+    /// Reads the upgrade request, and accepts it only if the client
+    /// asks for the protocol this side speaks.
+    /// Once the request is accepted, each call resumes writing the response.
+    pub fn recv<T:Read + Write>(&mut self, stream: &mut T, pids: (u16, u16)) -> io::Result<()> {
+        if self.response.is_empty() {
+            try!(self.recv_request(stream, pids));
+        }
+
+        self.send_response(stream)
+    }
+
+    /// Tells whether the response is pending, waiting for the stream to be writable.
+    pub fn is_sending(&self) -> bool {
+        !self.response.is_empty() && self.written < self.response.len()
+    }
+
+    fn recv_request<T:Read + Write>(&mut self, stream: &mut T, pids: (u16, u16)) -> io::Result<()> {
+        try!(read_http_header(stream, &mut self.request));
+
+        let (proto_id, _) = pids;
+        let protocol = subprotocol(proto_id);
+        let res = HttpHeader::parse(&self.request).and_then(|request| {
+            let key = try!(request.check_request(&self.path, &protocol));
+
+            Ok(accept_key(key))
+        });
+
+        match res {
+            Ok(accept) => {
+                let response = format!(
+                    "HTTP/1.1 101 Switching Protocols\r\n\
+                     Upgrade: websocket\r\n\
+                     Connection: Upgrade\r\n\
+                     Sec-WebSocket-Accept: {}\r\n\
+                     Sec-WebSocket-Protocol: {}\r\n\
+                     \r\n",
+                    accept, protocol);
+
+                self.response = response.into_bytes();
+
+                Ok(())
+            },
+            Err(e) => {
+                let _ = stream.write(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n");
+
+                Err(e)
+            }
+        }
+    }
+
+    fn send_response<T:Write>(&mut self, stream: &mut T) -> io::Result<()> {
+        loop {
+            let written = self.written;
+
+            if try!(stream.write_buffer(&self.response, &mut self.written)) {
+                return Ok(());
+            }
+            if self.written == written {
+                return Err(would_block_io_error("handshake response not sent yet"));
+            }
+        }
+    }
+}
+
+/// Reads the stream one byte at a time until the end of the header,
+/// so that no byte of the first frame gets consumed.
+fn read_http_header<T:Read>(stream: &mut T, buffer: &mut Vec<u8>) -> io::Result<()> {
+    let mut byte = [0u8; 1];
+
+    while !buffer.ends_with(b"\r\n\r\n") {
+        if buffer.len() >= MAX_HEADER_SIZE {
+            return Err(invalid_data_io_error("handshake header is too long"));
+        }
+
+        match stream.read(&mut byte) {
+            Ok(0) => return Err(other_io_error("connection closed during handshake")),
+            Ok(_) => buffer.push(byte[0]),
+            Err(e) => return Err(e)
+        }
+    }
+
+    Ok(())
+}
+
+struct HttpHeader {
+    start_line: String,
+    fields: Vec<(String, String)>
+}
+
+impl HttpHeader {
+    fn parse(buffer: &[u8]) -> io::Result<HttpHeader> {
+        let text = match ::std::str::from_utf8(buffer) {
+            Ok(text) => text,
+            Err(_) => return Err(invalid_data_io_error("handshake header is not valid utf8"))
+        };
+        let mut lines = text.split("\r\n").filter(|line| !line.is_empty());
+        let start_line = match lines.next() {
+            Some(line) => line.to_owned(),
+            None => return Err(invalid_data_io_error("handshake header is empty"))
+        };
+        let mut fields = Vec::new();
+
+        for line in lines {
+            match line.find(':') {
+                Some(i) => fields.push((line[..i].trim().to_lowercase(), line[i + 1..].trim().to_owned())),
+                None => return Err(invalid_data_io_error("malformed handshake header"))
+            }
+        }
+
+        Ok(HttpHeader {
+            start_line: start_line,
+            fields: fields
+        })
+    }
+
+    fn get(&self, name: &str) -> Option<&String> {
+        self.fields.iter().find(|field| field.0 == name).map(|field| &field.1)
+    }
+
+    fn has_token(&self, name: &str, token: &str) -> bool {
+        match self.get(name) {
+            Some(value) => value.split(',').any(|t| t.trim().eq_ignore_ascii_case(token)),
+            None => false
+        }
+    }
+
+    fn check_upgrade(&self) -> io::Result<()> {
+        if self.has_token("upgrade", "websocket") && self.has_token("connection", "upgrade") {
+            Ok(())
+        } else {
+            Err(invalid_data_io_error("not a websocket upgrade"))
+        }
+    }
+
+    fn check_request(&self, path: &str, protocol: &str) -> io::Result<&String> {
+        let mut parts = self.start_line.split(' ');
+
+        if parts.next() != Some("GET") || parts.next() != Some(path) {
+            return Err(invalid_data_io_error("bad websocket request line"));
+        }
+
+        try!(self.check_upgrade());
+
+        if self.get("sec-websocket-version").map(|v| v.as_str()) != Some("13") {
+            return Err(invalid_data_io_error("unsupported websocket version"));
+        }
+
+        if !self.has_token("sec-websocket-protocol", protocol) {
+            return Err(invalid_data_io_error("received bad handshake"));
+        }
+
+        match self.get("sec-websocket-key") {
+            Some(key) => Ok(key),
+            None => Err(invalid_data_io_error("missing websocket key"))
+        }
+    }
+}
+
+/// Name of the websocket subprotocol for a scalability protocol, as in nanomsg ws mapping.
+fn subprotocol(proto_id: u16) -> String {
+    let name = match proto_id {
+        proto::PAIR       => "pair",
+        proto::PAIR1      => "pair1",
+        proto::PUB        => "pub",
+        proto::SUB        => "sub",
+        proto::REQ        => "req",
+        proto::REP        => "rep",
+        proto::PUSH       => "push",
+        proto::PULL       => "pull",
+        proto::SURVEYOR   => "surveyor",
+        proto::RESPONDENT => "respondent",
+        proto::BUS        => "bus",
+        _ => return format!("{}.sp.nanomsg.org", proto_id)
+    };
+
+    format!("{}.sp.nanomsg.org", name)
+}
+
+fn accept_key(key: &str) -> String {
+    let mut input = String::with_capacity(key.len() + WS_GUID.len());
+
+    input.push_str(key);
+    input.push_str(WS_GUID);
+
+    base64_encode(&sha1(input.as_bytes()))
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Digest and encoding required to compute the accept key                    */
+/*                                                                           */
+/*****************************************************************************/
+
+fn sha1(input: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut data = input.to_vec();
+    let bit_len = (input.len() as u64) * 8;
+
+    data.push(0x80);
+    while data.len() % 64 != 56 {
+        data.push(0);
+    }
+    let mut len_buffer = [0u8; 8];
+    BigEndian::write_u64(&mut len_buffer, bit_len);
+    data.extend_from_slice(&len_buffer);
+
+    for block in data.chunks(64) {
+        let mut w = [0u32; 80];
+
+        for i in 0..16 {
+            w[i] = BigEndian::read_u32(&block[i * 4..]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let (mut a, mut b, mut c, mut d, mut e) = (h[0], h[1], h[2], h[3], h[4]);
+
+        for (i, wi) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19  => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _       => (b ^ c ^ d, 0xCA62C1D6)
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*wi);
+
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        h[0] = h[0].wrapping_add(a);
+        h[1] = h[1].wrapping_add(b);
+        h[2] = h[2].wrapping_add(c);
+        h[3] = h[3].wrapping_add(d);
+        h[4] = h[4].wrapping_add(e);
+    }
+
+    let mut digest = [0u8; 20];
+    for (i, x) in h.iter().enumerate() {
+        BigEndian::write_u32(&mut digest[i * 4..], *x);
+    }
+    digest
+}
+
+fn base64_encode(input: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut output = String::with_capacity(input.len() * 4 / 3 + 4);
+
+    for chunk in input.chunks(3) {
+        let b0 = chunk[0] as usize;
+        let b1 = if chunk.len() > 1 { chunk[1] as usize } else { 0 };
+        let b2 = if chunk.len() > 2 { chunk[2] as usize } else { 0 };
+
+        output.push(ALPHABET[b0 >> 2] as char);
+        output.push(ALPHABET[((b0 & 0x03) << 4) | (b1 >> 4)] as char);
+        output.push(if chunk.len() > 1 { ALPHABET[((b1 & 0x0F) << 2) | (b2 >> 6)] as char } else { '=' });
+        output.push(if chunk.len() > 2 { ALPHABET[b2 & 0x3F] as char } else { '=' });
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+    use transport::ws::send::MaskGenerator;
+
+    #[test]
+    fn accept_key_should_match_rfc_sample() {
+        assert_eq!("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=", accept_key("dGhlIHNhbXBsZSBub25jZQ=="));
+    }
+
+    #[test]
+    fn base64_should_pad_output() {
+        assert_eq!("", base64_encode(b""));
+        assert_eq!("Zg==", base64_encode(b"f"));
+        assert_eq!("Zm8=", base64_encode(b"fo"));
+        assert_eq!("Zm9v", base64_encode(b"foo"));
+    }
+
+    #[test]
+    fn subprotocol_should_be_named_after_the_protocol() {
+        assert_eq!("req.sp.nanomsg.org", subprotocol(48));
+        assert_eq!("respondent.sp.nanomsg.org", subprotocol(99));
+        assert_eq!("1234.sp.nanomsg.org", subprotocol(1234));
+    }
+
+    #[test]
+    fn server_should_accept_client_request() {
+        let mut mask_gen = MaskGenerator::new();
+        let mut client = ClientHandshake::new("localhost:5454", "/", &mut mask_gen);
+        let mut server = ServerHandshake::new("/");
+        let mut request = Vec::new();
+
+        client.send(&mut request, (48, 49)).unwrap();
+
+        let mut server_stream = io::Cursor::new(request);
+        server.recv(&mut server_stream, (49, 48)).unwrap();
+
+        let response = server_stream.into_inner().split_off(client.request.len());
+        let mut client_stream = io::Cursor::new(response);
+        client.recv(&mut client_stream, (48, 49)).unwrap();
+    }
+
+    #[test]
+    fn server_should_refuse_client_with_wrong_protocol() {
+        let mut mask_gen = MaskGenerator::new();
+        let mut client = ClientHandshake::new("localhost:5454", "/", &mut mask_gen);
+        let mut server = ServerHandshake::new("/");
+        let mut request = Vec::new();
+
+        client.send(&mut request, (80, 81)).unwrap();
+
+        let mut server_stream = io::Cursor::new(request);
+        let err = server.recv(&mut server_stream, (49, 48)).unwrap_err();
+
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+
+    struct ChokedStream {
+        input: io::Cursor<Vec<u8>>,
+        output: Vec<u8>,
+        chunk: usize,
+        budget: usize
+    }
+
+    impl io::Read for ChokedStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl io::Write for ChokedStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let len = buf.len().min(self.chunk).min(self.budget);
+
+            if len == 0 {
+                return Err(io::Error::new(io::ErrorKind::WouldBlock, "choked"));
+            }
+
+            self.output.extend_from_slice(&buf[..len]);
+            self.budget -= len;
+
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn server_should_resume_a_partially_written_response() {
+        let mut mask_gen = MaskGenerator::new();
+        let mut client = ClientHandshake::new("localhost:5454", "/", &mut mask_gen);
+        let mut server = ServerHandshake::new("/");
+        let mut request = Vec::new();
+
+        client.send(&mut request, (48, 49)).unwrap();
+
+        let mut server_stream = ChokedStream {
+            input: io::Cursor::new(request),
+            output: Vec::new(),
+            chunk: 16,
+            budget: 40
+        };
+        let err = server.recv(&mut server_stream, (49, 48)).unwrap_err();
+
+        assert_eq!(io::ErrorKind::WouldBlock, err.kind());
+        assert_eq!(40, server_stream.output.len());
+        assert!(server.is_sending());
+
+        server_stream.budget = 1024;
+        server.recv(&mut server_stream, (49, 48)).unwrap();
+
+        assert!(!server.is_sending());
+
+        let mut client_stream = io::Cursor::new(server_stream.output);
+        client.recv(&mut client_stream, (48, 49)).unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+//! This module provides a websocket transport, following the nanomsg mapping:
+//! the client asks for the `<proto>.sp.nanomsg.org` subprotocol of its peer,
+//! and each message is sent as a single binary frame.
+//! Addresses are written `host:port/path`, the path defaulting to `/`.
+
+mod stub;
+mod send;
+mod recv;
+mod handshake;
+mod acceptor;
+
+use std::str::FromStr;
+use std::io;
+use std::net;
+
+use mio::tcp::{TcpListener, TcpStream};
+
+use self::stub::WsPipeStub;
+use self::acceptor::WsAcceptor;
+use transport::{Transport, Destination};
+use transport::pipe::Pipe;
+use transport::acceptor::Acceptor;
+use transport::async::AsyncPipe;
+use io_error::*;
+
+pub struct Ws;
+
+impl Ws {
+    fn connect(&self, host: &str, path: &str, dest: &Destination) -> io::Result<Box<Pipe>> {
+        let addr = try!(parse_socket_addr(host));
+        let stream = try!(TcpStream::connect(&addr));
+        try!(stream.set_nodelay(dest.tcp_no_delay));
+        let stub = WsPipeStub::client(stream, dest.recv_max_size, host, path);
+        let pipe = AsyncPipe::new(stub, dest.pids);
+
+        Ok(Box::new(pipe))
+    }
+    fn bind(&self, host: &str, path: &str, dest: &Destination) -> io::Result<Box<Acceptor>> {
+        let addr = try!(parse_socket_addr(host));
+        let listener = try!(TcpListener::bind(&addr));
+        let acceptor = WsAcceptor::new(listener, path, dest);
+
+        Ok(Box::new(acceptor))
+    }
+}
+
+fn split_addr(addr: &str) -> (&str, &str) {
+    match addr.find('/') {
+        Some(i) => (&addr[..i], &addr[i..]),
+        None => (addr, "/")
+    }
+}
+
+fn parse_socket_addr(host: &str) -> io::Result<net::SocketAddr> {
+    net::SocketAddr::from_str(host).map_err(|_| invalid_input_io_error(host.to_owned()))
+}
+
+impl Transport for Ws {
+    fn connect(&self, dest: &Destination) -> io::Result<Box<Pipe>> {
+        let (host, path) = split_addr(dest.addr);
+
+        self.connect(host, path, dest)
+    }
+
+    fn bind(&self, dest: &Destination) -> io::Result<Box<Acceptor>> {
+        let (host, path) = split_addr(dest.addr);
+
+        self.bind(host, path, dest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_addr;
+
+    #[test]
+    fn path_should_default_to_root() {
+        assert_eq!(("127.0.0.1:5454", "/"), split_addr("127.0.0.1:5454"));
+    }
+
+    #[test]
+    fn path_should_be_split_from_host() {
+        assert_eq!(("127.0.0.1:5454", "/chat/room"), split_addr("127.0.0.1:5454/chat/room"));
+    }
+}
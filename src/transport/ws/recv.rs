@@ -0,0 +1,305 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::io;
+
+use byteorder::{ BigEndian, ByteOrder };
+
+use core::Message;
+use transport::async::stub::*;
+use transport::ws::send::*;
+use io_error::*;
+
+/// Reads frames until a whole message is received, answering pings on the way.
+/// Only the bytes of the current frame are read from the stream, so nothing is
+/// left buffered once the message is returned.
+/// On the server side, frames sent by the client must be masked.
+pub struct RecvOperation {
+    recv_max_size: u64,
+    requires_mask: bool,
+    step: Option<RecvOperationStep>,
+    message: Vec<u8>,
+    is_fragmented: bool,
+    pings: Vec<Vec<u8>>
+}
+
+#[derive(Clone, Copy)]
+struct FrameHeader {
+    is_final: bool,
+    opcode: u8,
+    is_masked: bool,
+    len: u64,
+    mask_key: [u8; 4]
+}
+
+enum RecvOperationStep {
+    Header([u8; 2], usize),
+    Length(FrameHeader, [u8; 8], usize, usize),
+    MaskKey(FrameHeader, [u8; 4], usize),
+    Payload(FrameHeader, Vec<u8>, usize)
+}
+
+enum Progress {
+    Blocked(RecvOperationStep),
+    Next(RecvOperationStep),
+    Done(Message)
+}
+
+impl RecvOperation {
+    pub fn new(recv_max_size: u64, requires_mask: bool) -> RecvOperation {
+        RecvOperation {
+            recv_max_size: recv_max_size,
+            requires_mask: requires_mask,
+            step: Some(RecvOperationStep::Header([0; 2], 0)),
+            message: Vec::new(),
+            is_fragmented: false,
+            pings: Vec::new()
+        }
+    }
+
+    pub fn run<T:io::Read>(&mut self, stream: &mut T) -> io::Result<Option<Message>> {
+        loop {
+            let step = match self.step.take() {
+                Some(step) => step,
+                None => return Err(other_io_error("Cannot resume already finished recv operation"))
+            };
+
+            match try!(self.advance(stream, step)) {
+                Progress::Blocked(step) => {
+                    self.step = Some(step);
+                    return Ok(None);
+                },
+                Progress::Next(step) => self.step = Some(step),
+                Progress::Done(msg) => return Ok(Some(msg))
+            }
+        }
+    }
+
+    /// Returns the payloads of the pings received so far, that must be sent back in pongs.
+    pub fn take_pings(&mut self) -> Vec<Vec<u8>> {
+        self.pings.drain(..).collect()
+    }
+
+    fn advance<T:io::Read>(&mut self, stream: &mut T, step: RecvOperationStep) -> io::Result<Progress> {
+        match step {
+            RecvOperationStep::Header(mut buffer, mut read) => {
+                read += try!(stream.read_buffer(&mut buffer[read..]));
+
+                if read == 2 {
+                    self.on_header(buffer)
+                } else {
+                    Ok(Progress::Blocked(RecvOperationStep::Header(buffer, read)))
+                }
+            },
+            RecvOperationStep::Length(mut header, mut buffer, mut read, size) => {
+                read += try!(stream.read_buffer(&mut buffer[read..size]));
+
+                if read == size {
+                    header.len = if size == 2 {
+                        u64::from(BigEndian::read_u16(&buffer))
+                    } else {
+                        BigEndian::read_u64(&buffer)
+                    };
+                    self.on_length(header)
+                } else {
+                    Ok(Progress::Blocked(RecvOperationStep::Length(header, buffer, read, size)))
+                }
+            },
+            RecvOperationStep::MaskKey(mut header, mut buffer, mut read) => {
+                read += try!(stream.read_buffer(&mut buffer[read..]));
+
+                if read == 4 {
+                    header.mask_key = buffer;
+                    Ok(Progress::Next(payload_step(header)))
+                } else {
+                    Ok(Progress::Blocked(RecvOperationStep::MaskKey(header, buffer, read)))
+                }
+            },
+            RecvOperationStep::Payload(header, mut buffer, mut read) => {
+                read += try!(stream.read_buffer(&mut buffer[read..]));
+
+                if read == buffer.len() {
+                    if header.is_masked {
+                        apply_mask(&mut buffer, header.mask_key, 0);
+                    }
+                    self.on_payload(header, buffer)
+                } else {
+                    Ok(Progress::Blocked(RecvOperationStep::Payload(header, buffer, read)))
+                }
+            }
+        }
+    }
+
+    fn on_header(&mut self, buffer: [u8; 2]) -> io::Result<Progress> {
+        let header = FrameHeader {
+            is_final: buffer[0] & 0x80 != 0,
+            opcode: buffer[0] & 0x0F,
+            is_masked: buffer[1] & 0x80 != 0,
+            len: u64::from(buffer[1] & 0x7F),
+            mask_key: [0; 4]
+        };
+
+        if buffer[0] & 0x70 != 0 {
+            return Err(invalid_data_io_error("unsupported websocket extension"));
+        }
+
+        if self.requires_mask && !header.is_masked {
+            return Err(invalid_data_io_error("unmasked websocket frame from client"));
+        }
+
+        if header.opcode >= OPCODE_CLOSE && (!header.is_final || header.len > 125) {
+            return Err(invalid_data_io_error("malformed websocket control frame"));
+        }
+
+        match header.len {
+            126 => Ok(Progress::Next(RecvOperationStep::Length(header, [0; 8], 0, 2))),
+            127 => Ok(Progress::Next(RecvOperationStep::Length(header, [0; 8], 0, 8))),
+            _ => self.on_length(header)
+        }
+    }
+
+    fn on_length(&mut self, header: FrameHeader) -> io::Result<Progress> {
+        if header.opcode < OPCODE_CLOSE {
+            let msg_len = self.message.len() as u64 + header.len;
+
+            if self.recv_max_size > 0 && msg_len > self.recv_max_size {
+                return Err(invalid_data_io_error("message is too long"));
+            }
+        }
+
+        if header.is_masked {
+            Ok(Progress::Next(RecvOperationStep::MaskKey(header, [0; 4], 0)))
+        } else {
+            Ok(Progress::Next(payload_step(header)))
+        }
+    }
+
+    fn on_payload(&mut self, header: FrameHeader, payload: Vec<u8>) -> io::Result<Progress> {
+        match header.opcode {
+            OPCODE_TEXT | OPCODE_BINARY => {
+                if self.is_fragmented {
+                    return Err(invalid_data_io_error("expected a continuation frame"));
+                }
+                self.message = payload;
+            },
+            OPCODE_CONTINUATION => {
+                if !self.is_fragmented {
+                    return Err(invalid_data_io_error("unexpected continuation frame"));
+                }
+                self.message.extend_from_slice(&payload);
+            },
+            OPCODE_PING => {
+                self.pings.push(payload);
+                return Ok(Progress::Next(RecvOperationStep::Header([0; 2], 0)));
+            },
+            OPCODE_PONG => {
+                return Ok(Progress::Next(RecvOperationStep::Header([0; 2], 0)));
+            },
+            OPCODE_CLOSE => return Err(other_io_error("websocket closed by peer")),
+            _ => return Err(invalid_data_io_error("unknown websocket opcode"))
+        }
+
+        if header.is_final {
+            let body = ::std::mem::take(&mut self.message);
+
+            self.is_fragmented = false;
+
+            Ok(Progress::Done(Message::from_body(body)))
+        } else {
+            self.is_fragmented = true;
+
+            Ok(Progress::Next(RecvOperationStep::Header([0; 2], 0)))
+        }
+    }
+}
+
+fn payload_step(header: FrameHeader) -> RecvOperationStep {
+    RecvOperationStep::Payload(header, vec![0u8; header.len as usize], 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    #[test]
+    fn unmasked_frame_should_be_received() {
+        let frame = encode_frame(OPCODE_BINARY, &[&[65, 66, 67]], None);
+        let mut stream = io::Cursor::new(frame);
+        let mut operation = RecvOperation::new(1024, false);
+        let msg = operation.run(&mut stream).unwrap().unwrap();
+
+        assert_eq!(&[65, 66, 67], msg.get_body());
+    }
+
+    #[test]
+    fn masked_and_fragmented_frames_should_be_received_as_one_message() {
+        let mut frames = Vec::new();
+        frames.extend_from_slice(&[0x02, 0x82, 1, 2, 3, 4, 65 ^ 1, 66 ^ 2]);
+        frames.extend_from_slice(&[0x89, 0x01, 7]);
+        frames.extend_from_slice(&[0x80, 0x01, 67]);
+
+        let mut stream = io::Cursor::new(frames);
+        let mut operation = RecvOperation::new(1024, false);
+        let msg = operation.run(&mut stream).unwrap().unwrap();
+
+        assert_eq!(&[65, 66, 67], msg.get_body());
+        assert_eq!(vec![vec![7]], operation.take_pings());
+    }
+
+    #[test]
+    fn frame_received_in_pieces_should_be_resumed() {
+        let frame = encode_frame(OPCODE_BINARY, &[&vec![9u8; 300]], None);
+        let mut operation = RecvOperation::new(1024, false);
+
+        let mut stream = io::Cursor::new(frame[..3].to_vec());
+        assert!(operation.run(&mut stream).unwrap().is_none());
+
+        let mut stream = io::Cursor::new(frame[3..].to_vec());
+        let msg = operation.run(&mut stream).unwrap().unwrap();
+
+        assert_eq!(300, msg.get_body().len());
+    }
+
+    #[test]
+    fn too_long_message_should_be_refused() {
+        let frame = encode_frame(OPCODE_BINARY, &[&[1, 2, 3, 4]], None);
+        let mut stream = io::Cursor::new(frame);
+        let mut operation = RecvOperation::new(3, false);
+        let err = operation.run(&mut stream).unwrap_err();
+
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+
+    #[test]
+    fn close_frame_should_cause_an_error() {
+        let mut stream = io::Cursor::new(vec![0x88, 0x00]);
+        let mut operation = RecvOperation::new(0, false);
+
+        assert!(operation.run(&mut stream).is_err());
+    }
+
+    #[test]
+    fn unmasked_frame_should_be_refused_when_mask_is_required() {
+        let frame = encode_frame(OPCODE_BINARY, &[&[65, 66, 67]], None);
+        let mut stream = io::Cursor::new(frame);
+        let mut operation = RecvOperation::new(1024, true);
+        let err = operation.run(&mut stream).unwrap_err();
+
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+
+    #[test]
+    fn masked_frame_should_be_received_when_mask_is_required() {
+        let frame = encode_frame(OPCODE_BINARY, &[&[65, 66, 67]], Some([1, 2, 3, 4]));
+        let mut stream = io::Cursor::new(frame);
+        let mut operation = RecvOperation::new(1024, true);
+        let msg = operation.run(&mut stream).unwrap().unwrap();
+
+        assert_eq!(&[65, 66, 67], msg.get_body());
+    }
+}
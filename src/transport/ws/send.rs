@@ -0,0 +1,141 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::io;
+
+use byteorder::{ BigEndian, ByteOrder };
+use time;
+
+use transport::async::stub::*;
+
+pub const OPCODE_CONTINUATION: u8 = 0x0;
+pub const OPCODE_TEXT: u8         = 0x1;
+pub const OPCODE_BINARY: u8       = 0x2;
+pub const OPCODE_CLOSE: u8        = 0x8;
+pub const OPCODE_PING: u8         = 0x9;
+pub const OPCODE_PONG: u8         = 0xA;
+
+/// Writes a whole frame, which is encoded upfront since
+/// client frames must be masked anyway.
+pub struct SendOperation {
+    frame: Vec<u8>,
+    written: usize
+}
+
+impl SendOperation {
+    pub fn new(frame: Vec<u8>) -> SendOperation {
+        SendOperation {
+            frame: frame,
+            written: 0
+        }
+    }
+
+    pub fn run<T:io::Write>(&mut self, stream: &mut T) -> io::Result<bool> {
+        stream.write_buffer(&self.frame, &mut self.written)
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.written > 0
+    }
+}
+
+/// Encodes a final frame, masking the payload when a mask key is given.
+pub fn encode_frame(opcode: u8, parts: &[&[u8]], mask_key: Option<[u8; 4]>) -> Vec<u8> {
+    let len = parts.iter().fold(0, |acc, part| acc + part.len());
+    let mut frame = Vec::with_capacity(len + 14);
+    let mask_bit = if mask_key.is_some() { 0x80 } else { 0 };
+
+    frame.push(0x80 | opcode);
+
+    if len < 126 {
+        frame.push(mask_bit | len as u8);
+    } else if len <= 0xFFFF {
+        let mut buffer = [0u8; 2];
+        BigEndian::write_u16(&mut buffer, len as u16);
+        frame.push(mask_bit | 126);
+        frame.extend_from_slice(&buffer);
+    } else {
+        let mut buffer = [0u8; 8];
+        BigEndian::write_u64(&mut buffer, len as u64);
+        frame.push(mask_bit | 127);
+        frame.extend_from_slice(&buffer);
+    }
+
+    if let Some(key) = mask_key {
+        frame.extend_from_slice(&key);
+    }
+
+    let payload_start = frame.len();
+    for part in parts {
+        frame.extend_from_slice(part);
+    }
+
+    if let Some(key) = mask_key {
+        apply_mask(&mut frame[payload_start..], key, 0);
+    }
+
+    frame
+}
+
+/// Xors the buffer with the mask key, starting at the specified payload offset.
+pub fn apply_mask(buffer: &mut [u8], key: [u8; 4], offset: usize) {
+    for (i, byte) in buffer.iter_mut().enumerate() {
+        *byte ^= key[(offset + i) % 4];
+    }
+}
+
+/// Masking is meant to defeat proxy cache poisoning, not to protect the data,
+/// so a simple xorshift generator seeded from the clock is enough.
+pub struct MaskGenerator {
+    state: u64
+}
+
+impl MaskGenerator {
+    pub fn new() -> MaskGenerator {
+        let seed = time::precise_time_ns() ^ ((time::get_time().nsec as u64) << 32);
+
+        MaskGenerator { state: seed | 1 }
+    }
+
+    pub fn next_key(&mut self) -> [u8; 4] {
+        let mut key = [0u8; 4];
+
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+
+        BigEndian::write_u32(&mut key, (self.state >> 16) as u32);
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_frame_should_have_short_length() {
+        let frame = encode_frame(OPCODE_BINARY, &[&[1, 2], &[3]], None);
+
+        assert_eq!(vec![0x82, 3, 1, 2, 3], frame);
+    }
+
+    #[test]
+    fn medium_frame_should_have_16_bits_length() {
+        let payload = vec![0u8; 300];
+        let frame = encode_frame(OPCODE_BINARY, &[&payload], None);
+
+        assert_eq!(&[0x82, 126, 1, 44], &frame[..4]);
+        assert_eq!(304, frame.len());
+    }
+
+    #[test]
+    fn masked_frame_should_contain_key_and_masked_payload() {
+        let frame = encode_frame(OPCODE_BINARY, &[&[1, 2, 3, 4, 5]], Some([1, 2, 3, 4]));
+
+        assert_eq!(vec![0x82, 0x85, 1, 2, 3, 4, 0, 0, 0, 0, 4], frame);
+    }
+}
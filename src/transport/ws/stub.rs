@@ -0,0 +1,265 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::ops::Deref;
+use std::rc::Rc;
+use std::io;
+#[cfg(windows)]
+use std::io::{Read, Write};
+
+use mio;
+use mio::tcp::{TcpStream, Shutdown};
+
+use core::Message;
+use transport::ws::handshake::{ClientHandshake, ServerHandshake};
+use transport::ws::send::*;
+use transport::ws::recv::RecvOperation;
+use transport::async::stub::*;
+use io_error::*;
+
+/*****************************************************************************/
+/*                                                                           */
+/* WsPipeStub                                                                */
+/*                                                                           */
+/*****************************************************************************/
+
+enum Role {
+    Client(ClientHandshake),
+    Server(ServerHandshake)
+}
+
+pub struct WsPipeStub {
+    stream: TcpStream,
+    role: Role,
+    recv_max_size: u64,
+    mask_gen: MaskGenerator,
+    send_operation: Option<SendOperation>,
+    recv_operation: Option<RecvOperation>,
+    control: Vec<u8>,
+    control_written: usize
+}
+
+impl Deref for WsPipeStub {
+    type Target = mio::Evented;
+    fn deref(&self) -> &Self::Target {
+        &self.stream
+    }
+}
+
+impl WsPipeStub {
+    pub fn client(stream: TcpStream, recv_max_size: u64, host: &str, path: &str) -> WsPipeStub {
+        let mut mask_gen = MaskGenerator::new();
+        let handshake = ClientHandshake::new(host, path, &mut mask_gen);
+
+        WsPipeStub::new(stream, Role::Client(handshake), recv_max_size, mask_gen)
+    }
+
+    pub fn server(stream: TcpStream, recv_max_size: u64, path: &str) -> WsPipeStub {
+        let handshake = ServerHandshake::new(path);
+
+        WsPipeStub::new(stream, Role::Server(handshake), recv_max_size, MaskGenerator::new())
+    }
+
+    fn new(stream: TcpStream, role: Role, recv_max_size: u64, mask_gen: MaskGenerator) -> WsPipeStub {
+        WsPipeStub {
+            stream: stream,
+            role: role,
+            recv_max_size: recv_max_size,
+            mask_gen: mask_gen,
+            send_operation: None,
+            recv_operation: None,
+            control: Vec::new(),
+            control_written: 0
+        }
+    }
+
+    /// Frames sent by the client must be masked, those sent by the server must not.
+    fn encode_frame(&mut self, opcode: u8, parts: &[&[u8]]) -> Vec<u8> {
+        let mask_key = match self.role {
+            Role::Client(_) => Some(self.mask_gen.next_key()),
+            Role::Server(_) => None
+        };
+
+        encode_frame(opcode, parts, mask_key)
+    }
+
+    /// Writes the pending control frames, they can only be sent between two data frames.
+    fn flush_control(&mut self) -> io::Result<bool> {
+        if self.control.is_empty() {
+            return Ok(true);
+        }
+
+        if try!(self.stream.write_buffer(&self.control, &mut self.control_written)) {
+            self.control.clear();
+            self.control_written = 0;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn run_send_operation(&mut self, mut send_operation: SendOperation) -> io::Result<bool> {
+        if !send_operation.is_started() && !try!(self.flush_control()) {
+            self.send_operation = Some(send_operation);
+            return Ok(false);
+        }
+
+        if try!(send_operation.run(&mut self.stream)) {
+            try!(self.flush_control());
+            Ok(true)
+        } else {
+            self.send_operation = Some(send_operation);
+            Ok(false)
+        }
+    }
+
+    fn run_recv_operation(&mut self, mut recv_operation: RecvOperation) -> io::Result<Option<Message>> {
+        let res = recv_operation.run(&mut self.stream);
+
+        try!(self.answer_pings(recv_operation.take_pings()));
+
+        match try!(res) {
+            Some(msg) => Ok(Some(msg)),
+            None => {
+                self.recv_operation = Some(recv_operation);
+                Ok(None)
+            }
+        }
+    }
+
+    fn answer_pings(&mut self, pings: Vec<Vec<u8>>) -> io::Result<()> {
+        if pings.is_empty() {
+            return Ok(());
+        }
+
+        for ping in pings {
+            let pong = self.encode_frame(OPCODE_PONG, &[&ping]);
+
+            self.control.extend_from_slice(&pong);
+        }
+
+        if self.send_operation.is_none() {
+            try!(self.flush_control());
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for WsPipeStub {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Sender for WsPipeStub                                                     */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Sender for WsPipeStub {
+    fn start_send(&mut self, msg: Rc<Message>) -> io::Result<bool> {
+        let frame = self.encode_frame(OPCODE_BINARY, &[msg.get_header(), msg.get_body()]);
+        let send_operation = SendOperation::new(frame);
+
+        self.run_send_operation(send_operation)
+    }
+
+    fn resume_send(&mut self) -> io::Result<bool> {
+        if let Some(send_operation) = self.send_operation.take() {
+            self.run_send_operation(send_operation)
+        } else {
+            Err(other_io_error("Cannot resume send: no pending operation"))
+        }
+    }
+
+    fn has_pending_send(&self) -> bool {
+        self.send_operation.is_some()
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Receiver for WsPipeStub                                                   */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Receiver for WsPipeStub {
+    fn start_recv(&mut self) -> io::Result<Option<Message>> {
+        let requires_mask = match self.role {
+            Role::Client(_) => false,
+            Role::Server(_) => true
+        };
+        let recv_operation = RecvOperation::new(self.recv_max_size, requires_mask);
+
+        self.run_recv_operation(recv_operation)
+    }
+
+    fn resume_recv(&mut self) -> io::Result<Option<Message>> {
+        if let Some(recv_operation) = self.recv_operation.take() {
+            self.run_recv_operation(recv_operation)
+        } else {
+            Err(other_io_error("Cannot resume recv: no pending operation"))
+        }
+    }
+
+    fn has_pending_recv(&self) -> bool {
+        self.recv_operation.is_some()
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Handshake for WsPipeStub                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+// The HTTP upgrade replaces the SP handshake: the client sends its request
+// when the pipe is writable, the server answers it when it is readable,
+// waiting for the pipe to be writable if the response does not fit at once.
+impl Handshake for WsPipeStub {
+    fn send_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
+        match self.role {
+            Role::Client(ref mut handshake) => handshake.send(&mut self.stream, pids),
+            Role::Server(_) => Ok(())
+        }
+    }
+    fn recv_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
+        match self.role {
+            Role::Client(ref mut handshake) => handshake.recv(&mut self.stream, pids),
+            Role::Server(ref mut handshake) => handshake.recv(&mut self.stream, pids)
+        }
+    }
+    fn recv_handshake_interest(&self) -> mio::Ready {
+        match self.role {
+            Role::Server(ref handshake) if handshake.is_sending() => mio::Ready::writable(),
+            _ => mio::Ready::readable()
+        }
+    }
+}
+
+impl AsyncPipeStub for WsPipeStub {
+    fn local_addr(&self) -> Option<String> {
+        self.stream.local_addr().ok().map(|addr| addr.to_string())
+    }
+
+    fn peer_addr(&self) -> Option<String> {
+        self.stream.peer_addr().ok().map(|addr| addr.to_string())
+    }
+
+    fn is_connected(&self) -> bool {
+        self.stream.peer_addr().is_ok()
+    }
+
+    #[cfg(windows)]
+    fn read_and_write_void(&mut self) {
+        let mut buffer: [u8; 0] = [0; 0];
+
+        let _ = self.stream.read(&mut buffer);
+        let _ = self.stream.write(&buffer);
+    }
+}
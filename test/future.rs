@@ -0,0 +1,120 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::Duration;
+pub use std::thread;
+pub use std::io;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+pub use scaproust::*;
+
+pub use super::{urls, make_session, make_timeout, sleep_some};
+
+struct ThreadWaker(thread::Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn thread_waker() -> Waker {
+    Waker::from(Arc::new(ThreadWaker(thread::current())))
+}
+
+fn block_on<F: Future>(mut future: F) -> F::Output {
+    let waker = thread_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = unsafe { Pin::new_unchecked(&mut future) };
+
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending       => thread::park()
+        }
+    }
+}
+
+fn poll_once<F: Future + Unpin>(future: &mut F) -> Poll<F::Output> {
+    let waker = thread_waker();
+    let mut cx = Context::from_waker(&waker);
+
+    Pin::new(future).poll(&mut cx)
+}
+
+fn before_each() -> (Session, AsyncSocket, AsyncSocket, String) {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let url = urls::tcp::get();
+
+    let push = push.into_async().expect("Failed to convert socket !");
+    let pull = pull.into_async().expect("Failed to convert socket !");
+
+    (session, push, pull, url)
+}
+
+#[test]
+fn send_and_recv_complete_through_futures() {
+    let (session, mut push, mut pull, url) = before_each();
+
+    block_on(pull.bind(&url)).unwrap();
+    block_on(push.connect(&url)).unwrap();
+
+    let sender = thread::spawn(move || {
+        sleep_some();
+        block_on(push.send(vec![65, 66, 67])).unwrap();
+        push
+    });
+
+    let received = block_on(pull.recv()).unwrap();
+
+    assert_eq!(vec![65, 66, 67], received);
+    drop(sender.join().unwrap());
+    drop(session);
+}
+
+#[test]
+fn options_apply_to_futures() {
+    let (session, _, mut pull, _) = before_each();
+    let timeout = make_timeout();
+
+    block_on(pull.set_option(ConfigOption::RecvTimeout(timeout))).unwrap();
+
+    let err = block_on(pull.recv()).unwrap_err();
+
+    assert_eq!(io::ErrorKind::TimedOut, err.kind());
+    drop(session);
+}
+
+#[test]
+fn dropping_a_pending_recv_cancels_it() {
+    let (session, mut push, mut pull, url) = before_each();
+    let timeout = make_timeout();
+
+    block_on(pull.set_option(ConfigOption::RecvTimeout(timeout))).unwrap();
+    block_on(pull.bind(&url)).unwrap();
+    block_on(push.connect(&url)).unwrap();
+
+    {
+        let mut recv = pull.recv();
+
+        assert!(poll_once(&mut recv).is_pending());
+    }
+
+    block_on(push.send(vec![65, 66, 67])).unwrap();
+
+    // the message must not have been consumed by the cancelled recv
+    let received = block_on(pull.recv()).unwrap();
+
+    assert_eq!(vec![65, 66, 67], received);
+    drop(session);
+}
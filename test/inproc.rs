@@ -0,0 +1,159 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::io;
+
+pub use scaproust::*;
+
+pub use super::{urls, make_session, make_timeout, sleep_some};
+
+fn before_each() -> (Session, String) {
+    let _ = ::env_logger::init();
+    let session = make_session();
+    let url = urls::inproc::get();
+
+    (session, url)
+}
+
+#[test]
+fn send_a_message_through_bound_endpoint() {
+    let (mut session, url) = before_each();
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    push.set_send_timeout(make_timeout()).unwrap();
+
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+
+    push.send(vec![65, 66, 67]).unwrap();
+    let received = pull.recv().unwrap();
+
+    assert_eq!(vec![65, 66, 67], received);
+}
+
+#[test]
+fn connect_can_be_done_before_bind() {
+    let (mut session, url) = before_each();
+    let mut left = session.create_socket::<Pair>().expect("Failed to create socket !");
+    let mut right = session.create_socket::<Pair>().expect("Failed to create socket !");
+
+    left.set_recv_timeout(make_timeout()).unwrap();
+    right.set_send_timeout(make_timeout()).unwrap();
+
+    left.connect(&url).unwrap();
+    sleep_some();
+    right.bind(&url).unwrap();
+
+    right.send(vec![65, 66, 67]).unwrap();
+    let received = left.recv().unwrap();
+
+    assert_eq!(vec![65, 66, 67], received);
+}
+
+#[test]
+fn bind_twice_the_same_address_should_fail() {
+    let (mut session, url) = before_each();
+    let mut first = session.create_socket::<Pair>().expect("Failed to create socket !");
+    let mut second = session.create_socket::<Pair>().expect("Failed to create socket !");
+
+    first.bind(&url).unwrap();
+    let err = second.bind(&url).err().expect("Second bind should have failed !");
+
+    assert_eq!(io::ErrorKind::AddrInUse, err.kind());
+}
+
+#[test]
+fn mismatching_protocols_are_not_connected() {
+    let (mut session, url) = before_each();
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut publ = session.create_socket::<Pub>().expect("Failed to create socket !");
+
+    pull.set_recv_timeout(make_timeout()).unwrap();
+
+    pull.bind(&url).unwrap();
+    publ.connect(&url).unwrap();
+    sleep_some();
+
+    let err = publ.try_send(vec![65, 66, 67]).unwrap_err();
+    assert_eq!(io::ErrorKind::WouldBlock, err.kind());
+}
+
+#[test]
+fn send_a_request_and_receive_a_reply() {
+    let (mut session, url) = before_each();
+    let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+    let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+
+    req.set_send_timeout(make_timeout()).unwrap();
+    req.set_recv_timeout(make_timeout()).unwrap();
+    rep.set_send_timeout(make_timeout()).unwrap();
+    rep.set_recv_timeout(make_timeout()).unwrap();
+
+    rep.bind(&url).unwrap();
+    req.connect(&url).unwrap();
+
+    req.send(vec![65, 66, 67]).unwrap();
+    let request = rep.recv().unwrap();
+    assert_eq!(vec![65, 66, 67], request);
+
+    rep.send(vec![67, 66, 65]).unwrap();
+    let reply = req.recv().unwrap();
+    assert_eq!(vec![67, 66, 65], reply);
+}
+
+#[test]
+fn broadcast_reaches_every_subscriber() {
+    let (mut session, url) = before_each();
+    let mut publ = session.create_socket::<Pub>().expect("Failed to create socket !");
+    let mut sub1 = session.create_socket::<Sub>().expect("Failed to create socket !");
+    let mut sub2 = session.create_socket::<Sub>().expect("Failed to create socket !");
+
+    publ.bind(&url).unwrap();
+    sub1.connect(&url).unwrap();
+    sub2.connect(&url).unwrap();
+    sub1.set_recv_timeout(make_timeout()).unwrap();
+    sub2.set_recv_timeout(make_timeout()).unwrap();
+    sub1.set_option(ConfigOption::Subscribe(String::from(""))).unwrap();
+    sub2.set_option(ConfigOption::Subscribe(String::from(""))).unwrap();
+    sleep_some();
+
+    publ.send(vec![65, 66, 67]).unwrap();
+
+    assert_eq!(vec![65, 66, 67], sub1.recv().unwrap());
+    assert_eq!(vec![65, 66, 67], sub2.recv().unwrap());
+}
+
+#[test]
+fn connected_endpoint_reconnects_when_binder_comes_back() {
+    let (mut session, url) = before_each();
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    push.set_send_timeout(make_timeout()).unwrap();
+
+    pull.bind(&url).unwrap();
+    let ep = push.connect(&url).unwrap();
+    sleep_some();
+
+    push.send(vec![65, 66, 67]).unwrap();
+    assert_eq!(vec![65, 66, 67], pull.recv().unwrap());
+
+    // dropping the binder breaks the established pipe of the connected endpoint
+    drop(pull);
+    sleep_some();
+
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    pull.bind(&url).unwrap();
+    sleep_some();
+
+    assert_eq!(EndpointState::Active, ep.state().unwrap());
+    push.send(vec![67, 66, 65]).unwrap();
+    assert_eq!(vec![67, 66, 65], pull.recv().unwrap());
+}
@@ -83,3 +83,77 @@ fn send_a_message_back_and_forth() {
     assert_eq!(vec![67, 66, 65], received_rtl);
     drop(session);
 }
+
+#[test]
+fn pair1_replies_to_the_peer_a_message_came_from() {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let mut server = session.create_socket::<Pair1>().unwrap();
+    let mut client1 = session.create_socket::<Pair1>().unwrap();
+    let mut client2 = session.create_socket::<Pair1>().unwrap();
+    let url = urls::tcp::get();
+    let timeout = make_timeout();
+
+    server.set_recv_timeout(timeout).unwrap();
+    client1.set_recv_timeout(timeout).unwrap();
+    client2.set_recv_timeout(timeout).unwrap();
+
+    server.bind(&url).unwrap();
+    client1.connect(&url).unwrap();
+    client2.connect(&url).unwrap();
+    sleep_some();
+
+    client1.send(vec![1]).unwrap();
+    let request1 = server.recv_msg().unwrap();
+    client2.send(vec![2]).unwrap();
+    let request2 = server.recv_msg().unwrap();
+
+    assert_eq!(&[1], request1.get_body());
+    assert_eq!(&[2], request2.get_body());
+
+    let header2 = request2.get_header().to_vec();
+    server.send_msg(Message::from_header_and_body(header2, vec![20])).unwrap();
+    let header1 = request1.get_header().to_vec();
+    server.send_msg(Message::from_header_and_body(header1, vec![10])).unwrap();
+
+    assert_eq!(vec![10], client1.recv().unwrap());
+    assert_eq!(vec![20], client2.recv().unwrap());
+    drop(session);
+}
+
+#[test]
+fn pair1_messages_crossing_a_device_have_their_hop_count_incremented() {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let mut d_left = session.create_socket::<Pair1>().unwrap();
+    let mut d_right = session.create_socket::<Pair1>().unwrap();
+    let mut client = session.create_socket::<Pair1>().unwrap();
+    let mut server = session.create_socket::<RawPair1>().unwrap();
+    let d_left_url = urls::tcp::get();
+    let d_right_url = urls::tcp::get();
+    let timeout = make_timeout();
+
+    d_left.bind(&d_left_url).unwrap();
+    d_right.bind(&d_right_url).unwrap();
+    server.set_recv_timeout(timeout).unwrap();
+
+    let device = session.create_bridge_device(d_left, d_right).unwrap();
+    let device_thread = thread::spawn(move || device.run());
+
+    client.connect(&d_left_url).unwrap();
+    server.connect(&d_right_url).unwrap();
+    sleep_some();
+
+    client.send(vec![65, 66, 67]).unwrap();
+    let received = server.recv_msg().unwrap();
+    assert_eq!(&[0, 0, 0, 1], received.get_header());
+    assert_eq!(&[65, 66, 67], received.get_body());
+
+    server.set_option(ConfigOption::MaxTtl(1)).unwrap();
+    client.send(vec![67, 66, 65]).unwrap();
+    let err = server.recv_msg().unwrap_err();
+    assert_eq!(io::ErrorKind::TimedOut, err.kind());
+
+    drop(session);
+    device_thread.join().unwrap().unwrap_err();
+}
@@ -55,3 +55,42 @@ fn send_a_message_through_remote_endpoint() {
     assert_eq!(vec![65, 66, 67], received);
     drop(session);
 }
+
+#[test]
+fn send_buffer_absorbs_a_burst_without_blocking() {
+    let (session, mut push, mut pull, url) = before_each();
+
+    push.set_option(ConfigOption::SendBuffer(1024)).unwrap();
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+
+    sleep_some();
+
+    for i in 0..20u8 {
+        push.try_send(vec![i; 10]).unwrap();
+    }
+    for i in 0..20u8 {
+        assert_eq!(vec![i; 10], pull.recv().unwrap());
+    }
+    drop(session);
+}
+
+#[test]
+fn recv_buffer_reads_ahead() {
+    let (session, mut push, mut pull, url) = before_each();
+
+    pull.set_recv_buffer(1024).unwrap();
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+
+    sleep_some();
+
+    for i in 0..5u8 {
+        push.send(vec![i; 10]).unwrap();
+        sleep_some();
+    }
+    for i in 0..5u8 {
+        assert_eq!(vec![i; 10], pull.try_recv().unwrap());
+    }
+    drop(session);
+}
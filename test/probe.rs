@@ -59,4 +59,35 @@ fn report_socket_readiness() {
     assert!(!poll_result[1].recv, "After recv, Pull should not be recv ready");
     assert!(!poll_result[1].send, "After recv, Pull should not be send ready");
     drop(session);
-}
\ No newline at end of file
+}
+
+#[test]
+fn report_pair_readiness_once_a_message_is_prefetched() {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let timeout = make_hard_timeout();
+    let mut left = session.create_socket::<Pair>().expect("Failed to create socket !");
+    let mut right = session.create_socket::<Pair>().expect("Failed to create socket !");
+    let requests = vec![right.create_poll_req(true, false)];
+    let mut probe = session.create_probe(requests).expect("Failed to create probe !");
+    let url = urls::tcp::get();
+
+    left.set_send_timeout(make_timeout()).expect("Failed to set send timeout !");
+    right.set_recv_timeout(make_timeout()).expect("Failed to set recv timeout !");
+
+    left.bind(&url).unwrap();
+    right.connect(&url).unwrap();
+    sleep_some();
+
+    left.send(vec![65, 66, 67]).expect("Failed to send a message !");
+
+    let poll_result = probe.poll(timeout).expect("After send, poll should have succeed");
+    assert!(poll_result[0].recv, "After send, Pair should be recv ready");
+
+    let received = right.try_recv().expect("Failed to recv a message !");
+    assert_eq!(vec![65, 66, 67], received);
+
+    let poll_result = probe.poll(Duration::from_millis(50));
+    assert!(poll_result.map(|r| !r[0].recv).unwrap_or(true), "After recv, Pair should not be recv ready");
+    drop(session);
+}
@@ -115,3 +115,145 @@ fn ignores_messages_based_on_subscriptions() {
     assert_eq!(vec![65, 66, 67], received3);
     drop(session);
 }
+
+#[test]
+fn filter_messages_on_binary_topics() {
+    let (session, mut publ, mut sub1, mut sub2, mut sub3) = before_each();
+    let url = urls::tcp::get();
+
+    publ.bind(&url).unwrap();
+    sub1.connect(&url).unwrap();
+    sub2.connect(&url).unwrap();
+    sub3.connect(&url).unwrap();
+
+    sub1.subscribe(&[0x00, 0xFE]).unwrap();
+    sub2.subscribe(&[0xFF]).unwrap();
+    sub3.subscribe(&[0x00, 0xFE]).unwrap();
+    sub3.unsubscribe(&[0x00, 0xFE]).unwrap();
+
+    sleep_some();
+
+    let sent = vec![0x00, 0xFE, 0x80, 0x00];
+    publ.send(sent).unwrap();
+    let received1 = sub1.recv().unwrap();
+    let not_received2 = sub2.recv().unwrap_err();
+    let not_received3 = sub3.recv().unwrap_err();
+
+    assert_eq!(vec![0x00, 0xFE, 0x80, 0x00], received1);
+    assert_eq!(io::ErrorKind::TimedOut, not_received2.kind());
+    assert_eq!(io::ErrorKind::TimedOut, not_received3.kind());
+    drop(session);
+}
+
+#[test]
+fn shared_topic_is_kept_until_every_subscriber_left() {
+    let (session, mut publ, mut sub1, _, _) = before_each();
+    let url = urls::tcp::get();
+
+    publ.bind(&url).unwrap();
+    sub1.connect(&url).unwrap();
+
+    sub1.subscribe(b"A").unwrap();
+    sub1.subscribe(b"A").unwrap();
+    sub1.subscribe(&[0x00]).unwrap();
+    sub1.unsubscribe(b"A").unwrap();
+    assert_eq!(vec![vec![0x00], vec![65]], sub1.subscriptions().unwrap());
+
+    sleep_some();
+
+    publ.send(vec![65, 66, 67]).unwrap();
+    assert_eq!(vec![65, 66, 67], sub1.recv().unwrap());
+
+    sub1.unsubscribe(b"A").unwrap();
+    assert_eq!(vec![vec![0x00]], sub1.subscriptions().unwrap());
+
+    publ.send(vec![65, 66, 67]).unwrap();
+    let not_received = sub1.recv().unwrap_err();
+    assert_eq!(io::ErrorKind::TimedOut, not_received.kind());
+
+    let not_supported = publ.subscriptions().unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, not_supported.kind());
+    drop(session);
+}
+
+#[test]
+fn queue_messages_for_busy_subscribers() {
+    let (session, mut publ, mut sub1, _, _) = before_each();
+    let url = urls::tcp::get();
+
+    publ.set_option(ConfigOption::PubQueueDepth(100)).unwrap();
+    sub1.bind(&url).unwrap();
+    let ep = publ.connect(&url).unwrap();
+    sub1.subscribe(b"").unwrap();
+
+    sleep_some();
+
+    for i in 0..50u8 {
+        publ.send(vec![i]).unwrap();
+    }
+    for i in 0..50u8 {
+        assert_eq!(vec![i], sub1.recv().unwrap());
+    }
+
+    let drops = publ.dropped_messages().unwrap();
+    assert_eq!(1, drops.len());
+    assert_eq!(0, drops[0].dropped);
+    assert_eq!(0, drops[0].queued);
+    assert_eq!(ep.id(), drops[0].pipe);
+    drop(session);
+}
+
+#[test]
+fn queued_messages_are_sent_while_lingering() {
+    let (session, mut publ, mut sub1, _, _) = before_each();
+    let url = urls::tcp::get();
+    let linger = Duration::from_secs(5);
+
+    publ.set_option(ConfigOption::PubQueueDepth(100)).unwrap();
+    publ.set_option(ConfigOption::Linger(linger)).unwrap();
+    publ.bind(&url).unwrap();
+    sub1.connect(&url).unwrap();
+    sub1.subscribe(b"").unwrap();
+
+    sleep_some();
+
+    for i in 0..50u8 {
+        publ.send(vec![i]).unwrap();
+    }
+    drop(publ);
+
+    for i in 0..50u8 {
+        assert_eq!(vec![i], sub1.recv().unwrap());
+    }
+    drop(session);
+}
+
+#[test]
+fn count_messages_dropped_for_busy_subscribers() {
+    let (session, mut publ, mut sub1, _, _) = before_each();
+    let url = urls::tcp::get();
+
+    publ.set_option(ConfigOption::PubDropPolicy(DropPolicy::DropOldest)).unwrap();
+    publ.bind(&url).unwrap();
+    sub1.connect(&url).unwrap();
+    sub1.subscribe(b"").unwrap();
+
+    sleep_some();
+
+    for i in 0..50u8 {
+        publ.send(vec![i]).unwrap();
+    }
+    let mut received = 0;
+    while sub1.recv().is_ok() {
+        received += 1;
+    }
+
+    let drops = publ.dropped_messages().unwrap();
+    assert_eq!(1, drops.len());
+    assert_eq!(50, received + drops[0].dropped);
+    assert!(drops[0].dropped > 0);
+
+    let not_supported = sub1.dropped_messages().unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, not_supported.kind());
+    drop(session);
+}
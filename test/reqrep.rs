@@ -99,3 +99,154 @@ fn resend_the_request_silently_when_the_timeout_is_reached() {
     assert_eq!(vec![66, 65, 67], received_reply);
     drop(session);
 }
+
+#[test]
+fn receive_a_reply_that_arrived_before_recv_was_called() {
+    let (session, mut req, mut rep, url) = before_each();
+
+    rep.bind(&url).unwrap();
+    req.connect(&url).unwrap();
+
+    req.send(vec![65, 66, 67]).unwrap();
+    rep.recv().unwrap();
+    rep.send(vec![66, 65, 67]).unwrap();
+    sleep_some();
+
+    let received_reply = req.try_recv().unwrap();
+    assert_eq!(vec![66, 65, 67], received_reply);
+    drop(session);
+}
+
+#[test]
+fn raw_rep_receives_the_request_header_and_routes_the_reply_with_it() {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let mut req = session.create_socket::<Req>().unwrap();
+    let mut rep = session.create_socket::<RawRep>().unwrap();
+    let url = urls::tcp::get();
+    let timeout = make_timeout();
+
+    req.set_recv_timeout(timeout).unwrap();
+    rep.set_recv_timeout(timeout).unwrap();
+    rep.bind(&url).unwrap();
+    req.connect(&url).unwrap();
+
+    req.send(vec![65, 66, 67]).unwrap();
+    let received_request = rep.recv_msg().unwrap();
+    assert_eq!(8, received_request.get_header().len());
+    assert_eq!(0x80, received_request.get_header()[4] & 0x80);
+    assert_eq!(&[65, 66, 67], received_request.get_body());
+
+    let header = received_request.get_header().to_vec();
+    rep.send_msg(Message::from_header_and_body(header, vec![66, 65, 67])).unwrap();
+    let received_reply = req.recv().unwrap();
+    assert_eq!(vec![66, 65, 67], received_reply);
+    drop(session);
+}
+
+#[test]
+fn raw_req_sends_the_user_header_and_receives_the_reply_header() {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let mut req = session.create_socket::<RawReq>().unwrap();
+    let mut rep = session.create_socket::<Rep>().unwrap();
+    let url = urls::tcp::get();
+    let timeout = make_timeout();
+
+    req.set_recv_timeout(timeout).unwrap();
+    rep.set_recv_timeout(timeout).unwrap();
+    rep.bind(&url).unwrap();
+    req.connect(&url).unwrap();
+
+    req.send_msg(Message::from_header_and_body(vec![0x80, 0, 0, 42], vec![65, 66, 67])).unwrap();
+    let received_request = rep.recv().unwrap();
+    assert_eq!(vec![65, 66, 67], received_request);
+
+    rep.send(vec![66, 65, 67]).unwrap();
+    let received_reply = req.recv_msg().unwrap();
+    assert_eq!(&[0x80, 0, 0, 42], received_reply.get_header());
+    assert_eq!(&[66, 65, 67], received_reply.get_body());
+    drop(session);
+}
+
+#[test]
+fn req_contexts_receive_their_own_reply_whatever_the_reply_order() {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let mut req = session.create_socket::<Req>().unwrap();
+    let mut rep = session.create_socket::<RawRep>().unwrap();
+    let url = urls::tcp::get();
+    let timeout = make_timeout();
+
+    req.set_recv_timeout(timeout).unwrap();
+    rep.set_recv_timeout(timeout).unwrap();
+    rep.bind(&url).unwrap();
+    req.connect(&url).unwrap();
+
+    let mut ctx1 = req.create_context().unwrap();
+    let mut ctx2 = req.create_context().unwrap();
+
+    ctx1.send(vec![1]).unwrap();
+    ctx2.send(vec![2]).unwrap();
+
+    let mut requests = vec![rep.recv_msg().unwrap(), rep.recv_msg().unwrap()];
+    requests.sort_by_key(|msg| msg.get_body()[0]);
+
+    for request in requests.into_iter().rev() {
+        let body = vec![request.get_body()[0] * 10];
+        let header = request.get_header().to_vec();
+
+        rep.send_msg(Message::from_header_and_body(header, body)).unwrap();
+        sleep_some();
+    }
+
+    assert_eq!(vec![10], ctx1.recv().unwrap());
+    assert_eq!(vec![20], ctx2.recv().unwrap());
+    drop(session);
+}
+
+#[test]
+fn rep_contexts_reply_to_their_own_request_whatever_the_reply_order() {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let mut rep = session.create_socket::<Rep>().unwrap();
+    let mut req1 = session.create_socket::<Req>().unwrap();
+    let mut req2 = session.create_socket::<Req>().unwrap();
+    let url = urls::tcp::get();
+    let timeout = make_timeout();
+
+    rep.set_recv_timeout(timeout).unwrap();
+    rep.set_send_timeout(timeout).unwrap();
+    req1.set_recv_timeout(timeout).unwrap();
+    req2.set_recv_timeout(timeout).unwrap();
+    rep.bind(&url).unwrap();
+    req1.connect(&url).unwrap();
+    req2.connect(&url).unwrap();
+
+    let mut ctx1 = rep.create_context().unwrap();
+    let mut ctx2 = rep.create_context().unwrap();
+
+    req1.send(vec![1]).unwrap();
+    let request1 = ctx1.recv().unwrap();
+    req2.send(vec![2]).unwrap();
+    let request2 = ctx2.recv().unwrap();
+
+    ctx2.send(vec![request2[0] * 10]).unwrap();
+    ctx1.send(vec![request1[0] * 10]).unwrap();
+
+    assert_eq!(vec![request1[0] * 10], req1.recv().unwrap());
+    assert_eq!(vec![request2[0] * 10], req2.recv().unwrap());
+    drop(session);
+}
+
+#[test]
+fn refuse_to_create_a_context_on_a_socket_without_context_support() {
+    let mut session = make_session();
+    let mut rep = session.create_socket::<RawRep>().unwrap();
+
+    match rep.create_context() {
+        Err(e) => assert_eq!(io::ErrorKind::InvalidInput, e.kind()),
+        Ok(_) => panic!("RawRep should not support contexts !")
+    }
+    drop(session);
+}
@@ -0,0 +1,130 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::Duration;
+pub use std::thread;
+pub use std::io;
+
+use std::sync::Arc;
+
+pub use scaproust::*;
+
+pub use super::{urls, make_session, make_timeout, sleep_some};
+
+fn before_each() -> (Session, SharedSocket, Socket, String) {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let url = urls::tcp::get();
+    let timeout = make_timeout();
+    let push = push.into_shared();
+
+    push.set_option(ConfigOption::SendTimeout(timeout)).expect("Failed to set send timeout !");
+    pull.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+
+    (session, push, pull, url)
+}
+
+#[test]
+fn clones_send_concurrently_from_several_threads() {
+    let (session, push, mut pull, url) = before_each();
+
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+
+    let workers: Vec<_> = (0..4u8).map(|worker| {
+        let push = push.clone();
+
+        thread::spawn(move || {
+            for i in 0..5u8 {
+                push.send(vec![worker, i]).unwrap();
+            }
+        })
+    }).collect();
+
+    let mut received: Vec<Vec<u8>> = (0..20).map(|_| pull.recv().unwrap()).collect();
+
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    received.sort();
+    let expected: Vec<Vec<u8>> = (0..4u8).flat_map(|w| (0..5u8).map(move |i| vec![w, i])).collect();
+    assert_eq!(expected, received);
+    drop(push);
+    drop(session);
+}
+
+#[test]
+fn one_clone_can_be_used_by_several_threads() {
+    let (session, push, mut pull, url) = before_each();
+    let push = Arc::new(push);
+
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+
+    let workers: Vec<_> = (0..2u8).map(|worker| {
+        let push = push.clone();
+
+        thread::spawn(move || push.send(vec![worker]).unwrap())
+    }).collect();
+
+    let mut received = vec![pull.recv().unwrap(), pull.recv().unwrap()];
+
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    received.sort();
+    assert_eq!(vec![vec![0], vec![1]], received);
+    drop(push);
+    drop(session);
+}
+
+#[test]
+fn socket_stays_open_until_the_last_clone_is_dropped() {
+    let (session, push, mut pull, url) = before_each();
+    let other = push.clone();
+
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+    drop(push);
+
+    other.send(vec![65, 66, 67]).unwrap();
+    assert_eq!(vec![65, 66, 67], pull.recv().unwrap());
+    drop(other);
+    drop(session);
+}
+
+#[test]
+fn send_is_not_held_back_by_a_blocked_recv_of_another_clone() {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let pair = session.create_socket::<Pair>().expect("Failed to create socket !");
+    let mut peer = session.create_socket::<Pair>().expect("Failed to create socket !");
+    let url = urls::tcp::get();
+    let timeout = make_timeout();
+    let receiver = pair.into_shared();
+    let sender = receiver.clone();
+
+    sender.set_option(ConfigOption::SendTimeout(timeout)).expect("Failed to set send timeout !");
+    peer.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+    peer.bind(&url).unwrap();
+    receiver.connect(&url).unwrap();
+    sleep_some();
+
+    let recv_thread = thread::spawn(move || receiver.recv());
+    sleep_some();
+
+    sender.send(vec![65, 66, 67]).unwrap();
+    assert_eq!(vec![65, 66, 67], peer.recv().unwrap());
+
+    peer.send(vec![67, 66, 65]).unwrap();
+    assert_eq!(vec![67, 66, 65], recv_thread.join().unwrap().unwrap());
+    drop(sender);
+    drop(session);
+}
@@ -10,7 +10,7 @@ pub use std::io;
 
 pub use scaproust::*;
 
-pub use super::{urls, make_session, make_timeout, sleep_some};
+pub use super::{urls, make_session, make_timeout, make_hard_timeout, sleep_some};
 
 fn before_each() -> (Session, String) {
     let _ = ::env_logger::init();
@@ -176,3 +176,230 @@ fn set_option_should_support_max_recv_size() {
     assert!(req.set_option(ConfigOption::RecvMaxSize(64)).is_ok());
     assert!(rep.set_option(ConfigOption::RecvMaxSize(64)).is_ok());
 }
+
+#[test]
+fn set_option_should_support_linger() {
+    let (mut session, url) = before_each();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let linger = Duration::from_secs(5);
+    let big = vec![7u8; 8 * 1024 * 1024];
+
+    assert!(push.set_option(ConfigOption::Linger(linger)).is_ok());
+    push.set_option(ConfigOption::SendBuffer(64 * 1024 * 1024)).unwrap();
+    push.set_send_timeout(make_timeout()).unwrap();
+    pull.set_option(ConfigOption::RecvMaxSize(16 * 1024 * 1024)).unwrap();
+    pull.set_recv_timeout(Some(linger)).unwrap();
+    push.bind(&url).unwrap();
+    pull.connect(&url).unwrap();
+
+    // the peer does not read yet, so the last messages stay queued in the send buffer
+    for _ in 0..3 {
+        push.send(big.clone()).unwrap();
+    }
+    let pull_thread = thread::spawn(move || {
+        sleep_some();
+        (0..3).map(|_| pull.recv().unwrap().len()).collect::<Vec<_>>()
+    });
+
+    let start = ::std::time::Instant::now();
+    drop(push);
+    assert!(start.elapsed() < linger);
+
+    assert_eq!(vec![big.len(); 3], pull_thread.join().unwrap());
+    drop(session);
+}
+
+#[test]
+fn socket_without_pending_send_should_close_without_lingering() {
+    let (mut session, url) = before_each();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+
+    push.set_option(ConfigOption::Linger(Duration::from_secs(5))).unwrap();
+    push.bind(&url).unwrap();
+
+    let start = ::std::time::Instant::now();
+    drop(push);
+    assert!(start.elapsed() < make_hard_timeout());
+    drop(session);
+}
+
+fn make_lingering_req_rep(session: &mut Session, url: &str, linger: Duration) -> (Socket, Socket) {
+    let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+    let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+
+    req.set_option(ConfigOption::Linger(linger)).unwrap();
+    req.set_option(ConfigOption::ReqResendIvl(Duration::from_millis(100))).unwrap();
+    rep.set_option(ConfigOption::RecvMaxSize(64 * 1024 * 1024)).unwrap();
+    rep.set_recv_timeout(Some(Duration::from_secs(5))).unwrap();
+    rep.bind(url).unwrap();
+    req.connect(url).unwrap();
+
+    (req, rep)
+}
+
+#[test]
+fn req_resend_pending_at_close_should_be_flushed_while_lingering() {
+    let (mut session, url) = before_each();
+    let linger = Duration::from_secs(5);
+    let (mut req, mut rep) = make_lingering_req_rep(&mut session, &url, linger);
+    let big = vec![7u8; 32 * 1024 * 1024];
+
+    // the request is resent while the peer does not read, and is still being written on close
+    let rep_thread = thread::spawn(move || {
+        let request = rep.recv().unwrap().len();
+        sleep_some();
+        sleep_some();
+        let resent = rep.recv().unwrap().len();
+
+        (request, resent)
+    });
+    req.send(big.clone()).unwrap();
+    sleep_some();
+
+    let start = ::std::time::Instant::now();
+    drop(req);
+    assert!(start.elapsed() < linger);
+
+    assert_eq!((big.len(), big.len()), rep_thread.join().unwrap());
+    drop(session);
+}
+
+#[test]
+fn req_resend_pending_at_close_should_be_dropped_once_linger_expires() {
+    let (mut session, url) = before_each();
+    let linger = Duration::from_millis(500);
+    let (mut req, mut rep) = make_lingering_req_rep(&mut session, &url, linger);
+    let big = vec![7u8; 32 * 1024 * 1024];
+
+    // the resent request is never read, so the linger deadline is hit
+    let rep_thread = thread::spawn(move || {
+        let request = rep.recv().unwrap().len();
+
+        (rep, request)
+    });
+    req.send(big.clone()).unwrap();
+    let (mut rep, request) = rep_thread.join().unwrap();
+    assert_eq!(big.len(), request);
+    sleep_some();
+
+    let start = ::std::time::Instant::now();
+    drop(req);
+    let elapsed = start.elapsed();
+    assert!(elapsed >= linger);
+    assert!(elapsed < linger + make_hard_timeout());
+
+    rep.set_recv_timeout(make_timeout()).unwrap();
+    assert!(rep.recv().is_err());
+    drop(session);
+}
+
+#[test]
+fn closed_endpoint_should_not_be_reconnected() {
+    let (mut session, url) = before_each();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+    let ep = push.connect(&url).unwrap();
+    sleep_some();
+    ep.close().unwrap();
+
+    pull.bind(&url).unwrap();
+    thread::sleep(Duration::from_millis(300));
+
+    let err = push.try_send(vec![65, 66, 67]).unwrap_err();
+    assert_eq!(io::ErrorKind::WouldBlock, err.kind());
+    drop(session);
+}
+
+#[test]
+fn readiness_follows_the_ability_to_recv() {
+    let (mut session, url) = before_each();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let readiness = pull.readiness().unwrap();
+    let poll = mio::Poll::new().unwrap();
+    let mut events = mio::Events::with_capacity(4);
+    let token = mio::Token(7);
+
+    poll.register(&readiness, token, mio::Ready::readable(), mio::PollOpt::level()).unwrap();
+    push.set_send_timeout(make_timeout()).unwrap();
+    push.bind(&url).unwrap();
+    pull.connect(&url).unwrap();
+
+    poll.poll(&mut events, Some(make_hard_timeout())).unwrap();
+    assert!(events.is_empty());
+
+    push.send(vec![65, 66, 67]).unwrap();
+    poll.poll(&mut events, Some(make_hard_timeout())).unwrap();
+    let event = events.iter().next().expect("Readiness should be readable");
+    assert_eq!(token, event.token());
+    assert!(event.readiness().is_readable());
+
+    assert_eq!(vec![65, 66, 67], pull.try_recv().unwrap());
+    poll.poll(&mut events, Some(Duration::from_millis(50))).unwrap();
+    assert!(events.is_empty());
+    drop(session);
+}
+
+#[test]
+fn endpoints_can_be_queried_for_their_addresses_and_state() {
+    let (mut session, _) = before_each();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+    let bound = pull.bind("tcp://127.0.0.1:0").unwrap();
+    let bound_addr = bound.local_addr().unwrap().expect("Bound endpoint should have a local address");
+    assert!(!bound_addr.ends_with(":0"));
+    assert_eq!(None, bound.remote_addr().unwrap());
+    assert_eq!(EndpointState::Active, bound.state().unwrap());
+    assert_eq!("tcp://127.0.0.1:0", bound.url().unwrap());
+
+    let url = format!("tcp://{}", bound_addr);
+    let connected = push.connect(&url).unwrap();
+    sleep_some();
+    assert_eq!(EndpointState::Active, connected.state().unwrap());
+    assert_eq!(Some(bound_addr), connected.remote_addr().unwrap());
+    assert!(connected.local_addr().unwrap().is_some());
+    assert_eq!(url, connected.url().unwrap());
+
+    connected.close().unwrap();
+    drop(session);
+}
+
+#[test]
+fn bound_endpoints_list_the_peer_address_of_accepted_pipes() {
+    let (mut session, _) = before_each();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+    let bound = pull.bind("tcp://127.0.0.1:0").unwrap();
+    let bound_addr = bound.local_addr().unwrap().expect("Bound endpoint should have a local address");
+    assert!(bound.accepted_pipes().unwrap().is_empty());
+
+    let connected = push.connect(&format!("tcp://{}", bound_addr)).unwrap();
+    sleep_some();
+    let client_addr = connected.local_addr().unwrap();
+    assert!(client_addr.is_some());
+
+    let accepted = bound.accepted_pipes().unwrap();
+    assert_eq!(1, accepted.len());
+    assert_eq!(client_addr, accepted[0].remote_addr);
+    assert!(connected.accepted_pipes().unwrap().is_empty());
+
+    connected.close().unwrap();
+    drop(session);
+}
+
+#[test]
+fn endpoint_state_is_backoff_while_waiting_to_reconnect() {
+    let (mut session, url) = before_each();
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+
+    push.set_option(ConfigOption::RetryIvl(Duration::from_millis(500))).unwrap();
+    let ep = push.connect(&url).unwrap();
+    sleep_some();
+    assert_eq!(EndpointState::Backoff, ep.state().unwrap());
+    assert_eq!(None, ep.remote_addr().unwrap());
+    drop(session);
+}
@@ -81,3 +81,136 @@ fn refuse_to_send_a_response_before_receiving_a_survey() {
     assert_eq!(io::ErrorKind::InvalidData, not_sent.kind());
     drop(session);
 }
+
+#[test]
+fn overlapping_surveys_from_contexts_receive_their_own_responses() {
+    let (session, mut surv, mut resp1, _, url) = before_each();
+    surv.bind(&url).unwrap();
+    resp1.connect(&url).unwrap();
+
+    sleep_some();
+
+    let mut ctx1 = surv.create_context().unwrap();
+    let mut ctx2 = surv.create_context().unwrap();
+
+    ctx1.send(vec![1]).expect("Context 1 should have sent a survey");
+    sleep_some();
+    ctx2.send(vec![2]).expect("Context 2 should have sent a survey");
+
+    let survey1 = resp1.recv().expect("Respondent should have received survey #1");
+    resp1.send(vec![survey1[0] * 10]).expect("Respondent should have sent response #1");
+    let survey2 = resp1.recv().expect("Respondent should have received survey #2");
+    resp1.send(vec![survey2[0] * 10]).expect("Respondent should have sent response #2");
+
+    assert_eq!(vec![20], ctx2.recv().expect("Context 2 should have received its response"));
+    assert_eq!(vec![10], ctx1.recv().expect("Context 1 should have received its response"));
+    drop(session);
+}
+
+#[test]
+fn survey_collects_every_response_until_the_deadline() {
+    let (session, mut surv, mut resp1, mut resp2, url) = before_each();
+    surv.set_option(ConfigOption::SurveyDeadline(Duration::from_millis(500))).unwrap();
+    surv.bind(&url).unwrap();
+    resp1.connect(&url).unwrap();
+    resp2.connect(&url).unwrap();
+
+    sleep_some();
+
+    let respondents_thread = thread::spawn(move || {
+        let survey1 = resp1.recv().expect("Respondent 1 should have received a survey");
+        resp1.send(vec![survey1[0] + 1]).expect("Respondent 1 should have sent a response");
+        let survey2 = resp2.recv().expect("Respondent 2 should have received a survey");
+        resp2.send(vec![survey2[0] + 2]).expect("Respondent 2 should have sent a response");
+        (resp1, resp2)
+    });
+
+    let collected = surv.survey(vec![40]).expect("Surveyor should have collected the responses");
+    let mut bodies: Vec<Vec<u8>> = collected.responses.into_iter().map(|msg| msg.into()).collect();
+    bodies.sort();
+
+    assert_eq!(2, collected.surveyed);
+    assert_eq!(vec![vec![41], vec![42]], bodies);
+
+    let _ = respondents_thread.join().unwrap();
+    drop(session);
+}
+
+#[test]
+fn raw_respondent_receives_the_survey_header_and_routes_the_response_with_it() {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let mut surv = session.create_socket::<Surveyor>().unwrap();
+    let mut resp = session.create_socket::<RawRespondent>().unwrap();
+    let url = urls::tcp::get();
+    let timeout = make_timeout();
+
+    surv.set_recv_timeout(timeout).unwrap();
+    resp.set_recv_timeout(timeout).unwrap();
+    surv.bind(&url).unwrap();
+    resp.connect(&url).unwrap();
+    sleep_some();
+
+    surv.send(vec![65, 66, 67]).unwrap();
+    let received_survey = resp.recv_msg().unwrap();
+    assert_eq!(8, received_survey.get_header().len());
+    assert_eq!(0x80, received_survey.get_header()[4] & 0x80);
+    assert_eq!(&[65, 66, 67], received_survey.get_body());
+
+    let header = received_survey.get_header().to_vec();
+    resp.send_msg(Message::from_header_and_body(header, vec![66, 65, 67])).unwrap();
+    let received_response = surv.recv().unwrap();
+    assert_eq!(vec![66, 65, 67], received_response);
+    drop(session);
+}
+
+#[test]
+fn raw_surveyor_sends_the_user_header_and_receives_the_response_header() {
+    let _ = ::env_logger::init();
+    let mut session = make_session();
+    let mut surv = session.create_socket::<RawSurveyor>().unwrap();
+    let mut resp = session.create_socket::<Respondent>().unwrap();
+    let url = urls::tcp::get();
+    let timeout = make_timeout();
+
+    surv.set_recv_timeout(timeout).unwrap();
+    resp.set_recv_timeout(timeout).unwrap();
+    surv.bind(&url).unwrap();
+    resp.connect(&url).unwrap();
+    sleep_some();
+
+    surv.send_msg(Message::from_header_and_body(vec![0x80, 0, 0, 42], vec![65, 66, 67])).unwrap();
+    let received_survey = resp.recv().unwrap();
+    assert_eq!(vec![65, 66, 67], received_survey);
+
+    resp.send(vec![66, 65, 67]).unwrap();
+    let received_response = surv.recv_msg().unwrap();
+    assert_eq!(&[0x80, 0, 0, 42], received_response.get_header());
+    assert_eq!(&[66, 65, 67], received_response.get_body());
+    drop(session);
+}
+
+#[test]
+fn raw_survey_sockets_refuse_contexts_and_survey() {
+    let mut session = make_session();
+    let mut surv = session.create_socket::<RawSurveyor>().unwrap();
+    let mut resp = session.create_socket::<RawRespondent>().unwrap();
+
+    match surv.create_context() {
+        Err(e) => assert_eq!(io::ErrorKind::InvalidInput, e.kind()),
+        Ok(_) => panic!("RawSurveyor should not support contexts !")
+    }
+    match resp.create_context() {
+        Err(e) => assert_eq!(io::ErrorKind::InvalidInput, e.kind()),
+        Ok(_) => panic!("RawRespondent should not support contexts !")
+    }
+    match surv.survey(vec![65, 66, 67]) {
+        Err(e) => assert_eq!(io::ErrorKind::InvalidInput, e.kind()),
+        Ok(_) => panic!("RawSurveyor should not support survey !")
+    }
+    match resp.survey(vec![65, 66, 67]) {
+        Err(e) => assert_eq!(io::ErrorKind::InvalidInput, e.kind()),
+        Ok(_) => panic!("RawRespondent should not support survey !")
+    }
+    drop(session);
+}
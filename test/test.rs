@@ -8,6 +8,11 @@ extern crate log;
 extern crate env_logger;
 extern crate scaproust;
 extern crate rand;
+extern crate mio;
+#[cfg(feature = "tls")]
+extern crate rustls;
+#[cfg(feature = "tls")]
+extern crate rustls_pemfile;
 
 mod socket;
 mod fair_queue;
@@ -20,6 +25,13 @@ mod bus;
 mod device;
 mod reqrep_device;
 mod probe;
+mod inproc;
+mod ws;
+mod future;
+mod typed;
+mod shared;
+#[cfg(feature = "tls")]
+mod tls;
 
 pub use std::time::Duration;
 pub use std::thread;
@@ -52,6 +64,8 @@ pub fn make_session() -> Session {
     SessionBuilder::new().
         with("tcp", Tcp).
         with("ipc", Ipc).
+        with("inproc", Inproc).
+        with("ws", Ws).
         build().
         expect("Failed to create session !")
 }
@@ -82,6 +96,18 @@ pub mod urls {
         }
     }
 
+    pub mod tls {
+        pub fn get() -> String {
+            format!("tls+tcp://127.0.0.1:{}", super::next_port())
+        }
+    }
+
+    pub mod ws {
+        pub fn get() -> String {
+            format!("ws://127.0.0.1:{}", super::next_port())
+        }
+    }
+
     pub mod ipc {
 
         use rand;
@@ -102,6 +128,19 @@ pub mod urls {
         }
 
     }
+
+    pub mod inproc {
+
+        use rand;
+        use rand::Rng;
+
+        pub fn get() -> String {
+            let num: u64 = rand::thread_rng().gen();
+
+            format!("inproc://test_{}", num)
+        }
+
+    }
 }
 
 #[test]
@@ -0,0 +1,279 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::io;
+pub use std::io::Write;
+pub use std::net;
+pub use std::sync::Arc;
+
+pub use scaproust::*;
+
+use rustls;
+use rustls::pki_types::ServerName;
+use rustls_pemfile;
+
+pub use super::{urls, make_timeout, sleep_some};
+
+const CA: &[u8] = include_bytes!("certs/ca.pem");
+const SERVER_CERT: &[u8] = include_bytes!("certs/server.pem");
+const SERVER_KEY: &[u8] = include_bytes!("certs/server.key");
+const CLIENT_CERT: &[u8] = include_bytes!("certs/client.pem");
+const CLIENT_KEY: &[u8] = include_bytes!("certs/client.key");
+const ROGUE_CERT: &[u8] = include_bytes!("certs/rogue.pem");
+const ROGUE_KEY: &[u8] = include_bytes!("certs/rogue.key");
+
+fn before_each() -> String {
+    let _ = ::env_logger::init();
+
+    urls::tls::get()
+}
+
+fn make_tls_session(cert: &[u8], key: &[u8], client_ca: Option<&[u8]>) -> Session {
+    let tls = Tls::new(cert, key, client_ca).unwrap().trust(CA).unwrap();
+
+    SessionBuilder::new().
+        with("tls+tcp", tls).
+        build().
+        expect("Failed to create session !")
+}
+
+fn make_server_session() -> Session {
+    make_tls_session(SERVER_CERT, SERVER_KEY, None)
+}
+
+fn make_client_session() -> Session {
+    make_tls_session(CLIENT_CERT, CLIENT_KEY, None)
+}
+
+fn make_anonymous_client_session() -> Session {
+    SessionBuilder::new().
+        with("tls+tcp", Tls::client().trust(CA).unwrap()).
+        build().
+        expect("Failed to create session !")
+}
+
+/// Connects a plain rustls client, to control how messages are split into records.
+fn connect_raw_client(url: &str) -> rustls::StreamOwned<rustls::ClientConnection, net::TcpStream> {
+    let addr: net::SocketAddr = url.trim_start_matches("tls+tcp://").parse().unwrap();
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut &CA[..]) {
+        roots.add(cert.unwrap()).unwrap();
+    }
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let config = rustls::ClientConfig::builder_with_provider(provider).
+        with_safe_default_protocol_versions().unwrap().
+        with_root_certificates(roots).
+        with_no_client_auth();
+    let conn = rustls::ClientConnection::new(Arc::new(config), ServerName::from(addr.ip())).unwrap();
+    let sock = net::TcpStream::connect(addr).unwrap();
+
+    rustls::StreamOwned::new(conn, sock)
+}
+
+fn append_frame(buffer: &mut Vec<u8>, payload: &[u8]) {
+    let len = payload.len() as u64;
+
+    for i in 0..8 {
+        buffer.push((len >> (56 - 8 * i)) as u8);
+    }
+    buffer.extend_from_slice(payload);
+}
+
+#[test]
+fn send_a_message_through_bound_endpoint() {
+    let url = before_each();
+    let mut server = make_server_session();
+    let mut client = make_client_session();
+    let mut pull = server.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut push = client.create_socket::<Push>().expect("Failed to create socket !");
+
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    push.set_send_timeout(make_timeout()).unwrap();
+
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+
+    push.send(vec![65, 66, 67]).unwrap();
+    let received = pull.recv().unwrap();
+
+    assert_eq!(vec![65, 66, 67], received);
+}
+
+#[test]
+fn send_a_request_and_receive_a_reply() {
+    let url = before_each();
+    let mut server = make_server_session();
+    let mut client = make_client_session();
+    let mut rep = server.create_socket::<Rep>().expect("Failed to create socket !");
+    let mut req = client.create_socket::<Req>().expect("Failed to create socket !");
+
+    req.set_send_timeout(make_timeout()).unwrap();
+    req.set_recv_timeout(make_timeout()).unwrap();
+    rep.set_send_timeout(make_timeout()).unwrap();
+    rep.set_recv_timeout(make_timeout()).unwrap();
+
+    rep.bind(&url).unwrap();
+    req.connect(&url).unwrap();
+
+    req.send(vec![65, 66, 67]).unwrap();
+    let request = rep.recv().unwrap();
+    assert_eq!(vec![65, 66, 67], request);
+
+    rep.send(vec![67, 66, 65]).unwrap();
+    let reply = req.recv().unwrap();
+    assert_eq!(vec![67, 66, 65], reply);
+}
+
+#[test]
+fn send_several_large_messages() {
+    let url = before_each();
+    let mut server = make_server_session();
+    let mut client = make_client_session();
+    let mut pull = server.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut push = client.create_socket::<Push>().expect("Failed to create socket !");
+    let big = vec![7u8; 200 * 1024];
+
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    push.set_send_timeout(make_timeout()).unwrap();
+
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+
+    push.send(big.clone()).unwrap();
+    push.send(vec![65, 66, 67]).unwrap();
+
+    assert_eq!(big, pull.recv().unwrap());
+    assert_eq!(vec![65, 66, 67], pull.recv().unwrap());
+}
+
+#[test]
+fn client_with_a_certificate_is_accepted_when_required() {
+    let url = before_each();
+    let mut server = make_tls_session(SERVER_CERT, SERVER_KEY, Some(CA));
+    let mut client = make_client_session();
+    let mut pull = server.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut push = client.create_socket::<Push>().expect("Failed to create socket !");
+
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    push.set_send_timeout(make_timeout()).unwrap();
+
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+
+    push.send(vec![65, 66, 67]).unwrap();
+    let received = pull.recv().unwrap();
+
+    assert_eq!(vec![65, 66, 67], received);
+}
+
+#[test]
+fn client_without_certificate_is_accepted_when_not_required() {
+    let url = before_each();
+    let mut server = make_server_session();
+    let mut client = make_anonymous_client_session();
+    let mut pull = server.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut push = client.create_socket::<Push>().expect("Failed to create socket !");
+
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    push.set_send_timeout(make_timeout()).unwrap();
+
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+
+    push.send(vec![65, 66, 67]).unwrap();
+    let received = pull.recv().unwrap();
+
+    assert_eq!(vec![65, 66, 67], received);
+}
+
+#[test]
+fn client_without_certificate_is_refused_when_required() {
+    let url = before_each();
+    let mut server = make_tls_session(SERVER_CERT, SERVER_KEY, Some(CA));
+    let mut client = make_anonymous_client_session();
+    let mut pull = server.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut push = client.create_socket::<Push>().expect("Failed to create socket !");
+
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+    sleep_some();
+
+    let err = push.try_send(vec![65, 66, 67]).unwrap_err();
+    assert_eq!(io::ErrorKind::WouldBlock, err.kind());
+}
+
+#[test]
+fn server_is_authenticated_against_the_host_name_of_the_url() {
+    let url = before_each();
+    let mut server = make_server_session();
+    let mut client = make_client_session();
+    let mut pull = server.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut push = client.create_socket::<Push>().expect("Failed to create socket !");
+
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    push.set_send_timeout(make_timeout()).unwrap();
+
+    pull.bind(&url).unwrap();
+    push.connect(&url.replace("127.0.0.1", "localhost")).unwrap();
+
+    push.send(vec![65, 66, 67]).unwrap();
+    let received = pull.recv().unwrap();
+
+    assert_eq!(vec![65, 66, 67], received);
+}
+
+#[test]
+fn client_with_an_unknown_certificate_is_refused_when_required() {
+    let url = before_each();
+    let mut server = make_tls_session(SERVER_CERT, SERVER_KEY, Some(CA));
+    let mut client = make_tls_session(ROGUE_CERT, ROGUE_KEY, None);
+    let mut pull = server.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut push = client.create_socket::<Push>().expect("Failed to create socket !");
+
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+    sleep_some();
+
+    let err = push.try_send(vec![65, 66, 67]).unwrap_err();
+    assert_eq!(io::ErrorKind::WouldBlock, err.kind());
+}
+
+#[test]
+fn server_with_an_unknown_certificate_is_refused() {
+    let url = before_each();
+    let mut server = make_tls_session(ROGUE_CERT, ROGUE_KEY, None);
+    let mut client = make_client_session();
+    let mut pull = server.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut push = client.create_socket::<Push>().expect("Failed to create socket !");
+
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+    sleep_some();
+
+    let err = push.try_send(vec![65, 66, 67]).unwrap_err();
+    assert_eq!(io::ErrorKind::WouldBlock, err.kind());
+}
+
+#[test]
+fn receive_messages_written_in_a_single_record() {
+    let url = before_each();
+    let mut server = make_server_session();
+    let mut pull = server.create_socket::<Pull>().expect("Failed to create socket !");
+
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    pull.bind(&url).unwrap();
+
+    // the SP handshake of a push socket, followed by two messages, all in one record
+    let mut client = connect_raw_client(&url);
+    let mut record = vec![0, 83, 80, 0, 0, 80, 0, 0];
+    append_frame(&mut record, &[65, 66, 67]);
+    append_frame(&mut record, &[68, 69, 70]);
+    client.write_all(&record).unwrap();
+    client.flush().unwrap();
+
+    assert_eq!(vec![65, 66, 67], pull.recv().unwrap());
+    assert_eq!(vec![68, 69, 70], pull.recv().unwrap());
+}
@@ -0,0 +1,109 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::Duration;
+pub use std::thread;
+pub use std::io;
+
+pub use scaproust::*;
+
+pub use super::{urls, make_session, make_timeout, sleep_some};
+
+fn before_each() -> (Session, String) {
+    let _ = ::env_logger::init();
+    let session = make_session();
+    let url = urls::tcp::get();
+
+    (session, url)
+}
+
+#[test]
+fn typed_pub_sub_filter_messages() {
+    let (mut session, url) = before_each();
+    let mut publ = session.create_typed_socket::<PubSocket>().expect("Failed to create socket !");
+    let mut sub = session.create_typed_socket::<SubSocket>().expect("Failed to create socket !");
+
+    publ.set_send_timeout(make_timeout()).unwrap();
+    publ.set_queue_depth(2).unwrap();
+    sub.set_recv_timeout(make_timeout()).unwrap();
+    sub.subscribe(b"A").unwrap();
+    assert_eq!(vec![b"A".to_vec()], sub.subscriptions().unwrap());
+
+    publ.bind(&url).unwrap();
+    sub.connect(&url).unwrap();
+    sleep_some();
+
+    publ.send(vec![66, 66]).unwrap();
+    publ.send(vec![65, 66]).unwrap();
+
+    assert_eq!(vec![65, 66], sub.recv().unwrap());
+    drop(session);
+}
+
+#[test]
+fn typed_req_sends_a_request_and_gets_its_reply() {
+    let (mut session, url) = before_each();
+    let mut req = session.create_typed_socket::<ReqSocket>().expect("Failed to create socket !");
+    let mut rep = session.create_typed_socket::<RepSocket>().expect("Failed to create socket !");
+
+    req.set_recv_timeout(make_timeout()).unwrap();
+    req.set_resend_ivl(Duration::from_secs(5)).unwrap();
+    rep.set_recv_timeout(make_timeout()).unwrap();
+
+    rep.bind(&url).unwrap();
+    req.connect(&url).unwrap();
+
+    let server = thread::spawn(move || {
+        let request = rep.recv().unwrap();
+
+        rep.send(request.into_iter().rev().collect()).unwrap();
+        rep
+    });
+
+    let reply = req.request(vec![65, 66, 67]).unwrap();
+
+    assert_eq!(vec![67, 66, 65], reply);
+    drop(server.join().unwrap());
+    drop(session);
+}
+
+#[test]
+fn typed_socket_can_be_turned_back_into_an_untyped_one() {
+    let (mut session, url) = before_each();
+    let push = session.create_typed_socket::<PushSocket>().expect("Failed to create socket !");
+    let mut pull = session.create_typed_socket::<PullSocket>().expect("Failed to create socket !");
+    let mut push = Socket::from(push);
+
+    push.set_send_timeout(make_timeout()).unwrap();
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+
+    push.send(vec![65, 66, 67]).unwrap();
+
+    assert_eq!(vec![65, 66, 67], pull.recv().unwrap());
+    drop(session);
+}
+
+#[test]
+fn typed_socket_reconnects_with_its_retry_intervals() {
+    let (mut session, url) = before_each();
+    let mut push = session.create_typed_socket::<PushSocket>().expect("Failed to create socket !");
+    let mut pull = session.create_typed_socket::<PullSocket>().expect("Failed to create socket !");
+
+    push.set_retry_ivl(Duration::from_millis(20)).unwrap();
+    push.set_retry_ivl_max(Some(Duration::from_millis(40))).unwrap();
+    push.set_send_timeout(make_timeout()).unwrap();
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    push.connect(&url).unwrap();
+    sleep_some();
+    pull.bind(&url).unwrap();
+
+    push.send(vec![65, 66, 67]).unwrap();
+
+    assert_eq!(vec![65, 66, 67], pull.recv().unwrap());
+    drop(session);
+}
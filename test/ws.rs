@@ -0,0 +1,110 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::io;
+
+pub use scaproust::*;
+
+pub use super::{urls, make_session, make_timeout, sleep_some};
+
+fn before_each() -> (Session, String) {
+    let _ = ::env_logger::init();
+    let session = make_session();
+    let url = urls::ws::get();
+
+    (session, url)
+}
+
+#[test]
+fn send_a_message_through_bound_endpoint() {
+    let (mut session, url) = before_each();
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+
+    pull.set_recv_timeout(make_timeout()).unwrap();
+    push.set_send_timeout(make_timeout()).unwrap();
+
+    pull.bind(&url).unwrap();
+    push.connect(&url).unwrap();
+
+    push.send(vec![65, 66, 67]).unwrap();
+    let received = pull.recv().unwrap();
+
+    assert_eq!(vec![65, 66, 67], received);
+}
+
+#[test]
+fn send_a_request_and_receive_a_reply() {
+    let (mut session, url) = before_each();
+    let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+    let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+
+    req.set_send_timeout(make_timeout()).unwrap();
+    req.set_recv_timeout(make_timeout()).unwrap();
+    rep.set_send_timeout(make_timeout()).unwrap();
+    rep.set_recv_timeout(make_timeout()).unwrap();
+
+    rep.bind(&url).unwrap();
+    req.connect(&url).unwrap();
+
+    req.send(vec![65, 66, 67]).unwrap();
+    let request = rep.recv().unwrap();
+    assert_eq!(vec![65, 66, 67], request);
+
+    rep.send(vec![67, 66, 65]).unwrap();
+    let reply = req.recv().unwrap();
+    assert_eq!(vec![67, 66, 65], reply);
+}
+
+#[test]
+fn send_large_messages_in_both_directions() {
+    let (mut session, url) = before_each();
+    let mut left = session.create_socket::<Pair>().expect("Failed to create socket !");
+    let mut right = session.create_socket::<Pair>().expect("Failed to create socket !");
+    let big = vec![7u8; 100 * 1024];
+
+    left.set_send_timeout(make_timeout()).unwrap();
+    left.set_recv_timeout(make_timeout()).unwrap();
+    right.set_send_timeout(make_timeout()).unwrap();
+    right.set_recv_timeout(make_timeout()).unwrap();
+
+    left.bind(&format!("{}/pair", url)).unwrap();
+    right.connect(&format!("{}/pair", url)).unwrap();
+
+    right.send(big.clone()).unwrap();
+    assert_eq!(big, left.recv().unwrap());
+
+    left.send(big.clone()).unwrap();
+    assert_eq!(big, right.recv().unwrap());
+}
+
+#[test]
+fn mismatching_protocols_are_not_connected() {
+    let (mut session, url) = before_each();
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut publ = session.create_socket::<Pub>().expect("Failed to create socket !");
+
+    pull.bind(&url).unwrap();
+    publ.connect(&url).unwrap();
+    sleep_some();
+
+    let err = publ.try_send(vec![65, 66, 67]).unwrap_err();
+    assert_eq!(io::ErrorKind::WouldBlock, err.kind());
+}
+
+#[test]
+fn mismatching_paths_are_not_connected() {
+    let (mut session, url) = before_each();
+    let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+    let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+
+    pull.bind(&format!("{}/left", url)).unwrap();
+    push.connect(&format!("{}/right", url)).unwrap();
+    sleep_some();
+
+    let err = push.try_send(vec![65, 66, 67]).unwrap_err();
+    assert_eq!(io::ErrorKind::WouldBlock, err.kind());
+}